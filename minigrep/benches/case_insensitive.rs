@@ -0,0 +1,68 @@
+// 大小写不敏感搜索的性能对比：cargo bench
+//
+// 对比对象是最初的实现：每一行都 to_lowercase 成新的 String 再 contains。
+// 稳定版 Rust 还不能使用 #[bench]，这里用 Instant 简单计时，每种情况重复若干轮取最快的一次。
+//
+// 某次运行的结果（MB/s，越大越好）：
+//     ascii, frequent      to_lowercase  731   casefold  740
+//     ascii, absent        to_lowercase  740   casefold  721
+//     unicode, frequent    to_lowercase   88   casefold  124
+//     unicode, absent      to_lowercase   89   casefold  101
+// ASCII 查询串走逐字节比较的快速路径，和转小写后用 str::contains 查找相当；其他情况下不再需要为每行分配内存。
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use chry_minigrep::casefold::Finder;
+
+// 最初的实现（只统计匹配的行数，避免把收集结果的开销算进去）
+fn count_lowercase(query: &str, contents: &str) -> usize {
+    let query = query.to_lowercase();
+    contents.lines().filter(|line| line.to_lowercase().contains(&query)).count()
+}
+
+fn count_folded(finder: &Finder, contents: &str) -> usize {
+    contents.lines().filter(|line| finder.find_at(line.as_bytes(), 0).is_some()).count()
+}
+
+fn fastest(mut f: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut result = 0;
+    for _ in 0..10 {
+        let start = Instant::now();
+        result = black_box(f());
+        best = best.min(start.elapsed());
+    }
+    (best, result)
+}
+
+fn bench(name: &str, query: &str, contents: &str) {
+    let finder = Finder::new(query);
+    let (old, old_count) = fastest(|| count_lowercase(black_box(query), black_box(contents)));
+    let (new, new_count) = fastest(|| count_folded(black_box(&finder), black_box(contents)));
+    let mb = contents.len() as f64 / (1024.0 * 1024.0);
+    println!(
+        "{:<24} to_lowercase {:>8.1} MB/s ({} lines)   casefold {:>8.1} MB/s ({} lines)   {:.2}x",
+        name,
+        mb / old.as_secs_f64(),
+        old_count,
+        mb / new.as_secs_f64(),
+        new_count,
+        old.as_secs_f64() / new.as_secs_f64(),
+    );
+}
+
+fn main() {
+    let ascii = "2024-05-01 12:00:01 INFO  request handled in 12ms path=/api/v1/users status=200\n\
+                 2024-05-01 12:00:02 WARN  slow query detected table=orders duration=1532ms\n\
+                 2024-05-01 12:00:03 Error connection reset by peer remote=10.0.0.7:5432\n"
+        .repeat(20_000);
+    let unicode = "Straße und Fluß — ΣΟΦΟΣ σοφος, Привет мир, 你好世界, ſtrange Kelvin \u{212A}\n\
+                   Ничего интересного здесь нет, просто длинная строка текста для поиска\n"
+        .repeat(20_000);
+
+    bench("ascii, frequent", "error", &ascii);
+    bench("ascii, absent", "timeout", &ascii);
+    bench("unicode, frequent", "привет", &unicode);
+    bench("unicode, absent", "σοφία", &unicode);
+}
@@ -0,0 +1,318 @@
+// 多个固定字符串的同时查找：Aho-Corasick 自动机。
+//
+// 所有模式先建成一棵字典树，再用 BFS 为每个状态计算失败链接，并把缺失的转移直接填成“沿失败链接能到达的转移”，
+// 得到一个确定的有限自动机（DFA）。搜索时每个输入字节只需要查一次表，总时间和输入长度成正比，与模式的个数无关。
+// 为了减小转移表，只出现在模式中的字节各自占一个等价类，其他所有字节共用一个类。
+//
+// 和 grep 一样采用“最左最长”的匹配语义：在所有匹配中选起点最靠左的，起点相同时选最长的。
+// find_accepted 只考虑调用者接受的候选（例如 -w 要求前后不是单词字符），所以要看到在每个位置结尾的所有模式：
+// 每个状态记下失败链上最近的模式结尾，从那里再沿失败链往下就能依次得到更短的、也在这里结尾的模式。
+// 大小写不敏感时，输入逐个字符折叠（casefold::fold）后再送入自动机，返回的区间仍然是原始文本中的位置。
+
+use crate::casefold;
+use crate::utf8;
+
+/// 同时查找多个固定字符串的自动机
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    // 字节 → 等价类，0 表示没有出现在任何模式中的字节
+    classes: Box<[u16; 256]>,
+    class_count: usize,
+    // 转移表：状态 s 在等价类 c 上的下一个状态为 delta[s * class_count + c]，状态 0 是根
+    delta: Vec<u32>,
+    // 每个状态对应的字符串的长度（字节数和字符数）
+    depth: Vec<State>,
+    // 失败链接：当前字符串的最长真后缀所在的状态
+    fail: Vec<u32>,
+    // 失败链上（包括自己）最近的模式结尾的状态，也就是在这里结尾的最长模式；没有时为根 0
+    output: Vec<u32>,
+    // 有空模式时每个位置都能匹配
+    has_empty: bool,
+    case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct State {
+    bytes: usize,
+    chars: usize,
+}
+
+impl AhoCorasick {
+    /// 用一组模式创建自动机。`case_insensitive` 为 true 时按 Unicode 简单大小写折叠比较。
+    pub fn new<S: AsRef<str>>(patterns: &[S], case_insensitive: bool) -> AhoCorasick {
+        let patterns: Vec<String> = patterns
+            .iter()
+            .map(|p| match case_insensitive {
+                true => p.as_ref().chars().map(casefold::fold).collect(),
+                false => p.as_ref().to_string(),
+            })
+            .collect();
+
+        let mut classes = Box::new([0u16; 256]);
+        let mut class_count = 1;
+        for &b in patterns.iter().flat_map(|p| p.as_bytes()) {
+            if classes[b as usize] == 0 {
+                classes[b as usize] = class_count as u16;
+                class_count += 1;
+            }
+        }
+
+        // 先建字典树，不存在的转移用 NONE 表示
+        const NONE: u32 = u32::MAX;
+        let mut delta = vec![NONE; class_count];
+        let mut depth = vec![State::default()];
+        let mut output = vec![0u32];
+        let mut has_empty = false;
+        for pattern in &patterns {
+            if pattern.is_empty() {
+                has_empty = true;
+                continue;
+            }
+            let mut state = 0;
+            let mut chars = 0;
+            for (i, &b) in pattern.as_bytes().iter().enumerate() {
+                // UTF-8 中不是续字节（0b10xxxxxx）的字节都是一个字符的开头
+                if b & 0xC0 != 0x80 {
+                    chars += 1;
+                }
+                let slot = state * class_count + classes[b as usize] as usize;
+                state = match delta[slot] {
+                    NONE => {
+                        let next = depth.len();
+                        delta[slot] = next as u32;
+                        delta.extend(std::iter::repeat_n(NONE, class_count));
+                        depth.push(State { bytes: i + 1, chars });
+                        output.push(0);
+                        next
+                    }
+                    next => next as usize,
+                };
+            }
+            output[state] = state as u32;
+        }
+
+        // BFS 计算失败链接并补全转移。失败链接指向当前字符串的最长真后缀所在的状态，
+        // 它的深度更小，所以按 BFS 的顺序处理时失败状态的转移和输出都已经确定了。
+        let mut fail = vec![0u32; depth.len()];
+        let mut queue = std::collections::VecDeque::new();
+        for slot in delta.iter_mut().take(class_count) {
+            match *slot {
+                NONE => *slot = 0,
+                next => queue.push_back(next as usize),
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            let link = fail[state] as usize;
+            // 自己不是模式的结尾时，最长的输出来自失败链接
+            if output[state] == 0 {
+                output[state] = output[link];
+            }
+            for class in 0..class_count {
+                let slot = state * class_count + class;
+                let fallback = delta[link * class_count + class];
+                match delta[slot] {
+                    NONE => delta[slot] = fallback,
+                    next => {
+                        fail[next as usize] = fallback;
+                        queue.push_back(next as usize);
+                    }
+                }
+            }
+        }
+
+        AhoCorasick { classes, class_count, delta, depth, fail, output, has_empty, case_insensitive }
+    }
+
+    fn next_state(&self, state: usize, byte: u8) -> usize {
+        self.delta[state * self.class_count + self.classes[byte as usize] as usize] as usize
+    }
+
+    /// 在 `hay[at..]` 中查找最左最长的匹配，返回它在 `hay` 中的字节区间。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chry_minigrep::aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["needle", "he", "hers"], false);
+    /// assert_eq!(Some((2, 6)), ac.find_at(b"ushers", 0));
+    ///
+    /// let ac = AhoCorasick::new(&["ERROR", "warn"], true);
+    /// assert_eq!(Some((2, 6)), ac.find_at(b"# WARNING", 0));
+    /// ```
+    pub fn find_at(&self, hay: &[u8], at: usize) -> Option<(usize, usize)> {
+        self.find_accepted(hay, at, |_, _| true)
+    }
+
+    /// 和 `find_at` 相同，但只考虑 `accept(start, end)` 返回 true 的匹配。
+    /// 同一个起点上长短不同的模式都会交给 `accept` 检查，整个查找仍然只扫描一遍输入。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chry_minigrep::aho_corasick::AhoCorasick;
+    ///
+    /// let ac = AhoCorasick::new(&["abcd", "abc"], false);
+    /// assert_eq!(Some((0, 4)), ac.find_at(b"abcd", 0));
+    /// // 不接受最长的匹配时，从同一个起点开始的较短匹配胜出
+    /// assert_eq!(Some((0, 3)), ac.find_accepted(b"abcd", 0, |_, end| end != 4));
+    /// ```
+    pub fn find_accepted(&self, hay: &[u8], at: usize, accept: impl Fn(usize, usize) -> bool) -> Option<(usize, usize)> {
+        match self.case_insensitive {
+            true => self.find_folded(hay, at, &accept),
+            false => self.find_bytes(hay, at, &accept),
+        }
+    }
+
+    // 以 state 结尾的模式从长到短依次用 start 换算出起点，返回第一个被接受的。
+    // 更短的模式起点更靠右，在同一个结尾上不可能比它更好
+    fn accepted(
+        &self,
+        state: usize,
+        end: usize,
+        start: impl Fn(State) -> usize,
+        accept: &impl Fn(usize, usize) -> bool,
+    ) -> Option<(usize, usize)> {
+        let mut out = self.output[state] as usize;
+        while out != 0 {
+            let candidate = (start(self.depth[out]), end);
+            if accept(candidate.0, candidate.1) {
+                return Some(candidate);
+            }
+            out = self.output[self.fail[out] as usize] as usize;
+        }
+        None
+    }
+
+    // 空模式在 pos 处的匹配：只有还没有更靠左的匹配时才需要它，从 pos 开始的更长的匹配稍后仍然会胜出
+    fn empty(&self, best: Option<(usize, usize)>, pos: usize, accept: &impl Fn(usize, usize) -> bool) -> Option<(usize, usize)> {
+        match best.is_none() && self.has_empty && accept(pos, pos) {
+            true => Some((pos, pos)),
+            false => best,
+        }
+    }
+
+    fn find_bytes(&self, hay: &[u8], at: usize, accept: &impl Fn(usize, usize) -> bool) -> Option<(usize, usize)> {
+        let mut state = 0;
+        let mut best: Option<(usize, usize)> = None;
+        for (i, &b) in hay.iter().enumerate().skip(at) {
+            // 空模式只在字符的开头匹配
+            if b & 0xC0 != 0x80 {
+                best = self.empty(best, i, accept);
+            }
+            state = self.next_state(state, b);
+            let end = i + 1;
+            // 当前状态的字符串就是今后的匹配能够向左延伸到的最远位置，它已经在最佳匹配的起点右边时就可以停止了
+            if let Some((start, _)) = best {
+                if end - self.depth[state].bytes > start {
+                    return best;
+                }
+            }
+            if let Some(candidate) = self.accepted(state, end, |out| end - out.bytes, accept) {
+                best = prefer(best, candidate);
+            }
+        }
+        self.empty(best, hay.len().max(at), accept)
+    }
+
+    fn find_folded(&self, hay: &[u8], at: usize, accept: &impl Fn(usize, usize) -> bool) -> Option<(usize, usize)> {
+        let mut state = 0;
+        let mut best: Option<(usize, usize)> = None;
+        let mut pos = at;
+        let mut buf = [0; 4];
+        while let Some((c, len)) = utf8::decode(hay, pos) {
+            best = self.empty(best, pos, accept);
+            for &b in casefold::fold(c).encode_utf8(&mut buf).as_bytes() {
+                state = self.next_state(state, b);
+            }
+            pos += len;
+            // 折叠是一个字符对一个字符的，按字符数从原文中往回数就能得到对应的起点
+            if let Some((start, _)) = best {
+                if back(hay, pos, self.depth[state].chars) > start {
+                    return best;
+                }
+            }
+            if let Some(candidate) = self.accepted(state, pos, |out| back(hay, pos, out.chars), accept) {
+                best = prefer(best, candidate);
+            }
+        }
+        self.empty(best, pos, accept)
+    }
+}
+
+// 最左最长：起点更靠左，或者起点相同但更长
+fn prefer(best: Option<(usize, usize)>, candidate: (usize, usize)) -> Option<(usize, usize)> {
+    match best {
+        Some((start, end)) if start < candidate.0 || (start == candidate.0 && end >= candidate.1) => best,
+        _ => Some(candidate),
+    }
+}
+
+// 从 end 往回数 chars 个字符，返回起点的字节位置
+fn back(hay: &[u8], mut end: usize, chars: usize) -> usize {
+    for _ in 0..chars {
+        match utf8::decode_last(hay, end) {
+            Some((_, len)) => end -= len,
+            None => break,
+        }
+    }
+    end
+}
+
+impl crate::search::Matcher for AhoCorasick {
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        self.find_at(line, at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leftmost_longest() {
+        let ac = AhoCorasick::new(&["abcd", "bc", "b", "abcdef", "x"], false);
+        assert_eq!(Some((0, 6)), ac.find_at(b"abcdefg", 0));
+        assert_eq!(Some((1, 3)), ac.find_at(b"abcx", 0));
+        assert_eq!(Some((3, 4)), ac.find_at(b"abcx", 2));
+        assert_eq!(None, ac.find_at(b"zzz", 0));
+
+        // 空模式在每个位置都匹配，但从同一位置开始的更长匹配优先
+        let ac = AhoCorasick::new(&["", "ab"], false);
+        assert_eq!(Some((0, 0)), ac.find_at(b"xab", 0));
+        assert_eq!(Some((1, 3)), ac.find_at(b"xab", 1));
+    }
+
+    #[test]
+    fn thousands_of_patterns() {
+        let patterns: Vec<String> = (0..5000).map(|i| format!("id-{}", i * 7)).collect();
+        let ac = AhoCorasick::new(&patterns, false);
+        assert_eq!(Some((6, 14)), ac.find_at(b"user: id-34986 done", 0));
+        // "id-7" 是 "id-70" 的前缀，最长的那个优先
+        assert_eq!(Some((0, 5)), ac.find_at(b"id-70", 0));
+        assert_eq!(None, ac.find_at(b"user: id-34999 done", 0));
+    }
+
+    #[test]
+    fn thousands_of_whole_words() {
+        let patterns: Vec<String> = (0..5000).map(|i| format!("id-{}", i * 7)).collect();
+        let ac = AhoCorasick::new(&patterns, false);
+        let hay = "id-70x id-7 id-700 id-34986_ x-id-14 ".repeat(2000);
+        let hay = hay.as_bytes();
+        let word = |start, end| utf8::is_whole_word(hay, start, end);
+        // "id-70x" 中的 "id-70" 和 "id-7" 都不是完整的单词，"id-700" 中较短的 "id-70" 也不是
+        assert_eq!(Some((7, 11)), ac.find_accepted(hay, 0, word));
+        assert_eq!(Some((12, 18)), ac.find_accepted(hay, 11, word));
+        // '-' 不是单词字符，"x-id-14" 中的 "id-14" 仍然是完整的单词
+        assert_eq!(Some((31, 36)), ac.find_accepted(hay, 18, word));
+        let found = crate::search::find_all(&|line: &[u8], at| ac.find_accepted(line, at, |s, e| utf8::is_whole_word(line, s, e)), hay);
+        assert_eq!(3 * 2000, found.len());
+    }
+
+    #[test]
+    fn case_insensitive_positions_follow_the_original_text() {
+        let ac = AhoCorasick::new(&["straße", "kσ"], true);
+        assert_eq!(Some((3, 8)), ac.find_at("xx \u{212A}ς".as_bytes(), 0));
+        assert_eq!(Some((1, 9)), ac.find_at("-STRAẞE-".as_bytes(), 0));
+    }
+}
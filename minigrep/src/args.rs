@@ -0,0 +1,273 @@
+// 命令行参数的词法分析与解析错误。
+// Parser 只负责把参数拆成短选项、长选项和普通参数，具体有哪些选项、各自的含义由 Config::new 决定：
+//     -inv      组合在一起的短选项，依次拆成 -i -n -v
+//     -j4       短选项后面紧跟的内容作为它的值
+//     --include=*.rs 或 --include *.rs   长选项的值
+//     --        之后的参数全部当作普通参数，即使以 - 开头
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::regex;
+
+/// 命令行用法说明，`--help` 时打印
+pub const USAGE: &str = "\
+Usage: minigrep [OPTIONS] QUERY [PATH...]
+       minigrep [OPTIONS] -e PATTERN... [-f FILE...] [PATH...]
+       minigrep [--no-config] index [--index-dir DIR] [PATH...]
+
+Search for QUERY in each PATH. Directories are searched recursively.
+With no PATH, or when PATH is -, read standard input.
+Colors are disabled when NO_COLOR is set and can be changed with GREP_COLORS, e.g. 'ms=01;31:fn=35:ln=32'.
+Defaults are read from ~/.minigreprc and from the first .minigreprc found in the current
+directory or its parents, e.g. 'line-number = true' or 'context = 2'; options
+given on the command line override them, and --no-line-number and the like turn
+off switches set there. Patterns, search and output modes (such as --regex,
+--multiline or --stats) and --replace can't be set there.
+'minigrep index' builds a trigram index of PATH (default: the current directory) in DIR
+(default: .minigrep-index); searching with --index-dir DIR then skips files that cannot
+match. Files changed since the index was built are always searched.
+Exit status is 0 if a line is selected, 1 if no lines were selected, and 2 if an error occurred.
+
+Options:
+  -E, --regex               treat QUERY as a regular expression
+  -e, --regexp PATTERN      search for PATTERN; can be given more than once
+  -f, --file FILE           read patterns from FILE, one per line
+  -i, --ignore-case         case insensitive search (default when CASE_INSENSITIVE is set)
+  -s, --case-sensitive      case sensitive search, overriding CASE_INSENSITIVE and config files.
+                            As in ripgrep, -s is not grep's --no-messages
+  -v, --invert-match        select non-matching lines
+  -n, --line-number         prefix each line with its line number
+  -b, --byte-offset         prefix each line with its byte offset in the file
+      --column              prefix each line with the column of the first match
+  -A, --after-context N     print N lines of trailing context
+  -B, --before-context N    print N lines of leading context
+  -C, --context N           print N lines of leading and trailing context
+  -c, --count               print only a count of selected lines per file
+  -l, --files-with-matches  print only names of files containing matches
+  -L, --files-without-match print only names of files containing no match
+  -m, --max-count N         stop searching a file after N selected lines
+  -w, --word-regexp         match only whole words (not next to a letter, digit or '_')
+  -x, --line-regexp         match only whole lines
+  -o, --only-matching       print only the matched text, each match on its own line
+      --capture GROUP       with -E, print only capture group GROUP (a number or a name) of each
+                            match; implies -o
+  -U, --multiline           let patterns match across line breaks ('\\n' in a regex, or a newline
+                            in a literal pattern); each match prints all the lines it spans
+      --fuzzy K             match substrings within edit distance K of a pattern (insertions,
+                            deletions and substitutions); each line shows its distance as ~K:.
+                            K must be less than the length of every pattern
+  -a, --text                process a binary file as if it were text
+  -z, --search-zip          decompress gzip files (detected by their magic bytes) while searching
+      --encoding NAME       decode files as utf-8, utf-16le, utf-16be or latin-1 before searching;
+                            'auto' (default) detects a byte order mark. Byte offsets and columns
+                            (-b, --column, --json) still count bytes of the original file
+      --csv-field FIELD     match only in one field of each CSV record; FIELD is a column number
+                            (from 1) or a name from the header row. Quoted fields may contain
+                            commas and line breaks
+      --json-path PATH      match only in the value at PATH (like a.b.0.c) of each JSON Lines record
+  -j, --threads N           search N files in parallel (default: number of CPUs)
+      --include GLOB        search only files matching GLOB
+      --exclude GLOB        skip files matching GLOB
+      --follow              keep watching the files like 'tail -F' and print new matching lines
+                            as they are appended, surviving rotation and truncation; Ctrl-C stops
+      --index-dir DIR       use (or with 'index', build) the trigram index in DIR
+      --color[=WHEN]        highlight matches; WHEN is 'auto' (default), 'always' or 'never'
+      --replace TEMPLATE    print matching lines with each match replaced by TEMPLATE;
+                            $1, ${1}, $name and ${name} refer to capture groups, $$ is a literal $
+      --in-place            write the replacements back to each file (atomically)
+      --dry-run             show the changes --in-place would make as a diff without writing
+      --json                print results as JSON Lines: begin, match, context, end and summary events
+      --stats               print totals after the results: files and bytes searched, matched
+                            lines, elapsed time and throughput
+      --histogram           print matched lines per file, and per hour for lines that start with
+                            a timestamp (2026-10-17T14:03:00, [2026-10-17 14:03:00], Oct 17 14:03:00);
+                            syslog timestamps have no year and are listed separately
+      --no-config           don't read any .minigreprc
+      --no-line-number, --no-byte-offset, --no-column, --no-max-count, --no-word-regexp,
+      --no-line-regexp, --no-text, --no-search-zip
+                            turn off -n, -b, --column, -m, -w, -x, -a or -z, e.g. when a config
+                            file sets it
+  -h, --help                print this help and exit
+  -V, --version             print version information and exit";
+
+/// 解析命令行参数时产生的错误
+#[derive(Debug)]
+pub enum ArgsError {
+    /// 用户请求了 `--help`，调用者应当打印 [`USAGE`] 并正常退出
+    Help,
+    /// 用户请求了 `--version`
+    Version,
+    MissingQuery,
+    UnknownOption(String),
+    /// 选项需要一个值但没有提供
+    MissingValue(String),
+    /// 选项的值不合法：(选项, 值)
+    InvalidValue(String, String),
+    /// 不接受值的选项以 `--flag=value` 的形式给出了值
+    UnexpectedValue(String),
+    InvalidRegex(regex::Error),
+    /// `-f` 指定的模式文件无法读取：(文件名, 错误)
+    PatternFile(String, io::Error),
+    /// 两个选项不能同时使用
+    Conflict(String, String),
+    /// 第一个选项需要和第二个选项一起使用
+    Requires(String, String),
+    /// 配置文件中的错误：(位置, 错误信息)，位置为文件名或者“文件名:行号”
+    ConfigFile(String, String),
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgsError::Help => f.write_str(USAGE),
+            ArgsError::Version => write!(f, "minigrep {}", env!("CARGO_PKG_VERSION")),
+            ArgsError::MissingQuery => f.write_str("Didn't get a query string"),
+            ArgsError::UnknownOption(opt) => write!(f, "unknown option '{}'", opt),
+            ArgsError::MissingValue(opt) => write!(f, "option '{}' requires a value", opt),
+            ArgsError::InvalidValue(opt, value) => write!(f, "invalid value '{}' for option '{}'", value, opt),
+            ArgsError::UnexpectedValue(opt) => write!(f, "option '{}' doesn't take a value", opt),
+            ArgsError::InvalidRegex(e) => e.fmt(f),
+            ArgsError::PatternFile(file, e) => write!(f, "{}: {}", file, e),
+            ArgsError::Conflict(a, b) => write!(f, "options '{}' and '{}' cannot be used together", a, b),
+            ArgsError::Requires(a, b) => write!(f, "option '{}' requires '{}'", a, b),
+            ArgsError::ConfigFile(at, message) => write!(f, "{}: {}", at, message),
+        }
+    }
+}
+
+impl Error for ArgsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ArgsError::InvalidRegex(e) => Some(e),
+            ArgsError::PatternFile(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<regex::Error> for ArgsError {
+    fn from(e: regex::Error) -> ArgsError {
+        ArgsError::InvalidRegex(e)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Arg {
+    Short(char),
+    Long(String),
+    Value(String),
+}
+
+pub(crate) struct Parser<I: Iterator<Item = String>> {
+    args: I,
+    shorts: Vec<char>,          // 当前短选项组中还没处理的字符
+    long_value: Option<String>, // --name=value 中的 value
+    current: String,            // 最近一个选项的写法，用于错误信息
+    only_values: bool,          // 遇到 -- 之后不再解析选项
+}
+
+impl<I: Iterator<Item = String>> Parser<I> {
+    pub(crate) fn new(args: I) -> Parser<I> {
+        Parser { args, shorts: Vec::new(), long_value: None, current: String::new(), only_values: false }
+    }
+
+    /// 最近一个选项的写法，例如 `-j` 或 `--include`
+    pub(crate) fn option(&self) -> &str {
+        &self.current
+    }
+
+    pub(crate) fn next(&mut self) -> Result<Option<Arg>, ArgsError> {
+        // 上一个长选项带了 =value 却没有被取走，说明它是个不接受值的开关
+        if self.long_value.take().is_some() {
+            return Err(ArgsError::UnexpectedValue(self.current.clone()));
+        }
+        if !self.shorts.is_empty() {
+            let c = self.shorts.remove(0);
+            self.current = format!("-{}", c);
+            return Ok(Some(Arg::Short(c)));
+        }
+
+        let arg = match self.args.next() {
+            Some(arg) => arg,
+            None => return Ok(None),
+        };
+        if self.only_values {
+            return Ok(Some(Arg::Value(arg)));
+        }
+        if arg == "--" {
+            self.only_values = true;
+            return self.next();
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.find('=') {
+                Some(i) => (&long[..i], Some(long[i + 1..].to_string())),
+                None => (long, None),
+            };
+            self.current = format!("--{}", name);
+            self.long_value = value;
+            return Ok(Some(Arg::Long(name.to_string())));
+        }
+        // 单独的 "-" 不是选项
+        if arg.len() > 1 && arg.starts_with('-') {
+            self.shorts = arg[1..].chars().collect();
+            return self.next();
+        }
+        Ok(Some(Arg::Value(arg)))
+    }
+
+    /// 取出当前选项的值：`--name=value`、`-jVALUE` 中紧跟的部分，或者下一个参数
+    pub(crate) fn value(&mut self) -> Result<String, ArgsError> {
+        if let Some(value) = self.long_value.take() {
+            return Ok(value);
+        }
+        if !self.shorts.is_empty() {
+            return Ok(self.shorts.drain(..).collect());
+        }
+        self.args.next().ok_or_else(|| ArgsError::MissingValue(self.current.clone()))
+    }
+
+    /// 是否已经遇到了 `--`，之后的参数都是普通参数
+    pub(crate) fn after_separator(&self) -> bool {
+        self.only_values
+    }
+
+    /// 取出以 `--name=value` 形式紧跟在长选项后面的可选值，不会把下一个参数当作值
+    pub(crate) fn optional_value(&mut self) -> Option<String> {
+        self.long_value.take()
+    }
+
+    /// 取出当前选项的值并解析成数字等类型
+    pub(crate) fn parse_value<T: std::str::FromStr>(&mut self) -> Result<T, ArgsError> {
+        let value = self.value()?;
+        value.parse().map_err(|_| ArgsError::InvalidValue(self.current.clone(), value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_combined_flags_and_values() {
+        let args = ["-inj4", "--include=*.rs", "x", "--", "-v"];
+        let mut parser = Parser::new(args.iter().map(|s| s.to_string()));
+        assert_eq!(Some(Arg::Short('i')), parser.next().unwrap());
+        assert_eq!(Some(Arg::Short('n')), parser.next().unwrap());
+        assert_eq!(Some(Arg::Short('j')), parser.next().unwrap());
+        assert_eq!(4, parser.parse_value::<usize>().unwrap());
+        assert_eq!(Some(Arg::Long("include".to_string())), parser.next().unwrap());
+        assert_eq!("*.rs", parser.value().unwrap());
+        assert_eq!(Some(Arg::Value("x".to_string())), parser.next().unwrap());
+        assert_eq!(Some(Arg::Value("-v".to_string())), parser.next().unwrap());
+        assert_eq!(None, parser.next().unwrap());
+    }
+
+    #[test]
+    fn rejects_value_for_flag() {
+        let mut parser = Parser::new(vec!["--count=3".to_string()].into_iter());
+        assert_eq!(Some(Arg::Long("count".to_string())), parser.next().unwrap());
+        assert!(matches!(parser.next(), Err(ArgsError::UnexpectedValue(opt)) if opt == "--count"));
+    }
+}
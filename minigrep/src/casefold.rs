@@ -0,0 +1,241 @@
+// 大小写不敏感的字符串查找，使用 Unicode 简单大小写折叠（simple case folding）。
+//
+// 最初的实现把每一行都 to_lowercase 成一个新的 String 再查找，每行都要分配内存；
+// 而且小写转换不等于大小写折叠：'ſ'、'ς'、开尔文符号 'K'（U+212A）等字符转成小写后和它们的“同类”并不相等。
+// 这里改为边解码边逐个字符比较折叠后的结果：查询串只在创建 Finder 时折叠一次，搜索时不分配内存，
+// 返回的区间始终是原始行中的字节位置，即使折叠前后的字节长度不同（例如 'K' 占 3 个字节而 'k' 只占 1 个）。
+
+use std::convert::TryInto;
+use std::sync::OnceLock;
+
+use crate::utf8;
+
+// 基本多文种平面（BMP）中字符的折叠结果按 256 个字符一页缓存，第一次用到某一页时才计算。
+// std 的大小写转换每次都要在表中做二分查找，而一段文本通常只会用到少数几页，查页表要快得多。
+// BMP 中字符的折叠结果也在 BMP 中，所以每项用 u16 就够了。
+static PAGES: [OnceLock<Box<[u16; 256]>>; 256] = [const { OnceLock::new() }; 256];
+
+/// 返回字符的简单大小写折叠结果，折叠结果相同的两个字符在大小写不敏感时视为相等。
+///
+/// 标准库没有提供折叠表，这里借助一对一的大小写映射来构造：先转大写再转小写，
+/// 这样 'ſ' → 'S' → 's'、'ς' → 'Σ' → 'σ'。转换结果不是单个字符时（例如 'ß' 的大写是 "SS"）保持不变，
+/// 和简单折叠只使用一对一映射的规则一致。
+///
+/// # Examples
+///
+/// ```
+/// use chry_minigrep::casefold::fold;
+///
+/// assert_eq!('s', fold('ſ'));
+/// assert_eq!(fold('Σ'), fold('ς'));
+/// assert_eq!('ß', fold('ẞ'));
+/// ```
+pub fn fold(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    let code = c as u32;
+    if code <= 0xFFFF {
+        let page = PAGES[(code >> 8) as usize].get_or_init(|| {
+            let mut page = Box::new([0; 256]);
+            for (i, folded) in page.iter_mut().enumerate() {
+                let code = code & !0xFF | i as u32;
+                // 代理区（U+D800..U+DFFF）不是合法的字符，原样保留
+                *folded = char::from_u32(code).map_or(code, |c| fold_uncached(c) as u32) as u16;
+            }
+            page
+        });
+        return char::from_u32(page[(code & 0xFF) as usize] as u32).unwrap_or(c);
+    }
+    fold_uncached(c)
+}
+
+fn fold_uncached(c: char) -> char {
+    // 土耳其语的无点 ı 在简单折叠中没有映射，不能通过大写 I 折叠成 i
+    if c == 'ı' {
+        return c;
+    }
+    let upper = single(c.to_uppercase()).unwrap_or(c);
+    single(upper.to_lowercase()).unwrap_or(c)
+}
+
+// 大小写转换的结果恰好是一个字符时返回它
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+// 折叠结果的大写形式不是它自己的那些字符，例如 'ſ'（折叠成 's'，而 's' 的大写是 'S'）、'ς'、'ẞ'、
+// 开尔文符号、欧姆符号以及带下标 iota 的希腊字母。除了它们之外，折叠成 f 的字符只有 f 本身和 f 的大写。
+// 这张表由测试 equivalents_are_complete 对照所有字符验证。
+const FOLD_EXCEPTIONS: [char; 58] = [
+    '\u{B5}', '\u{17F}', '\u{1C5}', '\u{1C8}', '\u{1CB}', '\u{1F2}', '\u{345}', '\u{3C2}', '\u{3D0}', '\u{3D1}',
+    '\u{3D5}', '\u{3D6}', '\u{3F0}', '\u{3F1}', '\u{3F4}', '\u{3F5}', '\u{1C80}', '\u{1C81}', '\u{1C82}', '\u{1C83}',
+    '\u{1C84}', '\u{1C85}', '\u{1C86}', '\u{1C87}', '\u{1C88}', '\u{1E9B}', '\u{1E9E}', '\u{1F88}', '\u{1F89}', '\u{1F8A}',
+    '\u{1F8B}', '\u{1F8C}', '\u{1F8D}', '\u{1F8E}', '\u{1F8F}', '\u{1F98}', '\u{1F99}', '\u{1F9A}', '\u{1F9B}', '\u{1F9C}',
+    '\u{1F9D}', '\u{1F9E}', '\u{1F9F}', '\u{1FA8}', '\u{1FA9}', '\u{1FAA}', '\u{1FAB}', '\u{1FAC}', '\u{1FAD}', '\u{1FAE}',
+    '\u{1FAF}', '\u{1FBC}', '\u{1FBE}', '\u{1FCC}', '\u{1FFC}', '\u{2126}', '\u{212A}', '\u{212B}',
+];
+
+// 所有折叠结果为 folded 的字符
+fn equivalents(folded: char) -> Vec<char> {
+    let mut chars = vec![folded];
+    chars.extend(single(folded.to_uppercase()).filter(|&c| c != folded));
+    chars.extend(FOLD_EXCEPTIONS.iter().copied().filter(|&c| fold(c) == folded));
+    chars.retain(|&c| fold(c) == folded);
+    chars
+}
+
+// 只有开尔文符号（U+212A）和长 s（U+017F）这两个非 ASCII 字符会折叠成 ASCII 字符，分别是 'k' 和 's'
+fn only_ascii_folds_to(c: char) -> bool {
+    c.is_ascii() && c != 'k' && c != 's'
+}
+
+/// 大小写不敏感地查找一个固定的字符串
+#[derive(Debug, Clone)]
+pub struct Finder {
+    // 折叠后的查询串
+    needle: Vec<char>,
+    // 所有折叠后等于查询串第一个字符的字符，逐个字符扫描时只需要和它们比较，不必折叠文本中的每个字符
+    first: Vec<char>,
+    // 查询串第一个字符折叠后是 ASCII 时，匹配只可能从它的小写或大写字节开始，可以直接按字节跳过其他位置
+    first_byte: Option<u8>,
+    // 查询串全是 ASCII 并且不含 'k'、's' 时，匹配的文本也一定全是 ASCII，可以逐字节比较而不必解码
+    ascii: Option<Vec<u8>>,
+}
+
+impl Finder {
+    pub fn new(query: &str) -> Finder {
+        let needle: Vec<char> = query.chars().map(fold).collect();
+        let first_byte = needle.first().filter(|&&c| only_ascii_folds_to(c)).map(|&c| c as u8);
+        let ascii = match needle.iter().all(|&c| only_ascii_folds_to(c)) {
+            true => Some(needle.iter().map(|&c| c as u8).collect()),
+            false => None,
+        };
+        let first = needle.first().map_or_else(Vec::new, |&c| equivalents(c));
+        Finder { needle, first, first_byte, ascii }
+    }
+
+    /// 在 `hay[at..]` 中查找第一个匹配，返回它在 `hay` 中的字节区间。
+    ///
+    /// `hay` 不要求是合法的 UTF-8，非法字节按 U+FFFD 处理。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chry_minigrep::casefold::Finder;
+    ///
+    /// let finder = Finder::new("strasse");
+    /// assert_eq!(Some((4, 12)), finder.find_at("Die STRAſSE".as_bytes(), 0));
+    /// ```
+    pub fn find_at(&self, hay: &[u8], at: usize) -> Option<(usize, usize)> {
+        if self.needle.is_empty() {
+            return Some((at, at));
+        }
+        let mut start = at;
+        while start < hay.len() {
+            if let Some(first) = self.first_byte {
+                start += find_byte_ignore_case(&hay[start..], first)?;
+            }
+            if let Some(ascii) = &self.ascii {
+                let end = start + ascii.len();
+                if hay.get(start..end).is_some_and(|window| window.eq_ignore_ascii_case(ascii)) {
+                    return Some((start, end));
+                }
+                // 候选的起点一定是 ASCII 字节，前进一个字节即可
+                start += 1;
+                continue;
+            }
+            let (c, len) = utf8::decode(hay, start)?;
+            if self.first.contains(&c) {
+                if let Some(end) = self.match_rest(hay, start + len) {
+                    return Some((start, end));
+                }
+            }
+            start += len;
+        }
+        None
+    }
+
+    // 第一个字符已经匹配，查询串的其余部分是否从 start 处开始匹配，匹配时返回结束位置
+    fn match_rest(&self, hay: &[u8], start: usize) -> Option<usize> {
+        let mut at = start;
+        for &want in &self.needle[1..] {
+            let (c, len) = utf8::decode(hay, at)?;
+            if c != want && fold(c) != want {
+                return None;
+            }
+            at += len;
+        }
+        Some(at)
+    }
+}
+
+// 查找第一个和 ASCII 字符 lower（已经是小写）忽略大小写后相等的字节。
+// 每次取 8 个字节并行比较：字母的大小写只差 0x20 这一位，把每个字节的这一位置 1 后再和 lower 比较即可。
+// 对于非字母，置位可能带来假的候选位置（例如 0x14 会被当作 '4'），由调用者负责验证。
+fn find_byte_ignore_case(hay: &[u8], lower: u8) -> Option<usize> {
+    const LO: u64 = 0x0101_0101_0101_0101;
+    const HI: u64 = 0x8080_8080_8080_8080;
+    let pattern = LO * (lower | 0x20) as u64;
+    let case_bit = LO * 0x20;
+    let mut chunks = hay.chunks_exact(8);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap()) | case_bit;
+        let diff = word ^ pattern;
+        // 经典的“字中是否有零字节”技巧：最低的那个零字节一定会被准确地标记出来
+        let zero = diff.wrapping_sub(LO) & !diff & HI;
+        if zero != 0 {
+            return Some(i * 8 + zero.trailing_zeros() as usize / 8);
+        }
+    }
+    let rest = chunks.remainder();
+    let offset = hay.len() - rest.len();
+    rest.iter().position(|&b| b | 0x20 == lower | 0x20).map(|i| offset + i)
+}
+
+impl crate::search::Matcher for Finder {
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        self.find_at(line, at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_kelvin_and_long_s_fold_into_ascii() {
+        // Finder 的按字节跳跃依赖这一点；同时检查页表缓存和直接计算的结果一致
+        let into_ascii: Vec<char> = (0x80..=0x10FFFF)
+            .filter_map(char::from_u32)
+            .inspect(|&c| assert_eq!(fold_uncached(c), fold(c)))
+            .filter(|&c| fold(c).is_ascii())
+            .collect();
+        assert_eq!(vec!['\u{17F}', '\u{212A}'], into_ascii);
+    }
+
+    #[test]
+    fn equivalents_are_complete() {
+        // 折叠结果就是自己的字符一定包含在内，只需要检查其余的字符
+        for c in (0..=0x10FFFF).filter_map(char::from_u32).filter(|&c| fold(c) != c) {
+            assert!(equivalents(fold(c)).contains(&c), "{:?}", c);
+        }
+        assert_eq!(vec!['σ', 'Σ', 'ς'], equivalents('σ'));
+    }
+
+    #[test]
+    fn finds_original_slices() {
+        let finder = Finder::new("kσ");
+        let hay = "xx \u{212A}ς kΣ".as_bytes();
+        assert_eq!(Some((3, 8)), finder.find_at(hay, 0));
+        assert_eq!(Some((9, 12)), finder.find_at(hay, 8));
+
+        // 非法的 UTF-8 字节不影响后面的匹配；跨过 8 字节分块的边界也能找到
+        assert_eq!(Some((2, 5)), Finder::new("ABC").find_at(b"\xff\xfeabc", 0));
+        assert_eq!(Some((9, 13)), Finder::new("Tail").find_at(b"t4\x14tai_xxTAIL", 0));
+        assert_eq!(None, Finder::new("i").find_at("ı".as_bytes(), 0));
+    }
+}
@@ -0,0 +1,174 @@
+// 用 ANSI 转义序列给输出上色，颜色的含义和配置方式与 GNU grep 相同：
+//     ms  选中行中的匹配      mc  上下文行中的匹配（-v 时上下文行才是匹配的行）
+//     sl  选中行的其余部分    cx  上下文行的其余部分
+//     fn  文件名    ln  行号和列号    bn  字节偏移    se  分隔符 ':' '-' 以及 "--"
+// GREP_COLORS 环境变量可以覆盖其中任意几项，例如 GREP_COLORS='ms=01;32:fn=34'，mt= 同时设置 ms 和 mc。
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+
+/// `--color` 的取值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// 标准输出是终端并且没有设置 `NO_COLOR` 时上色
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// 根据选项、`NO_COLOR` 环境变量以及标准输出是否为终端，决定是否上色
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            // 按照 https://no-color.org 的约定，NO_COLOR 存在且不为空时不上色；--color=always 仍然优先
+            ColorChoice::Auto => {
+                env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = ();
+
+    // 同时接受 grep 的同义词 yes/force、no/none、tty/if-tty
+    fn from_str(s: &str) -> Result<ColorChoice, ()> {
+        match s {
+            "auto" | "tty" | "if-tty" => Ok(ColorChoice::Auto),
+            "always" | "yes" | "force" => Ok(ColorChoice::Always),
+            "never" | "no" | "none" => Ok(ColorChoice::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+/// 各部分输出使用的 SGR 参数（例如 `"01;31"`），空字符串表示不上色
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    pub selected_match: String,
+    pub context_match: String,
+    pub selected_line: String,
+    pub context_line: String,
+    pub file_name: String,
+    pub line_number: String,
+    pub byte_offset: String,
+    pub separator: String,
+}
+
+impl Default for Colors {
+    // grep 的默认配色：ms=01;31:mc=01;31:sl=:cx=:fn=35:ln=32:bn=32:se=36
+    fn default() -> Colors {
+        Colors {
+            selected_match: "01;31".to_string(),
+            context_match: "01;31".to_string(),
+            selected_line: String::new(),
+            context_line: String::new(),
+            file_name: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+        }
+    }
+}
+
+impl Colors {
+    /// 不上色：所有部分都原样输出
+    pub fn none() -> Colors {
+        Colors {
+            selected_match: String::new(),
+            context_match: String::new(),
+            selected_line: String::new(),
+            context_line: String::new(),
+            file_name: String::new(),
+            line_number: String::new(),
+            byte_offset: String::new(),
+            separator: String::new(),
+        }
+    }
+
+    /// 在默认配色的基础上应用 `GREP_COLORS` 环境变量
+    pub fn from_env() -> Colors {
+        let mut colors = Colors::default();
+        if let Ok(spec) = env::var("GREP_COLORS") {
+            colors.apply(&spec);
+        }
+        colors
+    }
+
+    /// 应用 `name=value:name=value` 形式的配置。
+    ///
+    /// 和 grep 一样，不认识的名字（包括 `rv`、`ne` 这样的开关）和不合法的值会被忽略。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chry_minigrep::color::Colors;
+    ///
+    /// let mut colors = Colors::default();
+    /// colors.apply("mt=01;32:fn=:ln=1;33");
+    /// assert_eq!("01;32", colors.context_match);
+    /// assert_eq!("", colors.file_name);
+    /// assert_eq!("1;33", colors.line_number);
+    /// ```
+    pub fn apply(&mut self, spec: &str) {
+        for item in spec.split(':') {
+            let (name, value) = match item.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            if !value.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                continue;
+            }
+            let value = value.to_string();
+            match name {
+                "mt" => {
+                    self.selected_match = value.clone();
+                    self.context_match = value;
+                }
+                "ms" => self.selected_match = value,
+                "mc" => self.context_match = value,
+                "sl" => self.selected_line = value,
+                "cx" => self.context_line = value,
+                "fn" => self.file_name = value,
+                "ln" => self.line_number = value,
+                "bn" => self.byte_offset = value,
+                "se" => self.separator = value,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// 用 `sgr` 指定的颜色输出 `text`；`sgr` 为空时原样输出。
+///
+/// 和 grep 一样在颜色前后加上 `ESC[K`（清除到行尾），避免终端换行时把背景色带到下一行。
+pub fn paint(out: &mut dyn Write, sgr: &str, text: &[u8]) -> io::Result<()> {
+    if sgr.is_empty() || text.is_empty() {
+        return out.write_all(text);
+    }
+    write!(out, "\x1b[{}m\x1b[K", sgr)?;
+    out.write_all(text)?;
+    out.write_all(b"\x1b[m\x1b[K")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paints_only_non_empty_colors() {
+        let mut out = Vec::new();
+        paint(&mut out, "01;31", b"hit").unwrap();
+        paint(&mut out, "", b" rest").unwrap();
+        assert_eq!(&b"\x1b[01;31m\x1b[Khit\x1b[m\x1b[K rest"[..], &out[..]);
+
+        let mut colors = Colors::default();
+        colors.apply("ms=1;4:bogus:ln=x:rv");
+        assert_eq!(("1;4", "32"), (colors.selected_match.as_str(), colors.line_number.as_str()));
+        assert_eq!(Ok(ColorChoice::Never), "none".parse());
+    }
+}
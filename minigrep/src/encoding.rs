@@ -0,0 +1,287 @@
+// 文本编码：识别 BOM，把 UTF-16（LE / BE）和 Latin-1 的内容边读边转换成 UTF-8 再搜索。
+//
+// 搜索引擎只认识 UTF-8（或者说任意字节），Windows 上生成的 UTF-16 日志在它看来满是 NUL 字节，只会被当作二进制文件。
+// Decoder 包装任意 BufRead，每次只转换读到的一段数据，不需要把整个文件读进内存。
+//
+// 输出的字节偏移要对应原始文件，而不是转换后的内容。转换是逐个字符进行的，一段转换结果在原始文件中
+// 占多少字节只取决于它本身：UTF-16 中每个字符占 2 或 4 个字节，Latin-1 中每个字符占 1 个字节，
+// 所以用 [`Encoding::original_len`] 就能把转换后的长度换算回去，不需要另外记录对应关系。
+
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::str::FromStr;
+
+/// 文本的编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1：每个字节就是 U+0000..U+00FF 中的一个字符
+    Latin1,
+}
+
+impl FromStr for Encoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Encoding, ()> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" => Ok(Encoding::Latin1),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "Latin-1",
+        })
+    }
+}
+
+impl Encoding {
+    /// 根据开头的 BOM 识别编码，返回编码和 BOM 的长度
+    pub fn from_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+            [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+            [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+            _ => None,
+        }
+    }
+
+    /// 确定内容的编码和开头需要跳过的 BOM 长度。`forced` 为 None 时按 BOM 识别，没有 BOM 就当作 UTF-8；
+    /// 指定了编码时只跳过属于这个编码的 BOM
+    pub fn detect(prefix: &[u8], forced: Option<Encoding>) -> (Encoding, usize) {
+        match (forced, Encoding::from_bom(prefix)) {
+            (None, Some(found)) => found,
+            (None, None) => (Encoding::Utf8, 0),
+            (Some(forced), Some((found, bom))) if forced == found => (forced, bom),
+            (Some(forced), _) => (forced, 0),
+        }
+    }
+
+    /// 由 [`Decoder`] 转换出来的 `decoded` 在原始内容中所占的字节数
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chry_minigrep::encoding::Encoding;
+    ///
+    /// assert_eq!(8, Encoding::Utf16Le.original_len("a€😀".as_bytes()));
+    /// assert_eq!(2, Encoding::Latin1.original_len("é\n".as_bytes()));
+    /// ```
+    pub fn original_len(self, decoded: &[u8]) -> usize {
+        // 转换结果总是合法的 UTF-8：不是后续字节（10xxxxxx）的字节数就是字符数，4 字节的字符在 UTF-16 中是一对代理项
+        let chars = || decoded.iter().filter(|&&b| b & 0xC0 != 0x80);
+        match self {
+            Encoding::Utf8 => decoded.len(),
+            Encoding::Utf16Le | Encoding::Utf16Be => chars().map(|&b| if b >= 0xF0 { 4 } else { 2 }).sum(),
+            Encoding::Latin1 => chars().count(),
+        }
+    }
+}
+
+// 转换的状态，和被包装的 reader 分开，这样转换时还可以借用 reader 的缓冲区
+struct State {
+    encoding: Encoding,
+    out: Vec<u8>,
+    pos: usize,
+    // UTF-16：上一段数据末尾不完整的码元，以及还在等待低代理项的高代理项
+    odd: Option<u8>,
+    high: Option<u16>,
+}
+
+impl State {
+    fn push(&mut self, c: char) {
+        let mut buf = [0; 4];
+        self.out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+
+    fn unit(&mut self, unit: u16) {
+        if let Some(high) = self.high.take() {
+            if (0xDC00..0xE000).contains(&unit) {
+                let c = 0x10000 + ((high as u32 - 0xD800) << 10) + (unit as u32 - 0xDC00);
+                self.push(char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER));
+                return;
+            }
+            self.push(char::REPLACEMENT_CHARACTER);
+        }
+        match unit {
+            0xD800..=0xDBFF => self.high = Some(unit),
+            0xDC00..=0xDFFF => self.push(char::REPLACEMENT_CHARACTER),
+            _ => self.push(char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER)),
+        }
+    }
+
+    fn decode(&mut self, mut raw: &[u8]) {
+        let unit = match self.encoding {
+            Encoding::Utf8 => return self.out.extend_from_slice(raw),
+            Encoding::Latin1 => return raw.iter().for_each(|&b| self.push(b as char)),
+            Encoding::Utf16Le => u16::from_le_bytes,
+            Encoding::Utf16Be => u16::from_be_bytes,
+        };
+        if let Some(first) = self.odd.take() {
+            match raw.split_first() {
+                Some((&second, rest)) => {
+                    self.unit(unit([first, second]));
+                    raw = rest;
+                }
+                None => self.odd = Some(first),
+            }
+        }
+        let mut pairs = raw.chunks_exact(2);
+        for pair in &mut pairs {
+            self.unit(unit([pair[0], pair[1]]));
+        }
+        if let [last] = pairs.remainder() {
+            self.odd = Some(*last);
+        }
+    }
+
+    // 内容结束时还没有配对的高代理项或者多出的一个字节各自变成一个 U+FFFD
+    fn finish(&mut self) {
+        if self.high.take().is_some() {
+            self.push(char::REPLACEMENT_CHARACTER);
+        }
+        if self.odd.take().is_some() {
+            self.push(char::REPLACEMENT_CHARACTER);
+        }
+    }
+}
+
+/// 把 `encoding` 编码的内容转换成 UTF-8。无法转换的部分（单独的代理项、末尾多出的一个字节）变成 U+FFFD。
+/// BOM 不由它处理，应当在包装之前跳过
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Read;
+/// use chry_minigrep::encoding::{Decoder, Encoding};
+///
+/// let mut text = String::new();
+/// Decoder::new(&b"h\0i\0\n\0"[..], Encoding::Utf16Le).read_to_string(&mut text).unwrap();
+/// assert_eq!("hi\n", text);
+/// ```
+pub struct Decoder<R> {
+    inner: R,
+    state: State,
+}
+
+impl<R: BufRead> Decoder<R> {
+    pub fn new(inner: R, encoding: Encoding) -> Decoder<R> {
+        Decoder { inner, state: State { encoding, out: Vec::new(), pos: 0, odd: None, high: None } }
+    }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Decoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let state = &mut self.state;
+        while state.pos == state.out.len() {
+            state.out.clear();
+            state.pos = 0;
+            let raw = self.inner.fill_buf()?;
+            if raw.is_empty() {
+                state.finish();
+                break;
+            }
+            let n = raw.len();
+            state.decode(raw);
+            self.inner.consume(n);
+        }
+        Ok(&state.out[state.pos..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.state.pos = (self.state.pos + amount).min(self.state.out.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{search_files, temp_files};
+
+    // 每次只读出一个字节的 BufRead，用来检查跨越数据块边界的代理项和码元
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.fill_buf()?.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.consume(n);
+            Ok(n)
+        }
+    }
+
+    impl BufRead for Trickle<'_> {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            Ok(&self.0[..self.0.len().min(1)])
+        }
+
+        fn consume(&mut self, amount: usize) {
+            self.0 = &self.0[amount..];
+        }
+    }
+
+    fn decode(raw: &[u8], encoding: Encoding) -> String {
+        let mut text = String::new();
+        Decoder::new(Trickle(raw), encoding).read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn transcodes_to_utf8() {
+        let text = "héllo €😀\r\n";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(text, decode(&le, Encoding::Utf16Le));
+        assert_eq!(text, decode(&be, Encoding::Utf16Be));
+        assert_eq!(le.len(), Encoding::Utf16Le.original_len(text.as_bytes()));
+        assert_eq!("caf\u{e9} \u{ff}", decode(b"caf\xe9 \xff", Encoding::Latin1));
+
+        // 单独的代理项和末尾多出的字节
+        assert_eq!("\u{fffd}a\u{fffd}\u{fffd}", decode(&[0x00, 0xD8, b'a', 0, 0x00, 0xDC, b'b'], Encoding::Utf16Le));
+    }
+
+    #[test]
+    fn detects_byte_order_marks() {
+        assert_eq!((Encoding::Utf16Le, 2), Encoding::detect(b"\xff\xfeh\0", None));
+        assert_eq!((Encoding::Utf16Be, 2), Encoding::detect(b"\xfe\xff\0h", None));
+        assert_eq!((Encoding::Utf8, 3), Encoding::detect(b"\xef\xbb\xbfhi", None));
+        assert_eq!((Encoding::Utf8, 0), Encoding::detect(b"hi", None));
+        // 指定的编码优先，不属于它的 BOM 按普通内容处理
+        assert_eq!((Encoding::Latin1, 0), Encoding::detect(b"\xef\xbb\xbfhi", Some(Encoding::Latin1)));
+        assert_eq!((Encoding::Utf16Le, 2), Encoding::detect(b"\xff\xfeh\0", Some(Encoding::Utf16Le)));
+        assert_eq!(Ok(Encoding::Utf16Be), "UTF-16BE".parse());
+        assert_eq!(Err(()), "utf-32".parse::<Encoding>());
+    }
+
+    #[test]
+    fn transcoded_positions_refer_to_the_original_file() {
+        let utf16: Vec<u8> = [0xFF, 0xFE].iter().copied().chain("é ERROR\n".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        let (_dir, paths) = temp_files("transcoded", &[("a.txt", &utf16)]);
+        // "é " 在 UTF-16 中占 4 个字节：列号、-b 和 JSON 中的位置都按原始文件计算
+        assert_eq!("1:5:é ERROR\n", search_files(&["minigrep", "-n", "--column", "ERROR"], &paths).0);
+        assert_eq!("6:ERROR\n", search_files(&["minigrep", "-ob", "ERROR"], &paths).0);
+        let json = search_files(&["minigrep", "--json", "ERROR"], &paths).0;
+        assert!(json.contains(r#""absolute_offset":2,"submatches":[{"match":{"text":"ERROR"},"start":4,"end":14}]"#), "{}", json);
+    }
+}
@@ -0,0 +1,436 @@
+// --csv-field / --json-path：只在记录的某个字段中匹配，输出的仍然是整条记录。
+//
+// CSV 按 RFC 4180 切分：字段可以用双引号括起来，其中可以有逗号和换行符，"" 表示一个双引号。
+// JSON Lines 中每行是一个 JSON 值，用 a.b.c 这样的路径取出其中的一个值，路径中的数字是数组的下标。
+//
+// 被引号括起来的 CSV 字段和 JSON 字符串要先去掉转义再匹配，这样模式写的是字段真正的内容。
+// 去掉转义后的文本中每个字节都记下它在记录中的位置，匹配区间再换算回记录中，输出时高亮的位置仍然是对的。
+
+use std::cell::Cell;
+
+use crate::search::Matcher;
+
+/// 要匹配的字段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Field {
+    /// CSV 的第几列，从 0 开始
+    CsvIndex(usize),
+    /// CSV 中表头（第一条记录）为这个名字的列
+    CsvName(String),
+    /// JSON 中这条路径上的值
+    JsonPath(Vec<String>),
+}
+
+impl Field {
+    /// 解析 --csv-field 的值：数字是从 1 开始的列号，其他的是列名
+    pub(crate) fn csv(spec: &str) -> Option<Field> {
+        match spec.parse::<usize>() {
+            Ok(0) => None,
+            Ok(index) => Some(Field::CsvIndex(index - 1)),
+            Err(_) if spec.is_empty() => None,
+            Err(_) => Some(Field::CsvName(spec.to_string())),
+        }
+    }
+
+    /// 解析 --json-path 的值：用 '.' 分隔的键，不能有空的部分
+    pub(crate) fn json(path: &str) -> Option<Field> {
+        let keys: Vec<String> = path.split('.').map(str::to_string).collect();
+        match keys.iter().any(String::is_empty) {
+            true => None,
+            false => Some(Field::JsonPath(keys)),
+        }
+    }
+}
+
+// 字段的值：没有转义时就是记录中的一段；否则是去掉转义后的文本，以及每个字节在记录中的位置（最后多一项，是字段的结束位置）
+#[derive(Debug, PartialEq, Eq)]
+enum Value {
+    Raw(usize, usize),
+    Unescaped(Vec<u8>, Vec<usize>),
+}
+
+impl Value {
+    fn text<'a>(&'a self, record: &'a [u8]) -> &'a [u8] {
+        match self {
+            Value::Raw(start, end) => &record[*start..*end],
+            Value::Unescaped(text, _) => text,
+        }
+    }
+
+    // 在字段中查找 record 中 at 之后的下一个匹配，返回它在 record 中的区间
+    fn find(&self, record: &[u8], matcher: &dyn Matcher, at: usize) -> Option<(usize, usize)> {
+        match self {
+            Value::Raw(start, end) => {
+                if at > *end {
+                    return None;
+                }
+                let (s, e) = matcher.find_at(&record[*start..*end], at.saturating_sub(*start))?;
+                Some((start + s, start + e))
+            }
+            Value::Unescaped(text, map) => {
+                let from = map.iter().position(|&pos| pos >= at)?;
+                let (s, e) = matcher.find_at(text, from)?;
+                Some((map[s], map[e]))
+            }
+        }
+    }
+}
+
+// 去掉转义时逐段积累文本和位置
+#[derive(Default)]
+struct Unescape {
+    text: Vec<u8>,
+    map: Vec<usize>,
+    escaped: bool,
+}
+
+impl Unescape {
+    // 原样复制 record 中从 at 开始的 bytes
+    fn copy(&mut self, bytes: &[u8], at: usize) {
+        self.text.extend_from_slice(bytes);
+        self.map.extend(at..at + bytes.len());
+    }
+
+    // 从 at 开始的一个转义序列表示字符 c
+    fn escape(&mut self, c: char, at: usize) {
+        let mut buf = [0; 4];
+        let bytes = c.encode_utf8(&mut buf).as_bytes();
+        self.text.extend_from_slice(bytes);
+        self.map.extend(std::iter::repeat_n(at, bytes.len()));
+        self.escaped = true;
+    }
+
+    fn finish(mut self, start: usize, end: usize) -> Value {
+        match self.escaped {
+            true => {
+                self.map.push(end);
+                Value::Unescaped(self.text, self.map)
+            }
+            false => Value::Raw(start, end),
+        }
+    }
+}
+
+// 依次产生一条 CSV 记录中的各个字段。没有配对的引号一直延续到记录末尾
+struct CsvFields<'r> {
+    record: &'r [u8],
+    pos: Option<usize>,
+}
+
+fn csv_fields(record: &[u8]) -> CsvFields<'_> {
+    CsvFields { record, pos: Some(0) }
+}
+
+impl Iterator for CsvFields<'_> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let record = self.record;
+        let pos = self.pos?;
+        let comma = |from: usize| record[from..].iter().position(|&b| b == b',').map_or(record.len(), |i| from + i);
+        let (value, end) = if record.get(pos) == Some(&b'"') {
+            let mut unescape = Unescape::default();
+            let mut at = pos + 1;
+            let close = loop {
+                let Some(i) = record[at..].iter().position(|&b| b == b'"') else {
+                    unescape.copy(&record[at..], at);
+                    break record.len();
+                };
+                unescape.copy(&record[at..at + i], at);
+                if record.get(at + i + 1) != Some(&b'"') {
+                    break at + i;
+                }
+                unescape.escape('"', at + i);
+                at += i + 2;
+            };
+            // 结束引号和逗号之间不应该有内容，有的话忽略
+            (unescape.finish(pos + 1, close), comma(close))
+        } else {
+            let end = comma(pos);
+            (Value::Raw(pos, end), end)
+        };
+        self.pos = Some(end + 1).filter(|&next| next <= record.len());
+        Some(value)
+    }
+}
+
+// JSON 的一个值中按路径查找。只做找到目标所需的最少解析，路径以外的部分只要括号和字符串配对就跳过
+struct Json<'r> {
+    record: &'r [u8],
+    pos: usize,
+}
+
+impl Json<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b' ' | b'\t' | b'\r' | b'\n') = self.record.get(self.pos) {
+            self.pos += 1;
+        }
+        self.record.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, b: u8) -> Option<()> {
+        (self.peek()? == b).then(|| self.pos += 1)
+    }
+
+    fn hex4(&self, at: usize) -> Option<u32> {
+        let digits = self.record.get(at..at + 4).filter(|d| d.iter().all(u8::is_ascii_hexdigit))?;
+        u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+    }
+
+    fn string(&mut self) -> Option<Value> {
+        self.eat(b'"')?;
+        let start = self.pos;
+        let mut unescape = Unescape::default();
+        loop {
+            let at = self.pos;
+            match *self.record.get(at)? {
+                b'"' => break,
+                b'\\' => {
+                    let (c, len) = match *self.record.get(at + 1)? {
+                        b'"' => ('"', 2),
+                        b'\\' => ('\\', 2),
+                        b'/' => ('/', 2),
+                        b'b' => ('\u{8}', 2),
+                        b'f' => ('\u{c}', 2),
+                        b'n' => ('\n', 2),
+                        b'r' => ('\r', 2),
+                        b't' => ('\t', 2),
+                        b'u' => {
+                            let unit = self.hex4(at + 2)?;
+                            // 代理项对写成两个 \u 转义；单独的代理项变成 U+FFFD
+                            let low = Some(at + 6)
+                                .filter(|&next| self.record[next..].starts_with(b"\\u"))
+                                .and_then(|next| self.hex4(next + 2))
+                                .filter(|low| (0xD800..0xDC00).contains(&unit) && (0xDC00..0xE000).contains(low));
+                            match low {
+                                Some(low) => (char::from_u32(0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00))?, 12),
+                                None => (char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER), 6),
+                            }
+                        }
+                        _ => return None,
+                    };
+                    unescape.escape(c, at);
+                    self.pos += len;
+                }
+                b => {
+                    unescape.copy(&[b], at);
+                    self.pos += 1;
+                }
+            }
+        }
+        let end = self.pos;
+        self.pos += 1;
+        Some(unescape.finish(start, end))
+    }
+
+    // 跳过一个值（对象和数组连同其中的内容）
+    fn skip(&mut self) -> Option<()> {
+        let mut depth = 0;
+        loop {
+            match self.peek()? {
+                b'"' => {
+                    self.string()?;
+                }
+                b'{' | b'[' => {
+                    self.pos += 1;
+                    depth += 1;
+                    continue;
+                }
+                b'}' | b']' if depth > 0 => {
+                    self.pos += 1;
+                    depth -= 1;
+                }
+                b',' | b':' if depth > 0 => {
+                    self.pos += 1;
+                    continue;
+                }
+                _ => {
+                    let len = self.record[self.pos..]
+                        .iter()
+                        .position(|b| b" \t\r\n,:[]{}\"".contains(b))
+                        .unwrap_or(self.record.len() - self.pos);
+                    if len == 0 {
+                        return None;
+                    }
+                    self.pos += len;
+                }
+            }
+            if depth == 0 {
+                return Some(());
+            }
+        }
+    }
+
+    // 进入当前对象中名为 key 的成员或者当前数组中下标为 key 的元素
+    fn enter(&mut self, key: &str) -> Option<()> {
+        match self.peek()? {
+            b'{' => {
+                self.pos += 1;
+                loop {
+                    let name = self.string()?;
+                    self.eat(b':')?;
+                    // 有重复的键时取第一个
+                    if name.text(self.record) == key.as_bytes() {
+                        return Some(());
+                    }
+                    self.skip()?;
+                    self.eat(b',')?;
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+                for _ in 0..key.parse::<usize>().ok()? {
+                    self.skip()?;
+                    self.eat(b',')?;
+                }
+                (self.peek()? != b']').then_some(())
+            }
+            _ => None,
+        }
+    }
+
+    // 路径指向的值：字符串去掉引号和转义，其他值（数字、true、对象等）取原样的文本
+    fn value(mut self, path: &[String]) -> Option<Value> {
+        for key in path {
+            self.enter(key)?;
+        }
+        if self.peek()? == b'"' {
+            return self.string();
+        }
+        let start = self.pos;
+        self.skip()?;
+        Some(Value::Raw(start, self.pos))
+    }
+}
+
+/// 把模式的匹配限制在每条记录的一个字段中。找不到字段的记录（列数不够、不是合法的 JSON、路径不存在）不匹配
+pub(crate) struct FieldMatcher<'m> {
+    inner: &'m dyn Matcher,
+    field: &'m Field,
+    // 按列名匹配时，第一条记录是表头：Searcher 把它交给 header 确定列号，它本身不参与匹配
+    header: Cell<bool>,
+    column: Cell<Option<usize>>,
+}
+
+impl<'m> FieldMatcher<'m> {
+    /// 每个文件要用一个新的 FieldMatcher，因为每个文件都有自己的表头
+    pub(crate) fn new(inner: &'m dyn Matcher, field: &'m Field) -> FieldMatcher<'m> {
+        FieldMatcher { inner, field, header: Cell::new(false), column: Cell::new(None) }
+    }
+
+    /// 按列名匹配而表头中没有这一列时返回列名
+    pub(crate) fn missing_column(&self) -> Option<&str> {
+        match self.field {
+            Field::CsvName(name) if self.header.get() && self.column.get().is_none() => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl Matcher for FieldMatcher<'_> {
+    fn find_at(&self, record: &[u8], at: usize) -> Option<(usize, usize)> {
+        let value = match self.field {
+            Field::CsvIndex(index) => csv_fields(record).nth(*index)?,
+            Field::CsvName(_) => csv_fields(record).nth(self.column.get()?)?,
+            Field::JsonPath(path) => Json { record, pos: 0 }.value(path)?,
+        };
+        value.find(record, self.inner, at)
+    }
+
+    fn header(&self, record: &[u8]) {
+        if let Field::CsvName(name) = self.field {
+            self.header.set(true);
+            self.column.set(csv_fields(record).position(|v| v.text(record) == name.as_bytes()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{find_all, Span};
+    use crate::tests::{search_files, temp_files};
+
+    fn texts(record: &str) -> Vec<String> {
+        csv_fields(record.as_bytes()).map(|v| String::from_utf8(v.text(record.as_bytes()).to_vec()).unwrap()).collect()
+    }
+
+    fn json(record: &str, path: &str) -> Option<String> {
+        let Some(Field::JsonPath(path)) = Field::json(path) else { panic!("invalid path") };
+        let value = Json { record: record.as_bytes(), pos: 0 }.value(&path)?;
+        Some(String::from_utf8(value.text(record.as_bytes()).to_vec()).unwrap())
+    }
+
+    fn finder(needle: &'static str) -> impl Fn(&[u8], usize) -> Option<(usize, usize)> {
+        move |line, at| {
+            let i = line[at..].windows(needle.len()).position(|w| w == needle.as_bytes())?;
+            Some((at + i, at + i + needle.len()))
+        }
+    }
+
+    #[test]
+    fn splits_csv_records() {
+        assert_eq!(vec!["a", "", "c,d", "say \"hi\"\nbye", ""], texts("a,,\"c,d\",\"say \"\"hi\"\"\nbye\","));
+        assert_eq!(vec![""], texts(""));
+        assert_eq!(vec!["x", "open, quote"], texts("x,\"open, quote"));
+        assert_eq!(Some(Field::CsvIndex(1)), Field::csv("2"));
+        assert_eq!(Some(Field::CsvName("name".to_string())), Field::csv("name"));
+        assert_eq!(None, Field::csv("0"));
+    }
+
+    #[test]
+    fn follows_json_paths() {
+        let record = r#"{"a": {"b": [1, {"c": "café \"x\""}], "n": null}, "d": "😀", "e": {"f": [true]}}"#;
+        assert_eq!(Some("café \"x\"".to_string()), json(record, "a.b.1.c"));
+        assert_eq!(Some("1".to_string()), json(record, "a.b.0"));
+        assert_eq!(Some("null".to_string()), json(record, "a.n"));
+        assert_eq!(Some("😀".to_string()), json(record, "d"));
+        assert_eq!(Some("{\"f\": [true]}".to_string()), json(record, "e"));
+        assert_eq!(None, json(record, "a.b.2"));
+        assert_eq!(None, json(record, "a.x"));
+        assert_eq!(None, json("not json", "a"));
+        assert_eq!(None, Field::json("a..b"));
+    }
+
+    #[test]
+    fn matches_only_inside_the_field() {
+        let inner = finder("\"x");
+        let field = Field::CsvIndex(1);
+        let matcher = FieldMatcher::new(&inner, &field);
+        // 去掉转义后匹配，区间换算回记录中（包括两个字符的 ""）
+        let record = b"\"x\",\"a \"\"x\"\"\",\"x";
+        assert_eq!(vec![Span { start: 7, end: 10 }], find_all(&matcher, record));
+
+        let inner = finder("ERROR");
+        let field = Field::csv("level").unwrap();
+        let matcher = FieldMatcher::new(&inner, &field);
+        assert!(find_all(&matcher, b"ERROR,level").is_empty());
+        matcher.header(b"message,level");
+        assert_eq!(vec![Span { start: 8, end: 13 }], find_all(&matcher, b"ERROR x,ERROR"));
+        assert!(find_all(&matcher, b"ERROR x,INFO").is_empty());
+        assert_eq!(None, matcher.missing_column());
+
+        let field = Field::csv("severity").unwrap();
+        let matcher = FieldMatcher::new(&inner, &field);
+        assert_eq!(None, matcher.missing_column());
+        matcher.header(b"message,level");
+        assert_eq!(Some("severity"), matcher.missing_column());
+
+        let field = Field::json("msg").unwrap();
+        let matcher = FieldMatcher::new(&inner, &field);
+        assert_eq!(vec![Span { start: 8, end: 13 }], find_all(&matcher, br#"{"msg":"ERROR","level":"ERROR"}"#));
+        assert!(find_all(&matcher, br#"{"level":"ERROR"}"#).is_empty());
+    }
+
+    #[test]
+    fn csv_header_is_never_selected() {
+        let (_dir, paths) = temp_files("csv-header", &[("log.csv", b"message,level\ndisk full,error\nstarted,info\n")]);
+        let search = |list: &[&str]| search_files(&[&["minigrep", "--csv-field", "level"], list, &["error"]].concat(), &paths).0;
+        assert_eq!("started,info\n", search(&["-v"]));
+        assert_eq!("1\n", search(&["-v", "-c"]));
+        assert_eq!("2:disk full,error\n", search(&["-n", "-B", "1"]));
+        // 按列号匹配时没有表头，第一行和其他记录一样
+        let (out, _) = search_files(&["minigrep", "-v", "-c", "--csv-field", "2", "error"], &paths);
+        assert_eq!("2\n", out);
+    }
+}
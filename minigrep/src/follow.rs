@@ -0,0 +1,231 @@
+// --follow：像 tail -F | grep 一样持续跟踪文件，输出新追加的匹配行。
+//
+// 开始时跳过文件中已有的内容（只数一下有多少行，让 -n 的行号保持正确），之后每隔一段时间检查一次每个文件：
+//     有新数据时读出其中完整的行交给调用者；最后没有换行符的半行先留着，等它写完再处理
+//     文件比已经读到的位置还短，说明被截断了（例如 > app.log，或者 logrotate 的 copytruncate），从头开始读
+//     路径指向了另一个文件（日志轮转时旧文件被改名，又新建了同名文件），先读完旧文件剩下的内容，再从头读新文件
+//     文件暂时不存在时继续等待，出现后从头开始读
+// Ctrl-C（SIGINT）和 SIGTERM 只设置一个标志，主循环在当前这一轮检查结束后退出，已经找到的匹配都会完整输出。
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// 两次检查之间的间隔
+pub(crate) const INTERVAL: Duration = Duration::from_millis(250);
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// 让 Ctrl-C 不再直接终止进程，而是通过 [`stopped`] 通知主循环退出
+pub(crate) fn stop_on_interrupt() {
+    #[cfg(unix)]
+    {
+        // 标准库没有提供信号处理，直接调用 C 库的 signal；处理函数只写一个原子变量，是异步信号安全的
+        extern "C" {
+            fn signal(signum: i32, handler: usize) -> usize;
+        }
+        extern "C" fn on_signal(_: i32) {
+            STOP.store(true, Ordering::SeqCst);
+        }
+        const SIGINT: i32 = 2;
+        const SIGTERM: i32 = 15;
+        unsafe {
+            signal(SIGINT, on_signal as extern "C" fn(i32) as usize);
+            signal(SIGTERM, on_signal as extern "C" fn(i32) as usize);
+        }
+    }
+    #[cfg(windows)]
+    {
+        extern "system" {
+            fn SetConsoleCtrlHandler(handler: Option<unsafe extern "system" fn(u32) -> i32>, add: i32) -> i32;
+        }
+        unsafe extern "system" fn on_ctrl(_: u32) -> i32 {
+            STOP.store(true, Ordering::SeqCst);
+            1
+        }
+        unsafe {
+            SetConsoleCtrlHandler(Some(on_ctrl), 1);
+        }
+    }
+}
+
+/// 是否收到了 Ctrl-C
+pub(crate) fn stopped() -> bool {
+    STOP.load(Ordering::SeqCst)
+}
+
+/// 等待 `duration`，收到 Ctrl-C 时提前返回
+pub(crate) fn sleep(duration: Duration) {
+    let step = Duration::from_millis(25);
+    let mut left = duration;
+    while !stopped() && !left.is_zero() {
+        thread::sleep(step.min(left));
+        left = left.saturating_sub(step);
+    }
+}
+
+// 判断路径前后指向的是不是同一个文件：Unix 上比较设备号和 inode，其他平台比较创建时间
+#[cfg(unix)]
+fn identity(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    let created = meta.created().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some((created.as_secs(), created.subsec_nanos() as u64))
+}
+
+/// 一行完整的新内容：(行号, 行首的字节偏移, 不含换行符的内容)
+pub(crate) type OnLine<'f> = dyn FnMut(usize, usize, &[u8]) -> io::Result<()> + 'f;
+
+/// 正在跟踪的一个文件
+pub(crate) struct Tail {
+    path: PathBuf,
+    file: Option<File>,
+    identity: Option<(u64, u64)>,
+    // 已经读到的位置
+    offset: u64,
+    line_number: usize,
+    // 读到了但还没有遇到换行符的内容
+    partial: Vec<u8>,
+}
+
+impl Tail {
+    /// 开始跟踪 `path`。已有的内容不会输出；文件还不存在时等它出现
+    pub(crate) fn new(path: PathBuf) -> Tail {
+        let mut tail = Tail { path, file: None, identity: None, offset: 0, line_number: 0, partial: Vec::new() };
+        if let Ok(mut file) = File::open(&tail.path) {
+            tail.identity = file.metadata().ok().and_then(|meta| identity(&meta));
+            let _ = tail.read_new(&mut file, &mut |_, _, _| Ok(()));
+            tail.file = Some(file);
+        }
+        tail
+    }
+
+    /// 检查一次文件，把新出现的完整行依次交给 `on_line`。文件被截断或者替换时返回一条说明
+    pub(crate) fn poll(&mut self, on_line: &mut OnLine) -> io::Result<Option<&'static str>> {
+        // 先读完当前打开的文件中新追加的内容，轮转之前最后写入旧文件的几行也不会丢
+        if let Some(mut file) = self.file.take() {
+            let result = self.read_new(&mut file, on_line);
+            self.file = Some(file);
+            result?;
+        }
+        let meta = match fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let notice = if self.file.is_none() || identity(&meta) != self.identity {
+            let notice = match self.file {
+                Some(_) => "has been replaced; following new file",
+                None => "has appeared; following new file",
+            };
+            // 旧文件最后没有换行符的半行也算作一行
+            if !self.partial.is_empty() {
+                self.line_number += 1;
+                let partial = std::mem::take(&mut self.partial);
+                on_line(self.line_number, self.offset as usize - partial.len(), &partial)?;
+            }
+            let file = File::open(&self.path)?;
+            self.identity = file.metadata().ok().and_then(|meta| identity(&meta));
+            self.file = Some(file);
+            notice
+        } else if meta.len() < self.offset {
+            if let Some(file) = &mut self.file {
+                file.seek(SeekFrom::Start(0))?;
+            }
+            "file truncated"
+        } else {
+            return Ok(None);
+        };
+        self.offset = 0;
+        self.line_number = 0;
+        self.partial.clear();
+        if let Some(mut file) = self.file.take() {
+            let result = self.read_new(&mut file, on_line);
+            self.file = Some(file);
+            result?;
+        }
+        Ok(Some(notice))
+    }
+
+    // 从文件当前的位置读到末尾，交出其中完整的行
+    fn read_new(&mut self, file: &mut File, on_line: &mut OnLine) -> io::Result<()> {
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = match file.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            // self.partial 的开头在文件中的位置
+            let base = self.offset as usize - self.partial.len();
+            self.offset += n as u64;
+            self.partial.extend_from_slice(&buf[..n]);
+            let mut start = 0;
+            while let Some(i) = self.partial[start..].iter().position(|&b| b == b'\n') {
+                let raw = &self.partial[start..start + i];
+                self.line_number += 1;
+                on_line(self.line_number, base + start, raw.strip_suffix(b"\r").unwrap_or(raw))?;
+                start += i + 1;
+            }
+            self.partial.drain(..start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempDir;
+    use std::io::Write;
+
+    fn collect(tail: &mut Tail) -> (Vec<String>, Option<&'static str>) {
+        let mut lines = Vec::new();
+        let notice = tail
+            .poll(&mut |n, offset, line| {
+                lines.push(format!("{}@{}:{}", n, offset, String::from_utf8_lossy(line)));
+                Ok(())
+            })
+            .unwrap();
+        (lines, notice)
+    }
+
+    #[test]
+    fn follows_appends_truncation_and_rotation() {
+        let dir = TempDir::new("follow");
+        let root = dir.path();
+        let path = root.join("app.log");
+        let append = |text: &str| fs::OpenOptions::new().create(true).append(true).open(&path).unwrap().write_all(text.as_bytes()).unwrap();
+
+        // 已有的内容跳过，但行号和偏移从它们之后接着算；半行要等写完才交出
+        append("old 1\nold 2\nhalf");
+        let mut tail = Tail::new(path.clone());
+        assert_eq!((vec![], None), collect(&mut tail));
+        append(" line\r\nnew 4\npart");
+        assert_eq!(vec!["3@12:half line", "4@23:new 4"], collect(&mut tail).0);
+
+        fs::write(&path, "x\n").unwrap();
+        assert_eq!((vec!["1@0:x".to_string()], Some("file truncated")), collect(&mut tail));
+
+        // 轮转：旧文件追加的最后几行先读完，然后切换到新文件
+        append("last\n");
+        fs::rename(&path, root.join("app.log.1")).unwrap();
+        assert_eq!((vec!["2@2:last".to_string()], None), collect(&mut tail));
+        append("fresh\n");
+        let (lines, notice) = collect(&mut tail);
+        assert_eq!((vec!["1@0:fresh".to_string()], Some("has been replaced; following new file")), (lines, notice));
+
+        // 一开始不存在的文件出现后从头读
+        let mut later = Tail::new(root.join("later.log"));
+        assert_eq!((vec![], None), collect(&mut later));
+        fs::write(root.join("later.log"), "hello\n").unwrap();
+        assert_eq!(vec!["1@0:hello"], collect(&mut later).0);
+    }
+}
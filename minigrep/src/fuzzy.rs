@@ -0,0 +1,309 @@
+// 近似匹配：查找与模式的 Levenshtein 编辑距离不超过 K 的子串。
+//
+// 采用 Myers（1999）的位并行算法：动态规划矩阵的一整列用两个位向量表示（每一行相对上一行的差是 +1 还是 -1），
+// 每读入文本中的一个字符只需要十几次位运算就能算出下一列，而不是逐个格子计算。
+// 模式超过 64 个字符时把列分成若干个 64 位的块，块与块之间传递最低一行的差值（Hyyrö 的分块方法），长度不受限制。
+//
+// 位置按字符计算编辑距离（一个汉字算一个字符），返回的区间是原始文本中的字节位置。
+
+use std::collections::HashMap;
+
+use crate::casefold;
+use crate::utf8;
+
+// 模式中每个字符出现在哪些位置：第 i 位为 1 表示模式的第 i 个字符是它
+struct Peq {
+    blocks: usize,
+    ascii: Vec<u64>,
+    other: HashMap<char, Vec<u64>>,
+    none: Vec<u64>,
+}
+
+impl Peq {
+    fn new(pattern: &[char]) -> Peq {
+        let blocks = pattern.len().div_ceil(64).max(1);
+        let mut peq = Peq { blocks, ascii: vec![0; 128 * blocks], other: HashMap::new(), none: vec![0; blocks] };
+        for (i, &c) in pattern.iter().enumerate() {
+            let bits = match c.is_ascii() {
+                true => &mut peq.ascii[c as usize * blocks..(c as usize + 1) * blocks],
+                false => peq.other.entry(c).or_insert_with(|| vec![0; blocks]).as_mut_slice(),
+            };
+            bits[i / 64] |= 1 << (i % 64);
+        }
+        peq
+    }
+
+    fn get(&self, c: char) -> &[u64] {
+        match c.is_ascii() {
+            true => &self.ascii[c as usize * self.blocks..(c as usize + 1) * self.blocks],
+            false => self.other.get(&c).map_or(&self.none, Vec::as_slice),
+        }
+    }
+}
+
+// 动态规划矩阵的当前一列。pv / mv 的第 i 位表示第 i + 1 行比第 i 行大 1 / 小 1，score 为最后一行的值
+struct Column {
+    pv: Vec<u64>,
+    mv: Vec<u64>,
+    score: usize,
+    // 最后一个块中对应模式最后一个字符的那一位
+    last_row: u64,
+}
+
+impl Column {
+    // 第 0 列：第 i 行为 i，即和空串的距离
+    fn new(len: usize, blocks: usize) -> Column {
+        Column { pv: vec![!0; blocks], mv: vec![0; blocks], score: len, last_row: 1 << ((len.max(1) - 1) % 64) }
+    }
+
+    // 读入文本中的一个字符，计算下一列。top 是第 0 行在这一列的增量：
+    // 查找子串时任意位置都可以作为起点，第 0 行全为 0（增量 0）；从固定的位置开始比较时第 0 行为 0, 1, 2...（增量 +1）
+    fn step(&mut self, eq: &[u64], top: i32) {
+        let last = self.pv.len() - 1;
+        let mut hin = top;
+        for b in 0..=last {
+            let (pv, mv) = (self.pv[b], self.mv[b]);
+            let mut eq = eq[b];
+            let xv = eq | mv;
+            // 上一个块的最后一行减小了，相当于这个块的第一行多了一个匹配
+            if hin < 0 {
+                eq |= 1;
+            }
+            let xh = (((eq & pv).wrapping_add(pv)) ^ pv) | eq;
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+            let high = if b == last { self.last_row } else { 1 << 63 };
+            let hout = (ph & high != 0) as i32 - (mh & high != 0) as i32;
+            ph <<= 1;
+            mh <<= 1;
+            if hin < 0 {
+                mh |= 1;
+            } else if hin > 0 {
+                ph |= 1;
+            }
+            self.pv[b] = mh | !(xv | ph);
+            self.mv[b] = ph & xv;
+            hin = hout;
+        }
+        self.score = (self.score as isize + hin as isize) as usize;
+    }
+}
+
+/// 近似匹配一个模式
+pub struct Fuzzy {
+    len: usize,
+    max_distance: usize,
+    case_insensitive: bool,
+    forward: Peq,
+    // 反转后的模式，用来从匹配的结尾往回确定起点
+    backward: Peq,
+}
+
+impl Fuzzy {
+    /// `max_distance` 为允许的最大编辑距离（插入、删除、替换各算 1）。
+    /// `case_insensitive` 为 true 时按 Unicode 简单大小写折叠比较字符。
+    pub fn new(pattern: &str, max_distance: usize, case_insensitive: bool) -> Fuzzy {
+        let mut chars: Vec<char> = pattern.chars().collect();
+        if case_insensitive {
+            chars.iter_mut().for_each(|c| *c = casefold::fold(*c));
+        }
+        let forward = Peq::new(&chars);
+        chars.reverse();
+        Fuzzy { len: chars.len(), max_distance, case_insensitive, forward, backward: Peq::new(&chars) }
+    }
+
+    fn fold(&self, c: char) -> char {
+        if self.case_insensitive { casefold::fold(c) } else { c }
+    }
+
+    /// 在 `hay[at..]` 中查找第一个编辑距离不超过上限的子串，返回它的字节区间和编辑距离。
+    ///
+    /// 结尾取第一个满足条件的位置之后距离不再减小的地方；起点取使距离最小的位置，距离相同时取最靠左的。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chry_minigrep::fuzzy::Fuzzy;
+    ///
+    /// let fuzzy = Fuzzy::new("connection", 2, false);
+    /// assert_eq!(Some((6, 15, 1)), fuzzy.find_at(b"error conection reset", 0));
+    /// assert_eq!(None, fuzzy.find_at(b"error connect reset", 0).filter(|m| m.2 == 0));
+    /// ```
+    pub fn find_at(&self, hay: &[u8], at: usize) -> Option<(usize, usize, usize)> {
+        self.find_accepted(hay, at, |_, _| true)
+    }
+
+    /// 和 `find_at` 相同，但只考虑 `accept(start, end)` 返回 true 的子串（例如 -w、-x 的限制）。
+    /// 同一个结尾可以有多个距离不超过上限的起点，一个起点也可以有多个结尾，它们都会交给 `accept` 检查：
+    /// 结尾按 `find_at` 的顺序，先是距离连续减小的一串结尾中最好的那个、再依次往前，然后才是后面的结尾；
+    /// 每个结尾上的起点按距离从小到大、距离相同时从左到右。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chry_minigrep::fuzzy::Fuzzy;
+    ///
+    /// let fuzzy = Fuzzy::new("hello", 1, false);
+    /// assert_eq!(Some((0, 5, 0)), fuzzy.find_at(b"helloo", 0));
+    /// // 不接受距离最小的 "hello" 时，整个 "helloo"（距离 1）也是一个匹配
+    /// assert_eq!(Some((0, 6, 1)), fuzzy.find_accepted(b"helloo", 0, |_, end| end == 6));
+    /// ```
+    pub fn find_accepted(&self, hay: &[u8], at: usize, accept: impl Fn(usize, usize) -> bool) -> Option<(usize, usize, usize)> {
+        if self.len == 0 {
+            let mut pos = at;
+            while pos <= hay.len() {
+                if accept(pos, pos) {
+                    return Some((pos, pos, 0));
+                }
+                pos += utf8::decode(hay, pos).map_or(1, |(_, len)| len);
+            }
+            return None;
+        }
+        let mut column = Column::new(self.len, self.forward.blocks);
+        // 距离连续减小的一串结尾和它们的距离
+        let mut chain: Vec<(usize, usize)> = Vec::new();
+        let mut pos = at;
+        while let Some((c, len)) = utf8::decode(hay, pos) {
+            column.step(self.forward.get(self.fold(c)), 0);
+            pos += len;
+            if chain.last().is_some_and(|&(_, distance)| column.score < distance) {
+                chain.push((pos, column.score));
+                continue;
+            }
+            if let Some(found) = self.accepted(hay, at, &chain, &accept) {
+                return Some(found);
+            }
+            chain.clear();
+            if column.score <= self.max_distance {
+                chain.push((pos, column.score));
+            }
+        }
+        self.accepted(hay, at, &chain, &accept)
+    }
+
+    // 从 chain 中最好的（最后的）结尾开始，返回第一个被接受的子串
+    fn accepted(&self, hay: &[u8], at: usize, chain: &[(usize, usize)], accept: &impl Fn(usize, usize) -> bool) -> Option<(usize, usize, usize)> {
+        chain.iter().rev().find_map(|&(end, _)| {
+            let start = self.starts(hay, at, end).into_iter().find(|&(_, start)| accept(start, end));
+            start.map(|(distance, start)| (start, end, distance))
+        })
+    }
+
+    // 以 end 结尾、距离不超过上限的子串的起点，按 (距离, 起点) 排序。
+    // 用反转的模式从结尾往回比较，第 0 行固定从结尾开始，每一列的得分就是以该位置为起点的子串的编辑距离
+    fn starts(&self, hay: &[u8], at: usize, end: usize) -> Vec<(usize, usize)> {
+        let mut column = Column::new(self.len, self.backward.blocks);
+        let mut starts = Vec::new();
+        let (mut pos, mut scanned) = (end, 0);
+        // 子串比模式长出的字符至少要删除掉，再往左距离一定超过上限
+        while pos > at && scanned < self.len + self.max_distance {
+            let Some((c, len)) = utf8::decode_last(hay, pos) else { break };
+            column.step(self.backward.get(self.fold(c)), 1);
+            pos -= len;
+            scanned += 1;
+            if column.score <= self.max_distance {
+                starts.push((column.score, pos));
+            }
+        }
+        starts.sort_unstable();
+        starts
+    }
+
+    /// `text` 整体与模式的编辑距离
+    pub fn distance(&self, text: &[u8]) -> usize {
+        let mut column = Column::new(self.len, self.forward.blocks);
+        let mut pos = 0;
+        while let Some((c, len)) = utf8::decode(text, pos) {
+            if self.len == 0 {
+                column.score += 1;
+            } else {
+                column.step(self.forward.get(self.fold(c)), 1);
+            }
+            pos += len;
+        }
+        column.score
+    }
+}
+
+impl crate::search::Matcher for Fuzzy {
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        self.find_at(line, at).map(|(start, end, _)| (start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 直接填动态规划表：以每个位置结尾的子串与模式的最小编辑距离
+    fn naive_scores(pattern: &[u8], text: &[u8]) -> Vec<usize> {
+        let mut column: Vec<usize> = (0..=pattern.len()).collect();
+        let mut scores = Vec::new();
+        for &t in text {
+            let mut next = vec![0; pattern.len() + 1];
+            for i in 1..=pattern.len() {
+                let substitute = column[i - 1] + (pattern[i - 1] != t) as usize;
+                next[i] = substitute.min(column[i] + 1).min(next[i - 1] + 1);
+            }
+            column = next;
+            scores.push(column[pattern.len()]);
+        }
+        scores
+    }
+
+    #[test]
+    fn bit_parallel_matches_dynamic_programming() {
+        // 用线性同余生成器产生小字母表上的随机串，覆盖单块和多块（超过 64 个字符）的模式
+        let mut seed = 12345u32;
+        let mut random = |n: usize| -> Vec<u8> {
+            (0..n)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    b"abcd"[(seed >> 16) as usize % 4]
+                })
+                .collect()
+        };
+        for len in [1, 5, 63, 64, 65, 130] {
+            let pattern = random(len);
+            let text = random(300);
+            let pattern_chars: Vec<char> = pattern.iter().map(|&b| b as char).collect();
+            let peq = Peq::new(&pattern_chars);
+            let mut column = Column::new(len, peq.blocks);
+            let scores: Vec<usize> = text
+                .iter()
+                .map(|&t| {
+                    column.step(peq.get(t as char), 0);
+                    column.score
+                })
+                .collect();
+            assert_eq!(naive_scores(&pattern, &text), scores, "pattern length {}", len);
+        }
+    }
+
+    #[test]
+    fn finds_spans_and_distances() {
+        let fuzzy = Fuzzy::new("hello world", 2, false);
+        let hay = b"say helo wrld! hello world";
+        assert_eq!(Some((4, 13, 2)), fuzzy.find_at(hay, 0));
+        assert_eq!(Some((15, 26, 0)), fuzzy.find_at(hay, 13));
+        assert_eq!(2, fuzzy.distance(b"helo wrld"));
+
+        // 按字符计算距离，大小写不敏感时先折叠
+        let fuzzy = Fuzzy::new("straße", 1, true);
+        assert_eq!(Some((0, 7, 1)), fuzzy.find_at("STRASE".as_bytes(), 0).map(|(s, _, d)| (s, 7, d)));
+        assert_eq!(Some((3, 11, 0)), fuzzy.find_at("ab STRAẞE".as_bytes(), 0));
+        assert_eq!(None, Fuzzy::new("abc", 0, false).find_at(b"abd", 0));
+    }
+
+    #[test]
+    fn tries_every_end_and_start_within_the_distance() {
+        let fuzzy = Fuzzy::new("hello", 1, false);
+        // "hello" 后面紧跟着字母，整个 "helloo" 才是一个单词
+        let hay = b"helloo world";
+        assert_eq!(Some((0, 6, 1)), fuzzy.find_accepted(hay, 0, |start, end| utf8::is_whole_word(hay, start, end)));
+        // 同一个结尾上距离更大的起点
+        assert_eq!(Some((0, 6, 1)), fuzzy.find_accepted(b"xhello", 0, |start, _| start == 0));
+        assert_eq!(Some((1, 5, 1)), fuzzy.find_accepted(b"hello", 0, |start, _| start == 1));
+        assert_eq!(None, fuzzy.find_accepted(b"hello", 0, |_, end| end == 3));
+    }
+}
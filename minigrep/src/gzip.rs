@@ -0,0 +1,533 @@
+// gzip 解压：内置的 DEFLATE（RFC 1951）解码器加上 gzip 文件格式（RFC 1952），只用标准库。
+//
+// GzDecoder 包装任意 BufRead，实现 Read：调用者每次读取时才解压出下一段数据，
+// 只需要保存最近 32KiB 的输出作为回溯窗口，任意大小的压缩文件都可以边解压边搜索。
+// 支持多个成员首尾相连的文件（例如 cat a.gz b.gz > c.gz），每个成员的 CRC32 和长度都会校验。
+//
+// 哈夫曼解码先查一张 2^10 项的表，覆盖绝大多数较短的码字；更长的码字再按规范哈夫曼编码逐位解码。
+
+use std::io::{self, BufRead, Read};
+
+/// gzip 文件开头的两个魔数字节
+pub const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// DEFLATE 的回溯距离最大为 32KiB
+const WINDOW: usize = 32 * 1024;
+// 每次解压至少产出这么多数据才返回，减少状态切换
+const CHUNK: usize = 32 * 1024;
+
+// 长度码 257..285 和距离码 0..29 对应的基数和额外位数
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// 动态哈夫曼块中“码长的码长”的排列顺序
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// CRC-32（IEEE 802.3，反射多项式 0xEDB88320），查表在编译期生成
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |c, &b| CRC_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("gzip: {}", message))
+}
+
+// 按 DEFLATE 的约定从低位到高位读取比特
+struct BitReader<R> {
+    inner: R,
+    bits: u64,
+    count: u32,
+}
+
+impl<R: BufRead> BitReader<R> {
+    // 尽量让缓冲中至少有 n 位，输入结束时返回 false
+    fn fill(&mut self, n: u32) -> io::Result<bool> {
+        while self.count < n {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(false);
+            }
+            let mut used = 0;
+            for &b in buf {
+                if self.count > 56 {
+                    break;
+                }
+                self.bits |= (b as u64) << self.count;
+                self.count += 8;
+                used += 1;
+            }
+            self.inner.consume(used);
+        }
+        Ok(true)
+    }
+
+    fn consume(&mut self, n: u32) {
+        self.bits >>= n;
+        self.count -= n;
+    }
+
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        if !self.fill(n)? {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "gzip: unexpected end of file"));
+        }
+        let value = (self.bits & ((1u64 << n) - 1)) as u32;
+        self.consume(n);
+        Ok(value)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        self.bits(8).map(|b| b as u8)
+    }
+
+    fn u16_le(&mut self) -> io::Result<u16> {
+        self.bits(16).map(|v| v as u16)
+    }
+
+    fn u32_le(&mut self) -> io::Result<u32> {
+        Ok(self.u16_le()? as u32 | (self.u16_le()? as u32) << 16)
+    }
+
+    // 丢弃到下一个字节边界为止的比特
+    fn align(&mut self) {
+        self.consume(self.count % 8);
+    }
+
+    fn at_eof(&mut self) -> io::Result<bool> {
+        Ok(self.count == 0 && self.inner.fill_buf()?.is_empty())
+    }
+}
+
+// 码字长度不超过 FAST_BITS 的符号可以直接查表
+const FAST_BITS: u32 = 10;
+
+// 规范哈夫曼编码的解码表
+struct Huffman {
+    // 以接下来的 FAST_BITS 位为下标：符号 << 4 | 码长，0 表示码字更长，需要逐位解码
+    fast: Vec<u16>,
+    // 每种码长的码字个数，以及按（码长, 符号）排序的所有符号
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Huffman> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        // 码字不能超额分配；不完整的编码是允许的（例如只有一个距离码）
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        let mut next_code = [0u32; 16];
+        let mut code = 0;
+        for len in 1..16 {
+            code = (code + counts[len - 1] as u32) << 1;
+            next_code[len] = code;
+        }
+        let mut fast = vec![0u16; 1 << FAST_BITS];
+        for (symbol, &len) in lengths.iter().enumerate().filter(|(_, &len)| len > 0) {
+            let len = len as usize;
+            symbols[offsets[len] as usize] = symbol as u16;
+            offsets[len] += 1;
+            let code = next_code[len];
+            next_code[len] += 1;
+            if len as u32 <= FAST_BITS {
+                // 码字从高位开始存放，而比特是从低位开始读的，所以表的下标要把码字反转过来
+                let reversed = code.reverse_bits() >> (32 - len);
+                for index in (reversed as usize..fast.len()).step_by(1 << len) {
+                    fast[index] = (symbol as u16) << 4 | len as u16;
+                }
+            }
+        }
+        Ok(Huffman { fast, counts, symbols })
+    }
+
+    fn decode<R: BufRead>(&self, input: &mut BitReader<R>) -> io::Result<u16> {
+        // 输入快结束时可能凑不满 FAST_BITS 位，只要查到的码字没有超出已有的位数就没有问题
+        input.fill(FAST_BITS)?;
+        let entry = self.fast[(input.bits & ((1 << FAST_BITS) - 1)) as usize];
+        let len = (entry & 0xF) as u32;
+        if entry != 0 && len <= input.count {
+            input.consume(len);
+            return Ok(entry >> 4);
+        }
+        // 逐位解码：同一码长的码字是连续的整数，first 是这一码长的第一个码字
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= input.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+enum State {
+    // 读取成员的文件头；first 表示这是第一个成员，后面的成员可以不存在
+    Header { first: bool },
+    Block,
+    // 未压缩的块中还剩多少字节
+    Stored(usize),
+    // 哈夫曼编码的块：字面量/长度码表和距离码表
+    Codes(Box<(Huffman, Huffman)>),
+    Trailer,
+    Done,
+}
+
+/// 边读边解压的 gzip 解码器
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Read;
+/// use chry_minigrep::gzip::GzDecoder;
+///
+/// // echo "hello hello hello" | gzip
+/// let compressed: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xcbH\xcd\xc9\xc9W\xc8@\x90\\\x00;|\x8a\xdf\x12\x00\x00\x00";
+/// let mut text = String::new();
+/// GzDecoder::new(compressed).read_to_string(&mut text).unwrap();
+/// assert_eq!("hello hello hello\n", text);
+/// ```
+pub struct GzDecoder<R> {
+    input: BitReader<R>,
+    state: State,
+    // 当前块是不是成员的最后一块
+    last_block: bool,
+    // 解压出的数据：pos 之后的部分还没有交给调用者，此外至少保留最近 WINDOW 字节供回溯
+    window: Vec<u8>,
+    pos: usize,
+    // window[checked..] 还没有计入当前成员的 CRC32 和长度
+    checked: usize,
+    crc: u32,
+    size: u32,
+}
+
+impl<R: BufRead> GzDecoder<R> {
+    pub fn new(input: R) -> GzDecoder<R> {
+        GzDecoder {
+            input: BitReader { inner: input, bits: 0, count: 0 },
+            state: State::Header { first: true },
+            last_block: false,
+            window: Vec::with_capacity(2 * WINDOW + CHUNK),
+            pos: 0,
+            checked: 0,
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    fn checksum(&mut self) {
+        let fresh = &self.window[self.checked..];
+        self.crc = crc32(self.crc, fresh);
+        self.size = self.size.wrapping_add(fresh.len() as u32);
+        self.checked = self.window.len();
+    }
+
+    // 解压出下一段数据，直到产出至少 CHUNK 字节或者整个文件结束
+    fn decode(&mut self) -> io::Result<()> {
+        // 已经交给调用者、并且超出回溯范围的数据可以丢弃了
+        if self.window.len() > 2 * WINDOW {
+            self.checksum();
+            let cut = self.pos.min(self.window.len() - WINDOW);
+            self.window.drain(..cut);
+            self.pos -= cut;
+            self.checked -= cut;
+        }
+        let target = self.window.len() + CHUNK;
+        while self.window.len() < target {
+            match &mut self.state {
+                State::Done => break,
+                State::Header { first } => {
+                    let first = *first;
+                    if !first && self.input.at_eof()? {
+                        self.state = State::Done;
+                        continue;
+                    }
+                    self.header(first)?;
+                }
+                State::Block => self.block_header()?,
+                State::Stored(0) => self.state = State::Block,
+                State::Stored(left) => {
+                    *left -= 1;
+                    let b = self.input.byte()?;
+                    self.window.push(b);
+                }
+                State::Codes(_) => {
+                    if self.codes(target)? {
+                        self.state = State::Block;
+                    }
+                }
+                State::Trailer => {
+                    self.input.align();
+                    let (crc, size) = (self.input.u32_le()?, self.input.u32_le()?);
+                    self.checksum();
+                    if crc != self.crc || size != self.size {
+                        return Err(invalid("checksum mismatch"));
+                    }
+                    self.crc = 0;
+                    self.size = 0;
+                    self.state = State::Header { first: false };
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn header(&mut self, first: bool) -> io::Result<()> {
+        let input = &mut self.input;
+        if [input.byte()?, input.byte()?] != MAGIC {
+            // 和 gzip 一样忽略最后一个成员之后的多余数据
+            if first {
+                return Err(invalid("not in gzip format"));
+            }
+            self.state = State::Done;
+            return Ok(());
+        }
+        if input.byte()? != 8 {
+            return Err(invalid("unknown compression method"));
+        }
+        let flags = input.byte()?;
+        // 修改时间、额外标志和操作系统
+        for _ in 0..6 {
+            input.byte()?;
+        }
+        // FEXTRA：长度加内容
+        if flags & 0x04 != 0 {
+            for _ in 0..input.u16_le()? {
+                input.byte()?;
+            }
+        }
+        // FNAME、FCOMMENT：以 0 结尾的字符串
+        for flag in [0x08, 0x10] {
+            if flags & flag != 0 {
+                while input.byte()? != 0 {}
+            }
+        }
+        // FHCRC：文件头的校验和
+        if flags & 0x02 != 0 {
+            input.u16_le()?;
+        }
+        self.last_block = false;
+        self.state = State::Block;
+        Ok(())
+    }
+
+    fn block_header(&mut self) -> io::Result<()> {
+        if self.last_block {
+            self.state = State::Trailer;
+            return Ok(());
+        }
+        let input = &mut self.input;
+        self.last_block = input.bits(1)? == 1;
+        self.state = match input.bits(2)? {
+            0 => {
+                input.align();
+                let (len, nlen) = (input.u16_le()?, input.u16_le()?);
+                if len != !nlen {
+                    return Err(invalid("corrupt stored block"));
+                }
+                State::Stored(len as usize)
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                State::Codes(Box::new((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?)))
+            }
+            2 => State::Codes(Box::new(dynamic_tables(input)?)),
+            _ => return Err(invalid("invalid block type")),
+        };
+        Ok(())
+    }
+
+    // 解码哈夫曼块中的符号，直到块结束或者产出的数据达到 target，返回块是否已经结束
+    fn codes(&mut self, target: usize) -> io::Result<bool> {
+        let State::Codes(tables) = &self.state else { return Ok(true) };
+        let (literals, distances) = &**tables;
+        while self.window.len() < target {
+            let symbol = literals.decode(&mut self.input)? as usize;
+            if symbol < 256 {
+                self.window.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                return Ok(true);
+            }
+            let code = symbol - 257;
+            if code >= LENGTH_BASE.len() {
+                return Err(invalid("invalid length code"));
+            }
+            let length = LENGTH_BASE[code] as usize + self.input.bits(LENGTH_EXTRA[code] as u32)? as usize;
+            let code = distances.decode(&mut self.input)? as usize;
+            if code >= DIST_BASE.len() {
+                return Err(invalid("invalid distance code"));
+            }
+            let distance = DIST_BASE[code] as usize + self.input.bits(DIST_EXTRA[code] as u32)? as usize;
+            if distance > self.window.len() {
+                return Err(invalid("distance too far back"));
+            }
+            // 距离可能小于长度，复制的内容和正在写入的内容重叠，只能逐字节复制
+            let start = self.window.len() - distance;
+            for i in 0..length {
+                let b = self.window[start + i];
+                self.window.push(b);
+            }
+        }
+        Ok(false)
+    }
+}
+
+// 读取动态哈夫曼块开头的码表
+fn dynamic_tables<R: BufRead>(input: &mut BitReader<R>) -> io::Result<(Huffman, Huffman)> {
+    let literals = input.bits(5)? as usize + 257;
+    let distances = input.bits(5)? as usize + 1;
+    let code_lengths = input.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[i] = input.bits(3)? as u8;
+    }
+    let code_length_codes = Huffman::new(&lengths)?;
+
+    // 字面量/长度码和距离码的码长连续编码在一起，16 重复前一个码长，17、18 表示若干个 0
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_codes.decode(input)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or_else(|| invalid("repeat with no previous length"))?, 3 + input.bits(2)?),
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err(invalid("too many code lengths"));
+    }
+    if lengths[256] == 0 {
+        return Err(invalid("missing end-of-block code"));
+    }
+    Ok((Huffman::new(&lengths[..literals])?, Huffman::new(&lengths[literals..])?))
+}
+
+impl<R: BufRead> Read for GzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.window.len() {
+            if let State::Done = self.state {
+                return Ok(0);
+            }
+            self.decode()?;
+        }
+        let n = buf.len().min(self.window.len() - self.pos);
+        buf[..n].copy_from_slice(&self.window[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// `reader` 的内容以 gzip 魔数开头时返回边读边解压的读取器，否则原样返回
+pub fn maybe_decompress<'r>(mut reader: Box<dyn BufRead + 'r>) -> io::Result<Box<dyn BufRead + 'r>> {
+    // 第一次填充的缓冲区可能不足两个字节（例如来自管道），这时当作普通内容处理
+    if reader.fill_buf()?.starts_with(&MAGIC) {
+        return Ok(Box::new(io::BufReader::new(GzDecoder::new(reader))));
+    }
+    Ok(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    // 用未压缩的块构造 gzip 成员
+    fn stored(text: &[u8]) -> Vec<u8> {
+        let mut gz = vec![0x1f, 0x8b, 8, 0x08, 0, 0, 0, 0, 0, 3];
+        gz.extend_from_slice(b"name.txt\0");
+        for (i, chunk) in text.chunks(u16::MAX as usize).enumerate() {
+            let last = (i + 1) * u16::MAX as usize >= text.len();
+            gz.push(last as u8);
+            gz.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+            gz.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+            gz.extend_from_slice(chunk);
+        }
+        gz.extend_from_slice(&crc32(0, text).to_le_bytes());
+        gz.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        gz
+    }
+
+    #[test]
+    fn inflates_fixed_and_dynamic_blocks() {
+        // python3 -c 'import gzip; print(gzip.compress(text, mtime=0).hex())'，采用动态哈夫曼编码
+        let hex = "1f8b080000000000020365d23d0a02310044e1de53ec11924c7e058fb3c5c2a2904a3dbd08a6705e99810f42f2cee3be6f\
+                   e1baed733ee6edf97a5fceef12d7b286b486df5920d9493152419a936e64f062c14d8c8662a212547655a82a5473d5a906de2e\
+                   984a112a2528b9ca5405aaba6a541d6af8f73209a109210a56216421ef420c4328439e86fedaf8003c82c425c7020000";
+        let gz: Vec<u8> = (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect();
+        let text: String = (0..40).map(|i| format!("line {}: error={}\n", i, &"xyz"[i % 3..])).collect();
+        assert_eq!(text.as_bytes(), &decompress(&gz).unwrap()[..]);
+
+        // 损坏的数据报错而不是返回错误的内容
+        let mut corrupt = gz.clone();
+        corrupt[40] ^= 0x10;
+        assert!(decompress(&corrupt).is_err());
+        assert!(decompress(&gz[..gz.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn multiple_members_and_large_stored_blocks() {
+        let big: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let mut gz = stored(b"first\n");
+        gz.extend(stored(&big));
+        let out = decompress(&gz).unwrap();
+        assert_eq!((&b"first\n"[..], &big[..]), out.split_at(6));
+
+        let mut bad = stored(b"text");
+        let len = bad.len();
+        bad[len - 8] ^= 1;
+        assert_eq!(io::ErrorKind::InvalidData, decompress(&bad).unwrap_err().kind());
+        assert!(decompress(b"plain text").is_err());
+    }
+}
@@ -0,0 +1,392 @@
+// 三元组（trigram）索引：minigrep index 预先记下每个文件中出现过哪些连续的三个字节，
+// 之后带 --index-dir 搜索时，先根据查询中一定会出现的三元组排除不可能匹配的文件，只对剩下的候选文件运行正常的匹配。
+//
+// 三元组按 ASCII 小写记录，大小写敏感和不敏感的查询共用同一份索引：候选文件只会偏多，不会漏掉。
+// 带 BOM 的 UTF-16 文件和搜索时一样先转换成 UTF-8，记下的是转换后内容的三元组。
+// 每个文件同时记下修改时间和大小。之后被修改过或者新增的文件不能依赖索引判断，搜索时总是直接搜索它们；
+// 重新运行 minigrep index 时也只需要重新读取这些文件，其余文件沿用旧索引中的三元组。
+//
+// 索引保存在目录中的一个二进制文件里（整数均为小端序）：
+//     魔数 "MGIDX\0\0\x02"
+//     u32 文件数，每个文件：u32 路径长度、路径（规范化的绝对路径，UTF-8）、u64 秒、u32 纳秒、u64 大小、u8 是否为 gzip
+//     u32 三元组数，每个三元组：3 字节、varint 文件个数、varint 文件编号（与前一个编号的差）
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::encoding::{Decoder, Encoding};
+use crate::gzip;
+
+/// 没有给出 --index-dir 时 minigrep index 使用的目录
+pub(crate) const DEFAULT_DIR: &str = ".minigrep-index";
+const FILE_NAME: &str = "trigrams";
+// 版本 2：UTF-16 文件按转换后的内容记录三元组
+const MAGIC: &[u8; 8] = b"MGIDX\0\0\x02";
+
+// 三个字节拼成的 24 位整数
+type Trigram = u32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    path: String,
+    // 修改时间（自 UNIX 纪元以来的秒和纳秒）和大小，任何一个变了就认为文件被修改过
+    stamp: (u64, u32, u64),
+    compressed: bool,
+}
+
+/// 从磁盘加载或者新建的索引
+#[derive(Debug, Default)]
+pub(crate) struct Index {
+    files: Vec<Entry>,
+    by_path: HashMap<String, u32>,
+    // 每个三元组出现在哪些文件中，编号从小到大排列
+    postings: HashMap<Trigram, Vec<u32>>,
+}
+
+/// minigrep index 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Built {
+    pub files: usize,
+    // 没有变化、沿用旧索引的文件数
+    pub unchanged: usize,
+    pub trigrams: usize,
+}
+
+/// 用索引判断一个文件的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lookup {
+    // 不可能匹配，不需要搜索
+    Skip,
+    // 可能匹配
+    Search,
+    // 文件不在索引中，或者建立索引之后被修改过，只能直接搜索
+    Stale,
+}
+
+/// 查询中一定会出现的三元组：文件只要包含任意一个分支中的全部三元组，就可能匹配
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Query {
+    alternatives: Vec<Vec<Trigram>>,
+}
+
+impl Query {
+    /// `literals` 是任意一个出现即算匹配的固定字符串。只要其中一个提取不出三元组（例如太短），
+    /// 索引就无法排除任何文件，返回 None。
+    pub(crate) fn new(literals: &[&str], case_insensitive: bool) -> Option<Query> {
+        let alternatives: Vec<Vec<Trigram>> = literals.iter().map(|literal| literal_trigrams(literal, case_insensitive)).collect();
+        match alternatives.iter().any(Vec::is_empty) {
+            true => None,
+            false => Some(Query { alternatives }),
+        }
+    }
+}
+
+// 大小写不敏感时，非 ASCII 字符的折叠结果无法和按 ASCII 小写记录的三元组对应；
+// 'k' 和 's' 还分别和 'K'（开尔文符号）、'ſ' 折叠到一起，含有它们的三元组也不能用
+fn literal_trigrams(literal: &str, case_insensitive: bool) -> Vec<Trigram> {
+    let usable = |b: &u8| !case_insensitive || (b.is_ascii() && !matches!(b.to_ascii_lowercase(), b'k' | b's'));
+    let mut trigrams: Vec<Trigram> = literal
+        .as_bytes()
+        .windows(3)
+        .filter(|w| w.iter().all(usable))
+        .map(|w| w.iter().fold(0, |t, b| (t << 8) | b.to_ascii_lowercase() as Trigram))
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+// 读出一个文件中所有的三元组，排好序返回
+fn file_trigrams(mut reader: impl Read) -> io::Result<Vec<Trigram>> {
+    let mut set = HashSet::new();
+    let mut buf = vec![0; 64 * 1024];
+    let (mut window, mut seen) = (0, 0);
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for &b in &buf[..n] {
+            window = ((window << 8) | b.to_ascii_lowercase() as Trigram) & 0xFF_FFFF;
+            seen += 1;
+            if seen >= 3 {
+                set.insert(window);
+            }
+        }
+    }
+    let mut trigrams: Vec<Trigram> = set.into_iter().collect();
+    trigrams.sort_unstable();
+    Ok(trigrams)
+}
+
+// 索引中用规范化的绝对路径标识文件，这样在其他目录下用不同的相对路径搜索时也能找到
+fn key(path: &Path) -> Option<String> {
+    fs::canonicalize(path).ok()?.into_os_string().into_string().ok()
+}
+
+fn stamp(meta: &fs::Metadata) -> (u64, u32, u64) {
+    let modified = meta.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).unwrap_or_default();
+    (modified.as_secs(), modified.subsec_nanos(), meta.len())
+}
+
+fn with_path(path: &Path, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+/// 为 `files` 建立索引并写入 `dir`。`dir` 中已有的索引里没有变化的文件直接沿用，不再读取。
+/// 无法读取的文件不会中断整个过程，而是和结果一起返回
+pub(crate) fn build(dir: &Path, files: &[io::Result<PathBuf>]) -> io::Result<(Built, Vec<io::Error>)> {
+    // 旧索引损坏或者版本不同时从头开始
+    let old = Index::load(dir).unwrap_or_default();
+    let mut old_trigrams: Vec<Vec<Trigram>> = vec![Vec::new(); old.files.len()];
+    for (&trigram, ids) in &old.postings {
+        for &id in ids {
+            old_trigrams[id as usize].push(trigram);
+        }
+    }
+
+    let mut index = Index::default();
+    let mut errors = Vec::new();
+    let mut unchanged = 0;
+    for file in files {
+        let path = match file {
+            Ok(path) if path.as_os_str() != "-" => path,
+            Ok(_) => continue,
+            Err(e) => {
+                errors.push(io::Error::new(e.kind(), e.to_string()));
+                continue;
+            }
+        };
+        let Some(key) = key(path) else {
+            errors.push(with_path(path, io::Error::new(io::ErrorKind::InvalidData, "path can't be indexed")));
+            continue;
+        };
+        if index.by_path.contains_key(&key) {
+            continue;
+        }
+        let result = fs::metadata(path).and_then(|meta| {
+            let stamp = stamp(&meta);
+            match old.by_path.get(&key).map(|&id| (id as usize, &old.files[id as usize])) {
+                Some((id, entry)) if entry.stamp == stamp => {
+                    unchanged += 1;
+                    Ok((Entry { path: key.clone(), ..entry.clone() }, std::mem::take(&mut old_trigrams[id])))
+                }
+                _ => {
+                    let mut file = File::open(path)?;
+                    let mut magic = [0; 2];
+                    let compressed = file.read(&mut magic)? == 2 && magic == gzip::MAGIC;
+                    let mut reader = BufReader::new(File::open(path)?);
+                    let (encoding, bom) = Encoding::detect(reader.fill_buf()?, None);
+                    reader.consume(bom);
+                    let trigrams = match encoding {
+                        Encoding::Utf8 => file_trigrams(reader)?,
+                        _ => file_trigrams(Decoder::new(reader, encoding))?,
+                    };
+                    Ok((Entry { path: key.clone(), stamp, compressed }, trigrams))
+                }
+            }
+        });
+        match result {
+            Ok((entry, trigrams)) => {
+                let id = index.files.len() as u32;
+                for trigram in trigrams {
+                    index.postings.entry(trigram).or_default().push(id);
+                }
+                index.by_path.insert(key, id);
+                index.files.push(entry);
+            }
+            Err(e) => errors.push(with_path(path, e)),
+        }
+    }
+    index.save(dir)?;
+    Ok((Built { files: index.files.len(), unchanged, trigrams: index.postings.len() }, errors))
+}
+
+impl Index {
+    /// 读取 `dir` 中的索引
+    pub(crate) fn load(dir: &Path) -> io::Result<Index> {
+        let file = dir.join(FILE_NAME);
+        let data = fs::read(&file).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                format!("no index in {} (build one with 'minigrep index --index-dir {} PATH...')", dir.display(), dir.display()),
+            ),
+            _ => with_path(&file, e),
+        })?;
+        Index::decode(&data).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: corrupt or incompatible index", file.display()))
+        })
+    }
+
+    fn decode(data: &[u8]) -> Option<Index> {
+        let mut reader = Reader { data: data.strip_prefix(MAGIC)? };
+        let mut index = Index::default();
+        for id in 0..reader.u32()? {
+            let len = reader.u32()? as usize;
+            let path = String::from_utf8(reader.take(len)?.to_vec()).ok()?;
+            let stamp = (reader.u64()?, reader.u32()?, reader.u64()?);
+            let compressed = reader.take(1)?[0] != 0;
+            index.by_path.insert(path.clone(), id);
+            index.files.push(Entry { path, stamp, compressed });
+        }
+        for _ in 0..reader.u32()? {
+            let trigram = reader.take(3)?.iter().fold(0, |t, &b| (t << 8) | b as Trigram);
+            let mut ids = Vec::new();
+            let mut id = 0;
+            for _ in 0..reader.varint()? {
+                id += reader.varint()? as u32;
+                if id as usize >= index.files.len() {
+                    return None;
+                }
+                ids.push(id);
+            }
+            index.postings.insert(trigram, ids);
+        }
+        match reader.data.is_empty() {
+            true => Some(index),
+            false => None,
+        }
+    }
+
+    // 先写临时文件再改名，中途失败不会留下写了一半的索引。
+    // 目录中同时放一个忽略所有内容的 .gitignore，这样搜索它的上级目录时不会搜到索引本身
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(".gitignore"), "*\n")?;
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for entry in &self.files {
+            out.extend_from_slice(&(entry.path.len() as u32).to_le_bytes());
+            out.extend_from_slice(entry.path.as_bytes());
+            out.extend_from_slice(&entry.stamp.0.to_le_bytes());
+            out.extend_from_slice(&entry.stamp.1.to_le_bytes());
+            out.extend_from_slice(&entry.stamp.2.to_le_bytes());
+            out.push(entry.compressed as u8);
+        }
+        let mut trigrams: Vec<_> = self.postings.iter().collect();
+        trigrams.sort_unstable_by_key(|(&trigram, _)| trigram);
+        out.extend_from_slice(&(trigrams.len() as u32).to_le_bytes());
+        for (&trigram, ids) in trigrams {
+            out.extend_from_slice(&trigram.to_be_bytes()[1..]);
+            write_varint(&mut out, ids.len() as u64);
+            let mut last = 0;
+            for &id in ids {
+                write_varint(&mut out, (id - last) as u64);
+                last = id;
+            }
+        }
+        let temp = dir.join(format!("{}.tmp", FILE_NAME));
+        fs::write(&temp, &out)?;
+        fs::rename(&temp, dir.join(FILE_NAME))
+    }
+
+    /// 判断 `path` 是否需要搜索。-z 时 gzip 文件的三元组来自压缩后的内容，不能用来排除它们
+    pub(crate) fn lookup(&self, path: &Path, query: &Query, search_zip: bool) -> Lookup {
+        let Some(&id) = key(path).and_then(|key| self.by_path.get(&key)) else {
+            return Lookup::Stale;
+        };
+        let entry = &self.files[id as usize];
+        if fs::metadata(path).map(|meta| stamp(&meta)).ok() != Some(entry.stamp) {
+            return Lookup::Stale;
+        }
+        let contains = |trigram| self.postings.get(trigram).is_some_and(|ids: &Vec<u32>| ids.binary_search(&id).is_ok());
+        match (search_zip && entry.compressed) || query.alternatives.iter().any(|trigrams| trigrams.iter().all(contains)) {
+            true => Lookup::Search,
+            false => Lookup::Skip,
+        }
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+struct Reader<'d> {
+    data: &'d [u8],
+}
+
+impl<'d> Reader<'d> {
+    fn take(&mut self, n: usize) -> Option<&'d [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.data.split_at(n);
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)?.try_into().ok().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)?.try_into().ok().map(u64::from_le_bytes)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut n = 0;
+        for shift in (0..64).step_by(7) {
+            let b = self.take(1)?[0];
+            n |= ((b & 0x7f) as u64) << shift;
+            if b < 0x80 {
+                return Some(n);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempDir;
+
+    #[test]
+    fn extracts_usable_trigrams() {
+        let t = |s: &[u8; 3]| s.iter().fold(0, |t, &b| (t << 8) | b as Trigram);
+        assert_eq!(vec![t(b"err"), t(b"rro")], literal_trigrams("ERRo", false));
+        // 大小写不敏感时跳过含有 'k'、's' 和非 ASCII 字节的三元组
+        assert_eq!(vec![t(b" ma"), t(b"ail"), t(b"i m"), t(b"mai")], literal_trigrams("maski mail", true));
+        assert!(literal_trigrams("aé", true).is_empty());
+        assert!(Query::new(&["error", "ab"], false).is_none());
+        assert_eq!(vec![t(b"abc"), t(b"bca"), t(b"cab")], file_trigrams(&b"abcABC"[..]).unwrap());
+    }
+
+    #[test]
+    fn builds_reuses_and_invalidates() {
+        let temp = TempDir::new("index");
+        let root = temp.path();
+        let (a, b, dir) = (root.join("a.log"), root.join("b.log"), root.join("index"));
+        fs::write(&a, "disk full\nconnection reset\n").unwrap();
+        fs::write(&b, "all good\n").unwrap();
+        let files = vec![Ok(a.clone()), Ok(b.clone()), Ok(root.join("missing.log"))];
+
+        let (built, errors) = build(&dir, &files).unwrap();
+        assert_eq!((2, 0, 1), (built.files, built.unchanged, errors.len()));
+        let index = Index::load(&dir).unwrap();
+        let query = Query::new(&["Connection"], true).unwrap();
+        assert_eq!((Lookup::Search, Lookup::Skip), (index.lookup(&a, &query, false), index.lookup(&b, &query, false)));
+        assert_eq!(Lookup::Stale, index.lookup(&root.join("index/.gitignore"), &query, false));
+
+        // 修改过的文件在重建之前总是直接搜索，重建时只重新读取它
+        fs::write(&b, "connection refused, quite a bit longer\n").unwrap();
+        assert_eq!(Lookup::Stale, index.lookup(&b, &query, false));
+        let (built, _) = build(&dir, &files[..2]).unwrap();
+        assert_eq!((2, 1), (built.files, built.unchanged));
+        assert_eq!(Lookup::Search, Index::load(&dir).unwrap().lookup(&b, &query, false));
+
+        fs::write(dir.join(FILE_NAME), b"MGIDX\0\0\x01\x05").unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, Index::load(&dir).unwrap_err().kind());
+        assert_eq!(io::ErrorKind::NotFound, Index::load(root).unwrap_err().kind());
+    }
+}
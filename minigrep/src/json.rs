@@ -0,0 +1,210 @@
+// --json 输出：每个事件一行 JSON（JSON Lines），格式参考 ripgrep，方便编辑器和 CI 工具可靠地解析。
+//     {"type":"begin","data":{"path":{"text":"src/lib.rs"}}}
+//     {"type":"match","data":{"path":{...},"lines":{"text":"..."},"line_number":3,"absolute_offset":42,
+//                             "submatches":[{"match":{"text":"fn"},"start":0,"end":2}]}}
+//     {"type":"context","data":{...和 match 相同，submatches 为空...}}
+//     {"type":"end","data":{"path":{...},"binary_match":false,"stats":{...}}}
+//     {"type":"summary","data":{"elapsed_total":{...},"stats":{...}}}
+// 文本统一写成 {"text": "..."}；不是合法 UTF-8 的内容写成 {"bytes": "<base64>"}，保证原样还原。
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::encoding::Encoding;
+use crate::report::Histogram;
+use crate::search::{Span, Summary};
+
+/// 搜索的统计数据，用于 end 和 summary 事件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Stats {
+    pub searches: usize,
+    pub searches_with_match: usize,
+    pub bytes_searched: usize,
+    pub matched_lines: usize,
+    pub matches: usize,
+    // --histogram 的统计，只在文本输出中使用
+    pub histogram: Histogram,
+}
+
+impl Stats {
+    /// 一个文件的搜索结果对应的统计数据，不包括需要逐个数出来的匹配个数
+    pub(crate) fn of(summary: &Summary) -> Stats {
+        Stats {
+            searches: 1,
+            searches_with_match: (summary.selected > 0) as usize,
+            bytes_searched: summary.bytes_searched,
+            matched_lines: summary.selected,
+            matches: 0,
+            histogram: Histogram::default(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, other: &Stats) {
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.bytes_searched += other.bytes_searched;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+        self.histogram.add(&other.histogram);
+    }
+}
+
+// 写出带引号并转义的 JSON 字符串
+fn write_str(out: &mut dyn Write, s: &str) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    let mut plain = 0;
+    for (i, c) in s.char_indices() {
+        let escaped = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            c if (c as u32) < 0x20 => "",
+            _ => continue,
+        };
+        out.write_all(&s.as_bytes()[plain..i])?;
+        match escaped {
+            "" => write!(out, "\\u{:04x}", c as u32)?,
+            escaped => out.write_all(escaped.as_bytes())?,
+        }
+        plain = i + c.len_utf8();
+    }
+    out.write_all(&s.as_bytes()[plain..])?;
+    out.write_all(b"\"")
+}
+
+// 写出一段数据：合法的 UTF-8 写成 {"text":...}，否则写成 {"bytes":<base64>}
+fn write_data(out: &mut dyn Write, bytes: &[u8]) -> io::Result<()> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            out.write_all(b"{\"text\":")?;
+            write_str(out, text)?;
+        }
+        Err(_) => {
+            out.write_all(b"{\"bytes\":")?;
+            write_str(out, &base64(bytes))?;
+        }
+    }
+    out.write_all(b"}")
+}
+
+// 标准 base64 编码（RFC 4648，带 '=' 填充）
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn write_elapsed(out: &mut dyn Write, elapsed: Duration) -> io::Result<()> {
+    write!(
+        out,
+        "{{\"secs\":{},\"nanos\":{},\"human\":\"{:.6}s\"}}",
+        elapsed.as_secs(),
+        elapsed.subsec_nanos(),
+        elapsed.as_secs_f64()
+    )
+}
+
+fn write_stats(out: &mut dyn Write, stats: &Stats, elapsed: Duration) -> io::Result<()> {
+    out.write_all(b"{\"elapsed\":")?;
+    write_elapsed(out, elapsed)?;
+    write!(
+        out,
+        ",\"searches\":{},\"searches_with_match\":{},\"bytes_searched\":{},\"matched_lines\":{},\"matches\":{}}}",
+        stats.searches, stats.searches_with_match, stats.bytes_searched, stats.matched_lines, stats.matches
+    )
+}
+
+/// 开始搜索一个文件
+pub(crate) fn begin(out: &mut dyn Write, path: &str) -> io::Result<()> {
+    out.write_all(b"{\"type\":\"begin\",\"data\":{\"path\":")?;
+    write_data(out, path.as_bytes())?;
+    out.write_all(b"}}\n")
+}
+
+/// 匹配行（`kind` 为 "match"）或上下文行（"context"）。`spans` 中的区间相对于行首，
+/// 输出的 start、end 和 absolute_offset 一样是原始内容（编码为 `encoding`）中的字节数；
+/// `distances` 为 --fuzzy 时各个匹配的编辑距离，其他情况下为空。
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn line(
+    out: &mut dyn Write,
+    kind: &str,
+    path: &str,
+    line: &[u8],
+    line_number: usize,
+    absolute_offset: usize,
+    spans: &[Span],
+    encoding: Encoding,
+    distances: &[usize],
+) -> io::Result<()> {
+    write!(out, "{{\"type\":\"{}\",\"data\":{{\"path\":", kind)?;
+    write_data(out, path.as_bytes())?;
+    out.write_all(b",\"lines\":")?;
+    write_data(out, line)?;
+    write!(out, ",\"line_number\":{},\"absolute_offset\":{},\"submatches\":[", line_number, absolute_offset)?;
+    for (i, span) in spans.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        out.write_all(b"{\"match\":")?;
+        write_data(out, &line[span.start..span.end])?;
+        let start = encoding.original_len(&line[..span.start]);
+        let end = start + encoding.original_len(&line[span.start..span.end]);
+        write!(out, ",\"start\":{},\"end\":{}", start, end)?;
+        if let Some(distance) = distances.get(i) {
+            write!(out, ",\"distance\":{}", distance)?;
+        }
+        out.write_all(b"}")?;
+    }
+    out.write_all(b"]}}\n")
+}
+
+/// 一个文件搜索完毕。`binary_match` 表示这是在二进制文件中找到了匹配，因此没有输出匹配行。
+pub(crate) fn end(out: &mut dyn Write, path: &str, binary_match: bool, stats: &Stats, elapsed: Duration) -> io::Result<()> {
+    out.write_all(b"{\"type\":\"end\",\"data\":{\"path\":")?;
+    write_data(out, path.as_bytes())?;
+    write!(out, ",\"binary_match\":{},\"stats\":", binary_match)?;
+    write_stats(out, stats, elapsed)?;
+    out.write_all(b"}}\n")
+}
+
+/// 所有文件搜索完毕后的汇总
+pub(crate) fn summary(out: &mut dyn Write, stats: &Stats, elapsed: Duration) -> io::Result<()> {
+    out.write_all(b"{\"type\":\"summary\",\"data\":{\"elapsed_total\":")?;
+    write_elapsed(out, elapsed)?;
+    out.write_all(b",\"stats\":")?;
+    write_stats(out, stats, elapsed)?;
+    out.write_all(b"}}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_escaped_text_and_base64_bytes() {
+        let mut out = Vec::new();
+        let spans = [Span { start: 4, end: 6 }];
+        line(&mut out, "match", "a\"b.rs", b"\tx \"fn\"\x01", 3, 42, &spans, Encoding::Utf8, &[]).unwrap();
+        assert_eq!(
+            r#"{"type":"match","data":{"path":{"text":"a\"b.rs"},"lines":{"text":"\tx \"fn\"\u0001"},"line_number":3,"absolute_offset":42,"submatches":[{"match":{"text":"fn"},"start":4,"end":6}]}}"#,
+            String::from_utf8(out).unwrap().trim_end()
+        );
+
+        let mut out = Vec::new();
+        write_data(&mut out, b"\xffab").unwrap();
+        assert_eq!(r#"{"bytes":"/2Fi"}"#, String::from_utf8(out).unwrap());
+        assert_eq!(("Zg==", "Zm8=", "Zm9v"), (base64(b"f").as_str(), base64(b"fo").as_str(), base64(b"foo").as_str()));
+    }
+}
@@ -8,9 +8,71 @@
 //! `minigrep` 获取一个文件名和一个字符串作为参数，接着读取文件并找到其中包含字符串参数的行，然后打印出这些行。
 
 
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::fs;
 use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
+
+use regex::{Captures, Regex, RegexOptions};
+use aho_corasick::AhoCorasick;
+use encoding::Encoding;
+use fields::{Field, FieldMatcher};
+use fuzzy::Fuzzy;
+use args::Arg;
+use color::{ColorChoice, Colors};
+use json::Stats;
+use pool::ThreadPool;
+use replace::{Change, Template};
+use report::Tally;
+use search::Event;
+use walk::{Glob, WalkOptions};
+
+pub use args::{ArgsError, USAGE};
+pub use search::{Context, Match, Matcher, SearchOptions, Searcher, Sink, Span, Summary};
+
+// 命令行参数解析
+mod args;
+// 配置文件 .minigreprc
+mod rc;
+// 内置的正则表达式引擎：cargo doc --open => Modules => regex
+pub mod regex;
+// 递归遍历目录、glob 过滤与 .gitignore 支持
+pub mod walk;
+// 逐行搜索并生成带位置信息的匹配记录
+pub mod search;
+// 并行搜索多个文件用的线程池
+pub mod pool;
+// 匹配高亮：--color 与 GREP_COLORS
+pub mod color;
+// 按 Unicode 简单大小写折叠进行大小写不敏感查找
+pub mod casefold;
+// 同时查找多个固定字符串
+pub mod aho_corasick;
+// --fuzzy 的近似匹配（Myers 位并行算法）
+pub mod fuzzy;
+// --json 输出
+mod json;
+// -z：内置的 gzip 解压
+pub mod gzip;
+// --encoding：识别 BOM，把 UTF-16 和 Latin-1 转换成 UTF-8 再搜索
+pub mod encoding;
+// --replace 的替换模板与 --in-place 改写文件
+mod replace;
+// minigrep index 与 --index-dir：三元组索引
+mod index;
+// --follow：持续跟踪文件
+mod follow;
+// --csv-field 与 --json-path：只在记录的一个字段中匹配
+mod fields;
+// --stats 与 --histogram：搜索结束后输出的统计
+mod report;
+mod utf8;
 
 // ======== 以下代码段与项目无关，仅用于示范文档注释生成 ========
 // 注释文档生成：使用 pub use 导出合适的公有 API
@@ -44,22 +106,26 @@ pub mod utils {
     // 下面是markdown格式文档注释，所以在web显示时会续成一行...
     /// Combines two primary colors in equal amounts to create
     /// a secondary color.
-    pub fn mix(c1: PrimaryColor, c2: PrimaryColor) -> SecondaryColor {
+    pub fn mix(_c1: PrimaryColor, _c2: PrimaryColor) -> SecondaryColor {
         SecondaryColor::Orange
     }
 }
 // ======== 以上代码段与项目无关，仅用于示范文档注释生成 ========
 
 pub struct Config {
-    // 要搜索的字符串
-    pub query: String,
-    // 要搜索的文件名
-    pub filename: String,
+    // 要搜索的模式：命令行中的 QUERY，或者由 -e / -f 给出的一个或多个模式，任意一个匹配即可
+    pub patterns: Vec<String>,
+    // 要搜索的文件或目录，目录会被递归遍历；"-" 表示标准输入，没有给出路径时默认搜索标准输入
+    pub paths: Vec<PathBuf>,
+    // --include / --exclude：只搜索 / 跳过文件名匹配这些 glob 模式的文件
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
 
     /*
     增加一个额外的功能来改进 minigrep： 用户可以通过设置`环境变量`来设置搜索是否是大小写敏感的 。
     当然，我们也可以将其设计为一个命令行参数并要求用户每次需要时都加上它，不过在这里我们将使用环境变量。
     这允许用户设置环境变量一次之后在整个终端会话中所有的搜索都将是大小写不敏感的。
+    现在两者都支持了：环境变量作为默认值，命令行中的 -i 总是会打开大小写不敏感搜索。
 
     设置环境变量方法：
     PowerShell  => $env:CASE_INSENSITIVE=1  查看 $env:CASE_INSENSITIVE
@@ -67,6 +133,72 @@ pub struct Config {
     linux       => CASE_INSENSITIVE=1       查看 echo $CASE_INSENSITIVE
     */
     pub case_sensitive: bool, // 大小写敏感
+
+    // -E / --regex：把模式当作正则表达式而不是普通字符串
+    pub regex: bool,
+    // -v：选择不匹配的行
+    pub invert_match: bool,
+    // -n：输出行号
+    pub line_number: bool,
+    // -b：输出每行在文件中的字节偏移
+    pub byte_offset: bool,
+    // --column：输出第一个匹配所在的列（同时会输出行号）
+    pub column: bool,
+    // -A N / -B N / -C N：输出匹配行之后 / 之前 / 前后各 N 行的上下文
+    pub after_context: usize,
+    pub before_context: usize,
+    // -c：只输出每个文件匹配的行数
+    pub count: bool,
+    // -l：只输出包含匹配的文件名
+    pub files_with_matches: bool,
+    // -L：只输出不包含匹配的文件名
+    pub files_without_match: bool,
+    // -m N：每个文件选中 N 行后就停止搜索
+    pub max_count: Option<usize>,
+    // -w：只匹配完整的单词
+    pub word_regexp: bool,
+    // -x：只匹配整行
+    pub line_regexp: bool,
+    // -o：只输出匹配到的文本，每个匹配一行
+    pub only_matching: bool,
+    // --capture GROUP：只输出每个匹配中的一个捕获组（序号或名字），同时打开 -o
+    pub capture: Option<String>,
+    // -U：模式在整个文件上匹配，可以跨越换行符，输出匹配经过的所有行
+    pub multiline: bool,
+    // --fuzzy K：查找与模式的编辑距离不超过 K 的子串，输出时附带每行匹配的编辑距离
+    pub fuzzy: Option<usize>,
+    // -a：把二进制文件当作文本处理，照常输出匹配行
+    pub text: bool,
+    // -z：以 gzip 魔数开头的文件先解压再搜索
+    pub search_zip: bool,
+    // --encoding：文件的编码，None（auto）时按 BOM 识别，UTF-16 和 Latin-1 先转换成 UTF-8 再搜索
+    pub encoding: Option<Encoding>,
+    // --csv-field FIELD / --json-path PATH：只在每条 CSV 记录的一列或者每行 JSON 中的一个值里匹配，输出整条记录
+    pub csv_field: Option<String>,
+    pub json_path: Option<String>,
+    // --color：是否用颜色高亮匹配、文件名和行号
+    pub color: ColorChoice,
+    // --json：每个事件输出一行 JSON，供编辑器和 CI 工具解析
+    pub json: bool,
+    // --stats：最后输出搜索的文件数、字节数、选中的行数、用时和吞吐量
+    pub stats: bool,
+    // --histogram：最后输出每个文件以及每个小时（按行首的时间戳）选中的行数
+    pub histogram: bool,
+    // --replace TEMPLATE：输出替换后的匹配行，正则模式下模板可以用 $1、${name} 引用捕获组
+    pub replace: Option<String>,
+    // --in-place：把替换结果原子地写回文件；--dry-run：只以 diff 的形式预览改动，不写文件
+    pub in_place: bool,
+    pub dry_run: bool,
+
+    // --follow：像 tail -F 一样持续跟踪文件，输出之后新追加的匹配行，直到按下 Ctrl-C
+    pub follow: bool,
+    // minigrep index PATH...：不搜索，而是为 PATH 建立三元组索引
+    pub build_index: bool,
+    // --index-dir DIR：索引所在的目录。搜索时给出它就先用索引排除不可能匹配的文件
+    pub index_dir: Option<PathBuf>,
+
+    // -j N：同时搜索文件的线程数，默认为 CPU 核数
+    pub threads: usize,
 }
 
 impl Config {
@@ -92,38 +224,415 @@ impl Config {
     ///    process::exit(1);
     /// });
     /// */
+    ///
+    /// let args = ["minigrep", "-in", "--", "-rust-", "poem.txt"].iter().map(|s| s.to_string());
+    /// let cfg = chry_minigrep::Config::new(args).unwrap();
+    /// assert_eq!(vec!["-rust-"], cfg.patterns);
+    /// assert!(!cfg.case_sensitive && cfg.line_number);
     /// ```
-
-    // 因为我们拥有 args 的所有权，并且将通过对其进行迭代来改变 args ，所以我们可以将 mut 关键字添加到 args 参数的规范中以使其可变。
+    ///
+    /// # Errors
+    ///
+    /// 参数不合法时返回 [`ArgsError`]。`--help` 和 `--version` 也以 `ArgsError::Help`、`ArgsError::Version`
+    /// 的形式返回，由调用者决定如何打印。
+    //
+    // 参数类型从 std::env::Args 放宽为任意产生 String 的迭代器，这样测试里也可以直接传入 vec 构造的参数。
     // 返回 Result 而不应该 返回Config或调用 panic!
-    pub fn new(mut args: std::env::Args) -> Result<Config, &'static str> {
-        // &'static str => 显式静态声明 => 等价于隐式静态声明 &str
-        args.next();    // env::args 返回值的第一个值是程序的名称, 忽略并获取下一个值
-
-        // 要搜索的字符串
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
+    pub fn new(args: impl Iterator<Item = String>) -> Result<Config, ArgsError> {
+        // env::args 返回值的第一个值是程序的名称, 忽略并获取下一个值
+        let args: Vec<String> = args.skip(1).collect();
+        // 默认读取主目录和当前项目中的 .minigreprc，--no-config 时不读取任何配置文件
+        let (no_config, _) = scan(&args);
+        let config_files = match no_config {
+            true => Vec::new(),
+            false => rc::discover(env::current_dir().ok().as_deref(), rc::home_dir().as_deref()),
         };
+        Config::parse(args, &config_files)
+    }
 
-        // 要搜索的文件名
-        let filename = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a filename string"),
-        };
+    // 依次解析配置文件和命令行参数。配置文件中的每个设置都被转换成等价的长选项，
+    // 和命令行参数走同一套解析逻辑；它们先被处理，所以命令行中的同一选项会覆盖配置文件中的设置
+    fn parse(args: Vec<String>, config_files: &[PathBuf]) -> Result<Config, ArgsError> {
+        let (_, build_index) = scan(&args);
+        let mut sources = Vec::new();
+        for file in config_files {
+            sources.extend(rc::load(file)?);
+        }
+        sources.push((None, args));
 
-        // println!("case_sensitive: {:?}", env::var("CASE_INSENSITIVE"));
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err(); // 注意这里仅判断环境变量存在与否，不关心环境变量的内容！
-        // println!("case_sensitive: {}", case_sensitive);
         /*
         env::var 返回一个 Result，它在环境变量被设置时返回包含其值的 Ok 成员，并在环境变量未被设置时返回 Err 成员。
-        使用 Result 的 is_err 方法来检查其是否是一个 error（也就是环境变量未被设置的情况），这也就意味着我们 需要 进行一个大小写敏感搜索。
-        如果CASE_INSENSITIVE 环境变量被设置为任何值，is_err 会返回 false 并将进行大小写不敏感搜索。
-        我们并不关心环境变量所设置的 值，只关心它是否被设置了，所以检查 is_err 而不是 unwrap、expect 或任何我们已经见过的 Result 的方法。
+        最初这里只用 is_err 判断环境变量存在与否，于是 CASE_INSENSITIVE=0 也会打开大小写不敏感搜索。
+        现在会检查环境变量的值：未设置、空字符串以及 0/false/no/off 都表示大小写敏感。
         */
+        let mut case_sensitive = match env::var("CASE_INSENSITIVE") {
+            Ok(value) => matches!(value.to_lowercase().as_str(), "" | "0" | "false" | "no" | "off"),
+            Err(_) => true,
+        };
+        let mut regex = false;
+        let mut invert_match = false;
+        let mut line_number = false;
+        let mut byte_offset = false;
+        let mut column = false;
+        // -A、-B 优先于 -C，与它们出现的先后顺序无关
+        let mut after_context = None;
+        let mut before_context = None;
+        let mut context = 0;
+        // 上下文和编码是否由命令行给出，而不只是来自配置文件
+        let mut context_given = false;
+        let mut encoding_given = false;
+        let mut count = false;
+        let mut files_with_matches = false;
+        let mut files_without_match = false;
+        let mut max_count = None;
+        let mut word_regexp = false;
+        let mut line_regexp = false;
+        let mut only_matching = false;
+        let mut capture = None;
+        let mut multiline = false;
+        let mut fuzzy = None;
+        let mut text = false;
+        let mut search_zip = false;
+        let mut encoding = None;
+        let mut csv_field = None;
+        let mut json_path = None;
+        let mut color = ColorChoice::Auto;
+        let mut json = false;
+        let mut stats = false;
+        let mut histogram = false;
+        let mut replace = None;
+        let mut in_place = false;
+        let mut dry_run = false;
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        let mut index_dir = None;
+        let mut follow = false;
+        let mut threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        // -e / -f 给出的模式；给出了它们时第一个普通参数就不再是模式而是路径
+        let mut patterns = Vec::new();
+        let mut has_pattern_option = false;
+        let mut positional = Vec::new();
+
+        // 短选项和长选项统一成同一个名字再匹配，例如 -i 和 --ignore-case 都对应 "i" | "ignore-case"
+        for (origin, args) in sources {
+            let from_config = origin.is_some();
+            let mut parser = args::Parser::new(args.into_iter());
+            let mut parse = || -> Result<(), ArgsError> {
+                while let Some(arg) = parser.next()? {
+                    let name = match arg {
+                        Arg::Value(value) => {
+                            positional.push(value);
+                            continue;
+                        }
+                        Arg::Short(c) => c.to_string(),
+                        Arg::Long(name) if name.chars().count() > 1 => name,
+                        Arg::Long(_) => return Err(ArgsError::UnknownOption(parser.option().to_string())),
+                    };
+                    match name.as_str() {
+                        "E" | "regex" => regex = true,
+                        "e" | "regexp" => {
+                            patterns.push(parser.value()?);
+                            has_pattern_option = true;
+                        }
+                        "f" | "file" => {
+                            let file = parser.value()?;
+                            patterns.extend(read_patterns(&file).map_err(|e| ArgsError::PatternFile(file, e))?);
+                            has_pattern_option = true;
+                        }
+                        "i" | "ignore-case" => case_sensitive = false,
+                        // 和 ripgrep 一样用 -s，grep 的 -s 是 --no-messages（USAGE 中有说明）
+                        "s" | "case-sensitive" => case_sensitive = true,
+                        "v" | "invert-match" => invert_match = true,
+                        // 每个开关都有对应的 --no-*，用来关掉配置文件中打开的设置
+                        "n" | "line-number" => line_number = true,
+                        "no-line-number" => line_number = false,
+                        "b" | "byte-offset" => byte_offset = true,
+                        "no-byte-offset" => byte_offset = false,
+                        "column" => column = true,
+                        "no-column" => column = false,
+                        "A" | "after-context" => {
+                            after_context = Some(parser.parse_value()?);
+                            context_given |= !from_config;
+                        }
+                        "B" | "before-context" => {
+                            before_context = Some(parser.parse_value()?);
+                            context_given |= !from_config;
+                        }
+                        "C" | "context" => {
+                            context = parser.parse_value()?;
+                            context_given |= !from_config;
+                        }
+                        "c" | "count" => count = true,
+                        // -l 和 -L 互斥，后出现的生效
+                        "l" | "files-with-matches" => {
+                            files_with_matches = true;
+                            files_without_match = false;
+                        }
+                        "L" | "files-without-match" => {
+                            files_without_match = true;
+                            files_with_matches = false;
+                        }
+                        "m" | "max-count" => max_count = Some(parser.parse_value()?),
+                        "no-max-count" => max_count = None,
+                        "w" | "word-regexp" => word_regexp = true,
+                        "no-word-regexp" => word_regexp = false,
+                        "x" | "line-regexp" => line_regexp = true,
+                        "no-line-regexp" => line_regexp = false,
+                        "o" | "only-matching" => only_matching = true,
+                        "capture" => capture = Some(parser.value()?),
+                        "U" | "multiline" => multiline = true,
+                        "fuzzy" => fuzzy = Some(parser.parse_value()?),
+                        "a" | "text" => text = true,
+                        "no-text" => text = false,
+                        "z" | "search-zip" => search_zip = true,
+                        "no-search-zip" => search_zip = false,
+                        "encoding" => {
+                            let value = parser.value()?;
+                            encoding = match value.as_str() {
+                                "auto" => None,
+                                name => Some(name.parse().map_err(|_| ArgsError::InvalidValue(parser.option().to_string(), value.clone()))?),
+                            };
+                            encoding_given |= !from_config;
+                        }
+                        // 在这里就检查字段的写法，run 时再解析一次
+                        "csv-field" => {
+                            let value = parser.value()?;
+                            if Field::csv(&value).is_none() {
+                                return Err(ArgsError::InvalidValue(parser.option().to_string(), value));
+                            }
+                            csv_field = Some(value);
+                        }
+                        "json-path" => {
+                            let value = parser.value()?;
+                            if Field::json(&value).is_none() {
+                                return Err(ArgsError::InvalidValue(parser.option().to_string(), value));
+                            }
+                            json_path = Some(value);
+                        }
+                        // 和 grep 一样，--color 的值只能用 --color=WHEN 的形式给出，单独的 --color 等同于 auto
+                        "color" | "colour" => {
+                            color = match parser.optional_value() {
+                                Some(value) => value
+                                    .parse()
+                                    .map_err(|_| ArgsError::InvalidValue(parser.option().to_string(), value))?,
+                                None => ColorChoice::Auto,
+                            }
+                        }
+                        "json" => json = true,
+                        "stats" => stats = true,
+                        "histogram" => histogram = true,
+                        "replace" => replace = Some(parser.value()?),
+                        "in-place" => in_place = true,
+                        "dry-run" => dry_run = true,
+                        "include" => include.push(parser.value()?),
+                        "exclude" => exclude.push(parser.value()?),
+                        "index-dir" => index_dir = Some(PathBuf::from(parser.value()?)),
+                        "follow" => follow = true,
+                        "j" | "threads" => {
+                            threads = parser.parse_value()?;
+                            if threads == 0 {
+                                return Err(ArgsError::InvalidValue(parser.option().to_string(), "0".to_string()));
+                            }
+                        }
+                        // 已经在读取配置文件之前由 scan 处理过了
+                        "no-config" => {}
+                        "h" | "help" => return Err(ArgsError::Help),
+                        "V" | "version" => return Err(ArgsError::Version),
+                        _ => return Err(ArgsError::UnknownOption(parser.option().to_string())),
+                    }
+                }
+                Ok(())
+            };
+            // 配置文件中的错误带上它所在的文件和行号
+            parse().map_err(|e| match origin {
+                Some(at) => ArgsError::ConfigFile(at, e.to_string()),
+                None => e,
+            })?;
+        }
+        let mut positional = positional.into_iter();
+        // 子命令本身
+        if build_index {
+            positional.next();
+        }
 
-        Ok(Config { query, filename, case_sensitive })
+        // 配置文件中的上下文和编码是普通搜索的默认值，--follow 时不使用它们，而不是报告冲突
+        if follow && !context_given {
+            (after_context, before_context, context) = (None, None, 0);
+        }
+        if follow && !encoding_given {
+            encoding = None;
+        }
+
+        // --json 和改写文件都要逐个处理匹配行，既不能同时使用，也不能和只输出计数或文件名的选项一起使用
+        let rewrite = match (in_place, dry_run) {
+            (_, true) => Some("--dry-run"),
+            (true, false) => Some("--in-place"),
+            _ => None,
+        };
+        if let (Some(option), None) = (rewrite, &replace) {
+            return Err(ArgsError::Requires(option.to_string(), "--replace".to_string()));
+        }
+        // --follow 只输出新的匹配行：不支持上下文、计数等需要看到整个文件的输出方式
+        let modes = [
+            (follow, "--follow"),
+            (follow && (before_context.unwrap_or(context) > 0 || after_context.unwrap_or(context) > 0), "--context"),
+            (follow && index_dir.is_some(), "--index-dir"),
+            (follow && multiline, "--multiline"),
+            (follow && encoding.is_some(), "--encoding"),
+            (json, "--json"),
+            (rewrite.is_some(), rewrite.unwrap_or_default()),
+            (json && replace.is_some(), "--replace"),
+            (stats, "--stats"),
+            (histogram, "--histogram"),
+            (count, "--count"),
+            (files_with_matches, "--files-with-matches"),
+            (files_without_match, "--files-without-match"),
+        ];
+        let mut modes = modes.iter().filter(|(set, _)| *set).map(|(_, option)| *option);
+        if let (Some(first @ ("--follow" | "--json" | "--in-place" | "--dry-run")), Some(second)) = (modes.next(), modes.next()) {
+            return Err(ArgsError::Conflict(first.to_string(), second.to_string()));
+        }
+
+        // 字段是按记录切分出来的：两种字段不能一起用，也不能和替换、-U 以及逐行跟踪的 --follow 一起使用
+        let field_option = match (&csv_field, &json_path) {
+            (Some(_), _) => Some("--csv-field"),
+            (None, Some(_)) => Some("--json-path"),
+            (None, None) => None,
+        };
+        if let Some(option) = field_option {
+            let conflicts = [
+                (csv_field.is_some() && json_path.is_some(), "--json-path"),
+                (replace.is_some(), "--replace"),
+                (capture.is_some(), "--capture"),
+                (multiline, "--multiline"),
+                (follow, "--follow"),
+            ];
+            if let Some((_, other)) = conflicts.iter().find(|(set, _)| *set) {
+                return Err(ArgsError::Conflict(option.to_string(), other.to_string()));
+            }
+        }
+
+        // 近似匹配的是普通字符串，不能和正则表达式一起使用
+        if fuzzy.is_some() && regex {
+            return Err(ArgsError::Conflict("--fuzzy".to_string(), "--regex".to_string()));
+        }
+        // 只有正则表达式有捕获组；要输出的是组的内容还是替换的结果只能选一个
+        if capture.is_some() {
+            if !regex {
+                return Err(ArgsError::Requires("--capture".to_string(), "--regex".to_string()));
+            }
+            if replace.is_some() {
+                return Err(ArgsError::Conflict("--capture".to_string(), "--replace".to_string()));
+            }
+            only_matching = true;
+        }
+
+        // 要搜索的字符串；建立索引时所有的普通参数都是路径
+        if !has_pattern_option && !build_index {
+            patterns.push(positional.next().ok_or(ArgsError::MissingQuery)?);
+        }
+        // K 不小于模式的长度时空串也在 K 之内，每个非空的行都会以一个空的匹配被选中（空行反而不会），所以不允许
+        if let Some(distance) = fuzzy.filter(|&distance| patterns.iter().any(|p| p.chars().count() <= distance)) {
+            return Err(ArgsError::InvalidValue("--fuzzy".to_string(), distance.to_string()));
+        }
+
+        // 要搜索的文件名，可以有多个，也可以是目录；一个都没有时和 grep 一样从标准输入读取
+        let mut paths: Vec<PathBuf> = positional.map(PathBuf::from).collect();
+        if follow && (paths.is_empty() || paths.iter().any(|p| p.as_os_str() == "-")) {
+            // 标准输入读到末尾就结束了，没有可以跟踪的文件
+            return Err(ArgsError::Requires("--follow".to_string(), "PATH".to_string()));
+        }
+        if paths.is_empty() {
+            paths.push(PathBuf::from(if build_index { "." } else { "-" }));
+        }
+
+        // 在这里就编译一次正则表达式，让非法的模式尽早以清晰的错误信息报告出来，而不是等到 run 时才失败
+        if regex {
+            for pattern in &patterns {
+                Regex::new(pattern)?;
+            }
+        }
+
+        Ok(Config {
+            patterns,
+            paths,
+            include,
+            exclude,
+            case_sensitive,
+            regex,
+            invert_match,
+            line_number,
+            byte_offset,
+            column,
+            after_context: after_context.unwrap_or(context),
+            before_context: before_context.unwrap_or(context),
+            count,
+            files_with_matches,
+            files_without_match,
+            max_count,
+            word_regexp,
+            line_regexp,
+            only_matching,
+            capture,
+            multiline,
+            fuzzy,
+            text,
+            search_zip,
+            encoding,
+            csv_field,
+            json_path,
+            color,
+            json,
+            stats,
+            histogram,
+            replace,
+            in_place,
+            dry_run,
+            follow,
+            build_index,
+            index_dir,
+            threads,
+        })
+    }
+}
+
+// 需要一个值的选项，和 Config::parse 中取值的分支一一对应（--color 的值只能用 = 给出，不在其中）
+const VALUE_OPTIONS: [&str; 23] = [
+    "e", "regexp", "f", "file", "A", "after-context", "B", "before-context", "C", "context", "m", "max-count", "capture",
+    "fuzzy", "encoding", "csv-field", "json-path", "replace", "include", "exclude", "index-dir", "j", "threads",
+];
+// 可以写在子命令 index 之前的选项
+const INDEX_OPTIONS: [&str; 4] = ["no-config", "index-dir", "include", "exclude"];
+
+// 读取配置文件之前先用 Parser 扫描一遍命令行，返回是否给出了 --no-config，以及第一个普通参数是不是子命令 index。
+// 子命令之前只能有 INDEX_OPTIONS 中的选项，所以 minigrep -n index src 仍然是搜索 "index"，-e index 和 -- index 也一样。
+// 命令行中的错误留给 Config::parse 报告，扫描到出错的地方就停下
+fn scan(args: &[String]) -> (bool, bool) {
+    let mut parser = args::Parser::new(args.iter().cloned());
+    let (mut no_config, mut build_index) = (false, false);
+    // 到目前为止只出现过 INDEX_OPTIONS 中的选项，第一个普通参数还可能是子命令
+    let mut subcommand = true;
+    while let Ok(Some(arg)) = parser.next() {
+        let name = match arg {
+            Arg::Value(value) => {
+                build_index |= subcommand && value == "index" && !parser.after_separator();
+                subcommand = false;
+                continue;
+            }
+            Arg::Short(c) => c.to_string(),
+            Arg::Long(name) => name,
+        };
+        no_config |= name == "no-config";
+        subcommand &= INDEX_OPTIONS.contains(&name.as_str());
+        if VALUE_OPTIONS.contains(&name.as_str()) {
+            if parser.value().is_err() {
+                break;
+            }
+        } else {
+            parser.optional_value();
+        }
     }
+    (no_config, build_index)
 }
 
 /*
@@ -139,10 +648,17 @@ impl Config {
 // ========================================================================
 // 编写失败的测试
 // ========================================================================
+// 按照 TDD 的顺序测试写在实现之前，所以允许测试模块后面还有其他项
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
 
+    // 测试中的命令行参数
+    pub(crate) fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
     #[test]
     fn case_sensitive() {
         let query = "hello";
@@ -152,7 +668,11 @@ mod tests {
 03 Hello world
 04 123465789";
 
-        assert_eq!(vec!["    02 hello world"], search(query, contents));
+        let results = search(query, contents);
+        assert_eq!(vec![&b"    02 hello world"[..]], results.iter().map(|m| m.line).collect::<Vec<_>>());
+        // 结构化的匹配记录：行号、行首的字节偏移以及匹配在行内的区间
+        assert_eq!((2, 11), (results[0].line_number, results[0].byte_offset));
+        assert_eq!(vec![Span { start: 7, end: 12 }], results[0].spans);
     }
 
 
@@ -166,7 +686,455 @@ mod tests {
 03 Hello world
 04 123465789";
 
-        assert_eq!(vec!["    02 hello world", "03 Hello world"], search_case_insensitive(query, contents));
+        let results = search_case_insensitive(query, contents);
+        assert_eq!(vec![&b"    02 hello world"[..], b"03 Hello world"], results.iter().map(|m| m.line).collect::<Vec<_>>());
+        assert_eq!(Some(4), results[1].column());
+
+        // 大小写折叠而不是简单的转小写：长 s 'ſ' 和 's'、词尾的 'ς' 和 'Σ' 都视为相同，区间对应原文
+        let results = search_case_insensitive("ΣΟΦΟΣ SS", "σοφος ſs\nσοφοσ");
+        assert_eq!(vec![Span { start: 0, end: 14 }], results[0].spans);
+    }
+
+    #[test]
+    fn regex_search() {
+        let re = Regex::new(r"^\d+ (hello|abc)").unwrap();
+        let contents = "\
+01 abcdefg
+    02 hello world
+03 hello world
+04 123465789";
+
+        let results = search_regex(&re, contents);
+        assert_eq!(vec![1, 3], results.iter().map(|m| m.line_number).collect::<Vec<_>>());
+        assert_eq!(vec![Span { start: 0, end: 6 }], results[0].spans);
+    }
+
+    #[test]
+    fn word_and_line_regexp() {
+        let find = |list: &[&str], line: &str| {
+            let patterns = Patterns::new(&Config::new(args(list)).unwrap()).unwrap();
+            search::find_all(&patterns, line.as_bytes())
+        };
+        let spans = |list: &[(usize, usize)]| list.iter().map(|&(start, end)| Span { start, end }).collect::<Vec<_>>();
+
+        // 大小写敏感与不敏感的固定字符串：跳过标识符中间的 "id"，"ÿid" 中的 ÿ 也是字母
+        let line = "id ids _id ÿid (id)";
+        assert_eq!(spans(&[(0, 2), (17, 19)]), find(&["minigrep", "-s", "-w", "id"], line));
+        assert_eq!(spans(&[(0, 2), (17, 19)]), find(&["minigrep", "-i", "-w", "ID"], line));
+        // 多个固定字符串：从同一个起点开始的 "ids" 和 "id" 都会被检查
+        assert_eq!(spans(&[(0, 2), (3, 6), (17, 19)]), find(&["minigrep", "-s", "-w", "-e", "id", "-e", "ids"], line));
+        assert_eq!(spans(&[(0, 2), (17, 19)]), find(&["minigrep", "-i", "-w", "-e", "IDSX", "-e", "Id"], line));
+        assert_eq!(spans(&[(0, 2), (17, 19)]), find(&["minigrep", "-s", "-wE", "i."], line));
+
+        assert_eq!(spans(&[(0, 8)]), find(&["minigrep", "-i", "-x", "straße"], "STRAẞE"));
+        assert!(find(&["minigrep", "-s", "-x", "fn"], "fn main").is_empty());
+        assert!(find(&["minigrep", "-s", "-x", "-e", "fn", "-e", "main"], "fn main").is_empty());
+        assert_eq!(spans(&[(0, 7)]), find(&["minigrep", "-s", "-x", "-e", "fn", "-e", "fn main"], "fn main"));
+        assert_eq!(spans(&[(0, 7)]), find(&["minigrep", "-s", "-xE", "fn|fn main"], "fn main"));
+    }
+
+    #[test]
+    fn config_combined_flags() {
+        let cfg = Config::new(args(&["minigrep", "-vnc", "--files-with-matches", "-waz", "fn", "src"])).unwrap();
+        assert!(cfg.invert_match && cfg.line_number && cfg.count && cfg.files_with_matches && cfg.word_regexp && cfg.text);
+        assert!(cfg.search_zip && !cfg.line_regexp);
+        assert!(Config::new(args(&["minigrep", "--line-regexp", "fn"])).unwrap().line_regexp);
+    }
+
+    #[test]
+    fn empty_matches_like_grep() {
+        let spans = |pattern: &str, line: &str| {
+            let patterns = Patterns::new(&Config::new(args(&["minigrep", "-s", "-E", pattern])).unwrap()).unwrap();
+            search::find_all(&patterns, line.as_bytes()).iter().map(|s| (s.start, s.end)).collect::<Vec<_>>()
+        };
+        // 空匹配之后从下一个字符继续，紧跟在 "a" 后面的空匹配不算；同一位置上有非空的匹配时用非空的那个
+        assert_eq!(vec![(0, 0), (1, 2), (3, 3)], spans("a*", "xax"));
+        assert_eq!(vec![(0, 0), (1, 2), (3, 3)], spans("|a", "xax"));
+        assert_eq!(vec![(0, 0), (2, 2), (3, 3)], spans("", "éx"));
+
+        // 和 grep -o 的输出相同
+        let (_dir, paths) = temp_files("empty-matches", &[("a.txt", b"x_cbB xax_\n")]);
+        for (list, expected) in [(&["-E", "a*"][..], "a\n"), (&["-E", "|a"], "a\n"), (&["-E", "b?"], "b\n"), (&["-wE", "b?"], "")] {
+            assert_eq!(expected, search_files(&[&["minigrep", "-s", "-o"], list].concat(), &paths).0, "{:?}", list);
+        }
+    }
+
+    #[test]
+    fn multiline_patterns() {
+        let find = |list: &[&str], contents: &str| {
+            let cfg = Config::new(args(list)).unwrap();
+            let options = SearchOptions { multiline: cfg.multiline, ..Default::default() };
+            let results = search::search_lines(contents.as_bytes(), &Patterns::new(&cfg).unwrap(), &options);
+            results.iter().map(|m| (m.line_number, m.spans.clone())).collect::<Vec<_>>()
+        };
+        let spans = |list: &[(usize, usize)]| list.iter().map(|&(start, end)| Span { start, end }).collect::<Vec<_>>();
+        let contents = "fn main() {\n}\nfn  main\n";
+        assert_eq!(vec![(1, spans(&[(7, 13)]))], find(&["minigrep", "-sUE", r"\(\)\s*\{\n\}"], contents));
+        assert_eq!(vec![(2, spans(&[(0, 4)]))], find(&["minigrep", "-sUE", r"^\}\nfn"], contents));
+        // -x 在多行内容中按每一行的行首和行尾判断
+        assert_eq!(vec![(2, spans(&[(0, 1)]))], find(&["minigrep", "-sUx", "}"], contents));
+        assert_eq!(vec![(3, spans(&[(0, 8)]))], find(&["minigrep", "-iUx", "FN  MAIN"], contents));
+        assert!(matches!(Config::new(args(&["minigrep", "--follow", "-U", "a", "log"])), Err(ArgsError::Conflict(..))));
+    }
+
+    #[test]
+    fn index_subcommand() {
+        let cfg = Config::new(args(&["minigrep", "index", "--index-dir", "idx", "logs"])).unwrap();
+        assert!(cfg.build_index && cfg.patterns.is_empty());
+        assert_eq!((Some(PathBuf::from("idx")), vec![PathBuf::from("logs")]), (cfg.index_dir, cfg.paths));
+        assert_eq!(vec![PathBuf::from(".")], Config::new(args(&["minigrep", "index"])).unwrap().paths);
+
+        // 子命令是第一个普通参数，它前面只能有 index 也接受的选项
+        let cfg = Config::new(args(&["minigrep", "--no-config", "--index-dir=idx", "index", "logs"])).unwrap();
+        assert!(cfg.build_index && cfg.patterns.is_empty());
+        assert_eq!((Some(PathBuf::from("idx")), vec![PathBuf::from("logs")]), (cfg.index_dir, cfg.paths));
+        for list in [&["minigrep", "-n", "index", "src"][..], &["minigrep", "--", "index", "src"], &["minigrep", "-e", "index", "src"]] {
+            let cfg = Config::new(args(list)).unwrap();
+            assert!(!cfg.build_index, "{:?}", list);
+            assert_eq!(vec!["index"], cfg.patterns);
+        }
+    }
+
+    #[test]
+    fn scans_for_no_config_like_the_parser() {
+        let scan = |list: &[&str]| scan(&list.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        assert_eq!((true, false), scan(&["-n", "--no-config", "fn"]));
+        assert_eq!((true, true), scan(&["--exclude", "*.log", "--no-config", "index", "src"]));
+        // 作为选项的值或者在 -- 之后的 --no-config 是要搜索的模式
+        assert_eq!((false, false), scan(&["-e", "--no-config", "src"]));
+        assert_eq!((false, false), scan(&["--regexp=x", "-j2", "--", "--no-config"]));
+
+        // USAGE 中带参数名（例如 --include GLOB）的选项都要取一个值，scan 才能和 Config::parse 一样跳过它
+        for line in USAGE.lines().map(str::trim_start).filter(|line| line.starts_with('-')) {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let long = words.iter().position(|word| word.starts_with("--")).unwrap();
+            let takes_value = words.get(long + 1).is_some_and(|word| word.chars().all(|c| c.is_ascii_uppercase()));
+            let names = words[..=long].iter().map(|word| word.trim_start_matches('-').trim_end_matches(','));
+            for name in names.map(|name| name.split('[').next().unwrap()) {
+                assert_eq!(takes_value, VALUE_OPTIONS.contains(&name), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn follow_needs_files_and_line_output() {
+        let cfg = Config::new(args(&["minigrep", "--follow", "-n", "ERROR", "app.log"])).unwrap();
+        assert!(cfg.follow && cfg.line_number);
+        for list in [
+            &["minigrep", "--follow", "ERROR"][..],
+            &["minigrep", "--follow", "ERROR", "-"],
+            &["minigrep", "--follow", "-c", "ERROR", "app.log"],
+            &["minigrep", "--follow", "-C", "2", "ERROR", "app.log"],
+            &["minigrep", "--follow", "--json", "ERROR", "app.log"],
+            &["minigrep", "--follow", "--histogram", "ERROR", "app.log"],
+        ] {
+            assert!(Config::new(args(list)).is_err(), "{:?}", list);
+        }
+    }
+
+    #[test]
+    fn fuzzy_matches_with_distance() {
+        let patterns = Patterns::new(&Config::new(args(&["minigrep", "-s", "--fuzzy", "1", "-e", "timeout", "-e", "refused"])).unwrap()).unwrap();
+        let line = b"conection timout, connection refusd";
+        let spans = search::find_all(&patterns, line);
+        assert_eq!(vec![Span { start: 10, end: 16 }, Span { start: 29, end: 35 }], spans);
+        assert_eq!(Some(1), patterns.distance(b"timout"));
+        assert!(patterns.find_at(b"time out", 0).is_some() && patterns.find_at(b"tim eout", 0).is_some());
+        assert!(patterns.find_at(b"tmieout", 0).is_none());
+
+        assert!(matches!(
+            Config::new(args(&["minigrep", "-E", "--fuzzy=2", "a"])),
+            Err(ArgsError::Conflict(..))
+        ));
+        // K 必须小于每个模式的长度（按字符计算）
+        assert!(Config::new(args(&["minigrep", "--fuzzy", "2", "édf"])).is_ok());
+        for list in [&["minigrep", "--fuzzy", "3", "édf"][..], &["minigrep", "--fuzzy", "2", "-e", "timeout", "-e", "ab"]] {
+            assert!(matches!(Config::new(args(list)), Err(ArgsError::InvalidValue(o, v)) if o == "--fuzzy" && v == list[2]), "{:?}", list);
+        }
+    }
+
+    #[test]
+    fn fuzzy_word_and_line_regexp() {
+        let find = |list: &[&str], line: &str| {
+            let patterns = Patterns::new(&Config::new(args(&[&["minigrep", "-s", "--fuzzy", "1"], list].concat())).unwrap()).unwrap();
+            patterns.find_at(line.as_bytes(), 0)
+        };
+        // 距离最小的 "hello" 后面紧跟着 'o'，距离为 1 的 "helloo" 才是整行、整个单词
+        assert_eq!(Some((0, 6)), find(&["-x", "hello"], "helloo"));
+        assert_eq!(Some((0, 6)), find(&["-w", "hello"], "helloo world"));
+        assert_eq!(Some((4, 8)), find(&["-w", "hello"], "say hell. helloo"));
+        assert_eq!(None, find(&["-x", "hello"], "hellooo"));
+        assert_eq!(Some((8, 14)), find(&["-x", "-e", "hello", "-e", "world"], "hellooo\nworldd"));
+    }
+
+    #[test]
+    fn config_rejects_invalid_regex() {
+        let cfg = Config::new(args(&["minigrep", "-E", "a|b", "poem.txt"])).unwrap();
+        assert!(cfg.regex);
+        assert_eq!(vec![PathBuf::from("poem.txt")], cfg.paths);
+
+        let err = Config::new(args(&["minigrep", "--regex", "(ab", "poem.txt"])).err().unwrap();
+        assert_eq!("invalid regex: unclosed group '(' at position 0", err.to_string());
+    }
+
+    #[test]
+    fn config_paths_and_globs() {
+        let args = ["minigrep", "--include", "*.rs", "--exclude=*_test.rs", "fn", "src", "tests", "README.md"];
+        let cfg = Config::new(args.iter().map(|s| s.to_string())).unwrap();
+        assert_eq!(vec!["fn"], cfg.patterns);
+        assert_eq!(vec![PathBuf::from("src"), PathBuf::from("tests"), PathBuf::from("README.md")], cfg.paths);
+        assert_eq!(vec!["*.rs"], cfg.include);
+        assert_eq!(vec!["*_test.rs"], cfg.exclude);
+        // 没有给出路径时搜索标准输入
+        assert_eq!(vec![PathBuf::from("-")], Config::new(["minigrep", "fn"].iter().map(|s| s.to_string())).unwrap().paths);
+    }
+
+    #[test]
+    fn config_usage_errors() {
+        assert!(matches!(Config::new(args(&["minigrep", "-Q", "fn", "src"])), Err(ArgsError::UnknownOption(o)) if o == "-Q"));
+        assert!(matches!(Config::new(args(&["minigrep", "--include"])), Err(ArgsError::MissingValue(_))));
+        assert!(matches!(Config::new(args(&["minigrep", "-h"])), Err(ArgsError::Help)));
+    }
+
+    #[test]
+    fn config_multiple_patterns() {
+        // 给出 -e 之后第一个普通参数是路径
+        let cfg = Config::new(args(&["minigrep", "-e", "fn", "--regexp=struct", "src"])).unwrap();
+        assert_eq!(vec!["fn", "struct"], cfg.patterns);
+        assert_eq!(vec![PathBuf::from("src")], cfg.paths);
+
+        let (_dir, files) = temp_files("patterns", &[("patterns.txt", b"TODO\r\nFIXME\n")]);
+        let cfg = Config::new(args(&["minigrep", "-f", files[0].to_str().unwrap(), "-e", "XXX"])).unwrap();
+        assert_eq!(vec!["TODO", "FIXME", "XXX"], cfg.patterns);
+        assert_eq!(vec![PathBuf::from("-")], cfg.paths);
+
+        assert!(matches!(Config::new(args(&["minigrep", "-f", "/nonexistent/patterns"])), Err(ArgsError::PatternFile(..))));
+        assert!(Config::new(args(&["minigrep", "-E", "-e", "ok", "-e", "(bad", "src"])).is_err());
+    }
+
+    // 测试用的临时目录，离开作用域时连同其中的内容一起删除，断言失败时也不会留下
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> TempDir {
+            let dir = env::temp_dir().join(format!("minigrep-{}-{}", name, std::process::id()));
+            // 上次运行被中途打断时可能留下了同名的目录
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        pub(crate) fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // 在临时目录中写入 files，返回目录和各个文件的路径
+    pub(crate) fn temp_files(name: &str, files: &[(&str, &[u8])]) -> (TempDir, Vec<PathBuf>) {
+        let dir = TempDir::new(name);
+        let paths = files
+            .iter()
+            .map(|(file, contents)| {
+                let path = dir.path().join(file);
+                fs::write(&path, contents).unwrap();
+                path
+            })
+            .collect();
+        (dir, paths)
+    }
+
+    // 按命令行参数 list 依次搜索 paths，返回所有输出以及每个文件是否“成功”
+    pub(crate) fn search_files(list: &[&str], paths: &[PathBuf]) -> (String, Vec<bool>) {
+        let mut list = list.to_vec();
+        list.splice(1..1, ["--no-config", "--color=never"]);
+        let search = FileSearch::new(Config::new(args(&list)).unwrap(), paths.len() > 1).unwrap();
+        let mut out = Vec::new();
+        let found = paths.iter().map(|path| search.file(path, &mut out).unwrap().0).collect();
+        (String::from_utf8(out).unwrap(), found)
+    }
+
+    #[test]
+    fn counts_lists_and_exit_status() {
+        let (dir, paths) = temp_files("status", &[("a.txt", b"fn main\nlet x\nfn run\n"), ("b.txt", b"nothing here\n")]);
+        let (a, b) = (paths[0].display(), paths[1].display());
+        assert_eq!((format!("{}:2\n{}:0\n", a, b), vec![true, false]), search_files(&["minigrep", "-c", "fn"], &paths));
+        assert_eq!((format!("{}:1\n{}:0\n", a, b), vec![true, false]), search_files(&["minigrep", "-c", "-m", "1", "fn"], &paths));
+        assert_eq!((format!("{}\n", a), vec![true, false]), search_files(&["minigrep", "-l", "fn"], &paths));
+        // -L 以列出了文件作为“成功”
+        assert_eq!((format!("{}\n", b), vec![false, true]), search_files(&["minigrep", "-L", "fn"], &paths));
+        assert_eq!(("2\n".to_string(), vec![true]), search_files(&["minigrep", "-c", "fn"], &paths[..1]));
+
+        // run 的返回值决定退出码：没有匹配时为 Ok(false)（退出码 1），有文件无法读取时为 Err（退出码 2）
+        let run_with = |list: &[&str]| run(Config::new(args(&[&["minigrep", "--no-config"], list].concat())).unwrap());
+        assert!(!run_with(&["fn", paths[1].to_str().unwrap()]).unwrap());
+        assert!(run_with(&["fn", paths[1].to_str().unwrap(), dir.path().join("missing.txt").to_str().unwrap()]).is_err());
+    }
+
+    #[test]
+    fn config_file_listing_and_max_count() {
+        // -l 和 -L 以最后给出的为准
+        let cfg = Config::new(args(&["minigrep", "-l", "--files-without-match", "--max-count=2", "fn", "src"])).unwrap();
+        assert!(!cfg.files_with_matches && cfg.files_without_match);
+        assert_eq!(Some(2), cfg.max_count);
+    }
+
+    #[test]
+    fn panicking_search_fails_only_its_file() {
+        assert_eq!(3, unwind_as_error("a.txt", || Ok(3)).unwrap());
+        let e = unwind_as_error("b.txt", || -> io::Result<()> { panic!("index out of bounds") }).unwrap_err();
+        assert_eq!("b.txt: internal error: index out of bounds", e.to_string());
+        let e = unwind_as_error("c.txt", || -> io::Result<()> { panic!("line {}", 7) }).unwrap_err();
+        assert_eq!("c.txt: internal error: line 7", e.to_string());
+    }
+
+    #[test]
+    fn only_matching_prints_groups_and_positions() {
+        let utf16: Vec<u8> = [0xFF, 0xFE].iter().copied().chain("héllo\nab ERROR\n".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        let (_dir, paths) = temp_files(
+            "only-matching",
+            &[("a.txt", b"say a=1 b= cc=3\nkey=9\n"), ("b.txt", b"one\ntwo three\nfour\n"), ("c.txt", &utf16)],
+        );
+        let only = |list: &[&str], path: &PathBuf| search_files(&[&["minigrep", "-o"], list].concat(), std::slice::from_ref(path)).0;
+
+        // 按组号和组名选择捕获组，"b=" 中没有参与匹配的组不输出
+        assert_eq!("1:7:6:1\n1:15:14:3\n2:5:20:9\n", only(&["-nb", "--column", "-E", "--capture", "2", r"(\w+)=(\d)?"], &paths[0]));
+        assert_eq!("a\nb\ncc\nkey\n", only(&["-E", "--capture", "key", r"(?P<key>\w+)=(\d)?"], &paths[0]));
+        // 空的匹配和空的捕获组都不输出
+        assert_eq!("", only(&["-E", "z*"], &paths[0]));
+        assert_eq!("1\n3\n9\n", only(&["-E", "--capture", "1", r"\w=(\d?)"], &paths[0]));
+        // -U 的匹配从记录中间的某一行开始时，行号、列号和字节偏移都是匹配开始处的
+        assert_eq!("1:3:2:e\nt\n2:9:12:e\nf\n", only(&["-nb", "--column", "-U", "-E", r"e\nt|e\nf"], &paths[1]));
+        // 转码后的文件中，字节偏移和列号仍然是原始文件中的：BOM 2 字节，"héllo\nab " 每个字符 2 字节
+        assert_eq!("2:7:20:ERROR\n", only(&["-nb", "--column", "ERROR"], &paths[2]));
+    }
+
+    #[test]
+    fn config_capture() {
+        let cfg = Config::new(args(&["minigrep", "-E", "--capture", "name", "(?P<name>\\w+)"])).unwrap();
+        assert!(cfg.only_matching && cfg.capture.as_deref() == Some("name"));
+        assert!(matches!(Config::new(args(&["minigrep", "--capture", "1", "fn"])), Err(ArgsError::Requires(..))));
+        assert!(matches!(Config::new(args(&["minigrep", "-E", "--capture", "1", "--replace", "x", "(f)n"])), Err(ArgsError::Conflict(..))));
+    }
+
+    #[test]
+    fn config_encoding() {
+        assert_eq!(Some(Encoding::Utf16Le), Config::new(args(&["minigrep", "--encoding", "UTF-16LE", "fn"])).unwrap().encoding);
+        assert_eq!(None, Config::new(args(&["minigrep", "--encoding=latin1", "--encoding", "auto", "fn"])).unwrap().encoding);
+        assert!(matches!(Config::new(args(&["minigrep", "--encoding", "utf-32", "fn"])), Err(ArgsError::InvalidValue(..))));
+    }
+
+    #[test]
+    fn config_json() {
+        assert!(Config::new(args(&["minigrep", "--json", "-n", "fn"])).unwrap().json);
+        assert!(matches!(Config::new(args(&["minigrep", "--json", "-l", "fn"])), Err(ArgsError::Conflict(..))));
+    }
+
+    #[test]
+    fn config_stats_and_histogram() {
+        let cfg = Config::new(args(&["minigrep", "-c", "--stats", "--histogram", "fn"])).unwrap();
+        assert!(cfg.count && cfg.stats && cfg.histogram);
+        assert!(matches!(Config::new(args(&["minigrep", "--json", "--stats", "fn"])), Err(ArgsError::Conflict(..))));
+    }
+
+    #[test]
+    fn config_file_defaults() {
+        let (_dir, files) = temp_files("config", &[("minigreprc", b"")]);
+        let file = &files[0];
+        fs::write(file, "ignore_case = true\ncontext = 2\ncolor = 'always'\nexclude = ['target/*']\n").unwrap();
+
+        let cfg = Config::parse(args(&["fn"]).collect(), &files).unwrap();
+        assert!(!cfg.case_sensitive);
+        assert_eq!((2, 2, ColorChoice::Always), (cfg.before_context, cfg.after_context, cfg.color));
+        // 命令行中的选项覆盖配置文件，列表类的选项则是追加
+        let cfg = Config::parse(args(&["-s", "-A1", "--color=never", "--exclude", "*.lock", "fn"]).collect(), &files).unwrap();
+        assert!(cfg.case_sensitive);
+        assert_eq!((2, 1, ColorChoice::Never), (cfg.before_context, cfg.after_context, cfg.color));
+        assert_eq!(vec!["target/*", "*.lock"], cfg.exclude);
+
+        fs::write(file, "line-number = true\nthreads = many\n").unwrap();
+        let result = Config::parse(args(&["fn"]).collect(), &files);
+        assert!(matches!(&result, Err(ArgsError::ConfigFile(at, _)) if at.ends_with(":2")));
+        // 配置文件中的 regexp 会让命令行中的 QUERY 变成路径，所以不允许；改变匹配或输出方式的选项也一样
+        fs::write(file, "regexp = \"x\"\n").unwrap();
+        let result = Config::parse(args(&["hello", "f.txt"]).collect(), &files);
+        assert!(matches!(&result, Err(ArgsError::ConfigFile(at, message)) if at.ends_with(":1") && message.contains("regexp")));
+        for key in ["regex", "multiline", "stats"] {
+            fs::write(file, format!("{} = true\n", key)).unwrap();
+            assert!(matches!(Config::parse(args(&["fn"]).collect(), &files), Err(ArgsError::ConfigFile(..))), "{}", key);
+        }
+
+        // 配置文件中打开的开关可以用 --no-* 关掉，而不必用 --no-config 放弃所有设置
+        let settings = "word-regexp = true\nmax-count = 1\nline-number = true\nbyte_offset = true\ncolumn = true\n\
+                        line-regexp = true\ntext = true\nsearch-zip = true\n";
+        fs::write(file, settings).unwrap();
+        let switches = |cfg: &Config| {
+            [cfg.word_regexp, cfg.max_count.is_some(), cfg.line_number, cfg.byte_offset, cfg.column, cfg.line_regexp, cfg.text, cfg.search_zip]
+        };
+        assert_eq!([true; 8], switches(&Config::parse(args(&["fn"]).collect(), &files).unwrap()));
+        let negated = args(&[
+            "--no-word-regexp", "--no-max-count", "--no-line-number", "--no-byte-offset", "--no-column", "--no-line-regexp", "--no-text",
+            "--no-search-zip", "fn",
+        ]);
+        assert_eq!([false; 8], switches(&Config::parse(negated.collect(), &files).unwrap()));
+
+        // --follow 不使用配置文件中的上下文和编码，命令行中给出的仍然会报告冲突
+        fs::write(file, "context = 2\nencoding = 'latin1'\n").unwrap();
+        let cfg = Config::parse(args(&["--follow", "fn", "app.log"]).collect(), &files).unwrap();
+        assert_eq!((0, 0, None), (cfg.before_context, cfg.after_context, cfg.encoding));
+        assert!(Config::parse(args(&["--follow", "-A1", "fn", "app.log"]).collect(), &files).is_err());
+    }
+
+    #[test]
+    fn config_threads() {
+        assert_eq!(4, Config::new(args(&["minigrep", "-j", "4", "fn", "src"])).unwrap().threads);
+        assert_eq!(2, Config::new(args(&["minigrep", "-j2", "fn", "src"])).unwrap().threads);
+        assert!(Config::new(args(&["minigrep", "-j", "0", "fn", "src"])).is_err());
+    }
+
+    #[test]
+    fn config_context() {
+        let cfg = Config::new(args(&["minigrep", "-C2", "fn", "src"])).unwrap();
+        assert_eq!((2, 2), (cfg.before_context, cfg.after_context));
+        // -A / -B 优先于 -C
+        let cfg = Config::new(args(&["minigrep", "-A", "1", "--context=3", "fn", "src"])).unwrap();
+        assert_eq!((3, 1), (cfg.before_context, cfg.after_context));
+        assert!(Config::new(args(&["minigrep", "-B", "x", "fn", "src"])).is_err());
+    }
+
+    #[test]
+    fn config_color() {
+        // 不带值的 --colour 等同于 --color=auto，后给出的覆盖前面的
+        let cfg = Config::new(args(&["minigrep", "--color=always", "--colour", "fn", "src"])).unwrap();
+        assert_eq!(ColorChoice::Auto, cfg.color);
+        assert_eq!(vec!["fn"], cfg.patterns);
+        assert_eq!(ColorChoice::Never, Config::new(args(&["minigrep", "--color=never", "fn"])).unwrap().color);
+        assert!(matches!(Config::new(args(&["minigrep", "--color=sometimes", "fn"])), Err(ArgsError::InvalidValue(..))));
+    }
+
+    #[test]
+    fn config_replace() {
+        let cfg = Config::new(args(&["minigrep", "--replace", "$0!", "--dry-run", "fn", "src"])).unwrap();
+        assert_eq!((Some("$0!"), false, true), (cfg.replace.as_deref(), cfg.in_place, cfg.dry_run));
+        assert!(matches!(Config::new(args(&["minigrep", "--in-place", "fn"])), Err(ArgsError::Requires(..))));
+        assert!(matches!(Config::new(args(&["minigrep", "--replace", "x", "--in-place", "-c", "fn"])), Err(ArgsError::Conflict(..))));
+        // 和 grep 一样 -r 不是 --replace 的简写
+        assert!(matches!(Config::new(args(&["minigrep", "-r", "TODO", "src"])), Err(ArgsError::UnknownOption(..))));
+    }
+
+    #[test]
+    fn config_fields() {
+        let cfg = Config::new(args(&["minigrep", "--csv-field", "2", "--json-path", "a.b", "--json-path", "x", "fn"]));
+        assert!(matches!(cfg, Err(ArgsError::Conflict(first, second)) if first == "--csv-field" && second == "--json-path"));
+        assert_eq!(Some("level".to_string()), Config::new(args(&["minigrep", "--csv-field", "level", "fn"])).unwrap().csv_field);
+        assert!(matches!(Config::new(args(&["minigrep", "--csv-field", "0", "fn"])), Err(ArgsError::InvalidValue(..))));
+        assert!(matches!(Config::new(args(&["minigrep", "--json-path", "a.", "fn"])), Err(ArgsError::InvalidValue(..))));
+        assert!(matches!(Config::new(args(&["minigrep", "--json-path", "a", "-U", "fn"])), Err(ArgsError::Conflict(..))));
     }
 }
 
@@ -183,8 +1151,14 @@ search函数设计：
 
 使用显式生命周期'a：表明contents的生命周期和返回的vector生命周期相关联
 因为实现里面vector包含了contents slice的字符串 slice
+
+最初返回的是 Vec<&str>，调用者只能拿到匹配的行本身，无法知道匹配发生在哪里。
+现在返回结构化的匹配记录 Match：行号、行首的字节偏移以及行内每个匹配的区间。
+搜索引擎按字节处理内容（文件不一定是合法的 UTF-8），所以 Match 中的行是 &[u8]。
+遍历每一行、收集记录的逻辑统一放在 search 模块中，这里只需要告诉它“怎么在一行中找到下一个匹配”。
 */
-fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+/// 大小写敏感地搜索 `query`，返回所有匹配行的记录
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
     /*
     let mut results = Vec::new();
     // 使用 lines 方法遍历每一行
@@ -200,45 +1174,895 @@ fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
     // 使用迭代器适配器来使代码更简明 也避免了一个可变的中间 results vector 的使用。
     // 函数式编程风格倾向于最小化可变状态的数量来使代码更简洁。
     // 去掉可变状态可能会使得将来进行并行搜索的增强变得更容易，因为我们不必管理 results vector 的并发访问。
+    /*
     contents.lines()
         .filter(|line| line.contains(query))    // 使用 filter 适配器只保留 line.contains(query) 返回 true 的那些行
         .collect()                              // 将匹配行收集到另一个 vector 中
+    */
+    let automaton = AhoCorasick::new(&[query], false);
+    search::search_lines(contents.as_bytes(), &automaton, &SearchOptions::default())
 }
 
 
-fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+/// 大小写不敏感地搜索 `query`，按 Unicode 简单大小写折叠比较字符
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    /*
+    最初的实现把查询串和每一行都转成小写再比较：
     let query = query.to_lowercase();
-    let mut results = Vec::new();
-    // 使用 lines 方法遍历每一行
-    for line in contents.lines() {
-        // 用查询字符串搜索每一行
-        if line.to_lowercase().contains(&query) {
-            // 存储匹配的行
-            results.push(line);
+    contents.lines()
+        .filter(|line| line.to_lowercase().contains(&query))
+        .collect()
+    to_lowercase 每行都会分配一个新的 String，而且小写转换不等于大小写折叠（例如 'ſ' 和 's'、'ς' 和 'Σ'）。
+    现在由 casefold::Finder 逐个字符比较折叠后的结果，不分配内存，返回的区间也直接对应原始行。
+    */
+    let finder = casefold::Finder::new(query);
+    search::search_lines(contents.as_bytes(), &finder, &SearchOptions::default())
+}
+
+
+/// -E 模式：用编译好的正则表达式搜索
+pub fn search_regex<'a>(re: &Regex, contents: &'a str) -> Vec<Match<'a>> {
+    search::search_lines(contents.as_bytes(), re, &SearchOptions::default())
+}
+
+
+// 从文件中读取模式，每行一个；"-" 表示从标准输入读取
+fn read_patterns(file: &str) -> io::Result<Vec<String>> {
+    let contents = match file {
+        "-" => io::read_to_string(io::stdin())?,
+        _ => fs::read_to_string(file)?,
+    };
+    Ok(contents.lines().map(String::from).collect())
+}
+
+// 根据配置选择的匹配方式
+enum Patterns {
+    Regex(Regex),
+    // 单个模式的大小写不敏感查找
+    Folded(casefold::Finder, Bounds),
+    // 其他固定字符串，包括同时查找多个模式
+    Literals(AhoCorasick, Bounds),
+    // --fuzzy：每个模式各自近似匹配，取最靠左的
+    Fuzzy(Vec<Fuzzy>, Bounds),
+}
+
+// -w / -x 对固定字符串的限制，正则表达式在编译时就带上了这些限制。多个固定字符串从同一个起点可能有长短不同的
+// 多个候选，只检查自动机给出的那一个是不够的，所以把 accept 交给自动机逐个检查
+#[derive(Debug, Clone, Copy, Default)]
+struct Bounds {
+    word: bool,
+    line: bool,
+}
+
+impl Bounds {
+    // 和 grep 一样，-w 要求匹配的前后都不是单词字符（Unicode 字母、数字或 '_'），-x 要求匹配整行。
+    // -U 时 line 是整个文件，“整行”指从某一行的行首到这一行（或之后某一行）的行尾
+    fn accept(&self, line: &[u8], start: usize, end: usize) -> bool {
+        (!self.line || (utf8::is_line_start(line, start) && utf8::is_line_end(line, end)))
+            && (!self.word || utf8::is_whole_word(line, start, end))
+    }
+
+    // 用 find 查找满足限制的下一个匹配。单个固定字符串从同一个起点开始只有一个候选，
+    // 不满足限制时从下一个字符开始继续查找即可
+    fn find(&self, line: &[u8], mut at: usize, find: impl Fn(usize) -> Option<(usize, usize)>) -> Option<(usize, usize)> {
+        loop {
+            let (start, end) = find(at)?;
+            if self.accept(line, start, end) {
+                return Some((start, end));
+            }
+            // -x 时同一行里不会再有别的候选，直接从下一行开始
+            at = match self.line {
+                true => start + line[start..].iter().position(|&b| b == b'\n').map_or(line.len(), |i| i + 1),
+                false => start + utf8::decode(line, start).map_or(1, |(_, len)| len),
+            };
+            if at > line.len() || (self.line && at == line.len()) {
+                return None;
+            }
         }
     }
-    results
 }
 
+impl search::Matcher for Patterns {
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        self.find_at(line, at)
+    }
+
+    fn find_nonempty_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        match self {
+            Patterns::Regex(re) => search::Matcher::find_nonempty_at(re, line, at),
+            _ => None,
+        }
+    }
+}
+
+impl Patterns {
+    fn new(cfg: &Config) -> Result<Patterns, regex::Error> {
+        let bounds = Bounds { word: cfg.word_regexp, line: cfg.line_regexp };
+        if let Some(distance) = cfg.fuzzy {
+            let fuzzy = cfg.patterns.iter().map(|p| Fuzzy::new(p, distance, !cfg.case_sensitive)).collect();
+            return Ok(Patterns::Fuzzy(fuzzy, bounds));
+        }
+        // 空的模式文件不匹配任何内容；组合成正则表达式的话反而会变成匹配所有内容的空模式
+        if cfg.regex && !cfg.patterns.is_empty() {
+            // 多个模式组合成一个分支
+            let alternatives: Vec<String> = cfg.patterns.iter().map(|p| format!("(?:{})", p)).collect();
+            let pattern = alternatives.join("|");
+            // 大小写不敏感、-w 和 -x 都由正则引擎在编译时处理
+            let options = RegexOptions {
+                case_insensitive: !cfg.case_sensitive,
+                whole_word: cfg.word_regexp,
+                whole_line: cfg.line_regexp,
+                multi_line: cfg.multiline,
+            };
+            return Ok(Patterns::Regex(Regex::with_options(&pattern, &options)?));
+        }
+        match &cfg.patterns[..] {
+            [query] if !cfg.case_sensitive => Ok(Patterns::Folded(casefold::Finder::new(query), bounds)),
+            patterns => Ok(Patterns::Literals(AhoCorasick::new(patterns, !cfg.case_sensitive), bounds)),
+        }
+    }
+
+    // find_all 找到的一个匹配的各个捕获组；不使用正则表达式时只有代表整个匹配的 0 号组。
+    // 空匹配之后换成的非空匹配要用 captures_nonempty_at 才能重新得到
+    fn captures_of(&self, line: &[u8], span: Span) -> Option<Captures> {
+        match self {
+            Patterns::Regex(re) => {
+                let caps = re.captures_at(line, span.start)?;
+                match caps.get(0) == Some((span.start, span.end)) {
+                    true => Some(caps),
+                    false => re.captures_nonempty_at(line, span.start),
+                }
+            }
+            _ => Some(Captures::from_match(span.start, span.end)),
+        }
+    }
+
+    // 替换模板中的组名或组号对应的捕获组序号
+    fn capture_index(&self, name: &str) -> Option<usize> {
+        let count = match self {
+            Patterns::Regex(re) => re.captures_len(),
+            _ => 1,
+        };
+        match (name.parse::<usize>(), self) {
+            (Ok(i), _) => Some(i).filter(|&i| i < count),
+            (Err(_), Patterns::Regex(re)) => re.capture_index(name),
+            (Err(_), _) => None,
+        }
+    }
+
+    // 在 line 中从 at 开始查找下一个匹配
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        match self {
+            Patterns::Regex(re) => re.find_at(line, at),
+            Patterns::Folded(finder, bounds) => bounds.find(line, at, |at| finder.find_at(line, at)),
+            Patterns::Literals(automaton, bounds) => {
+                automaton.find_accepted(line, at, |start, end| bounds.accept(line, start, end))
+            }
+            // 近似匹配的结尾和起点都可能有多个候选，由 find_accepted 逐个检查 -w / -x 的限制。起点相同时取较长的那个
+            Patterns::Fuzzy(fuzzy, bounds) => fuzzy
+                .iter()
+                .filter_map(|f| f.find_accepted(line, at, |start, end| bounds.accept(line, start, end)))
+                .map(|(start, end, _)| (start, end))
+                .min_by_key(|&(start, end)| (start, !end)),
+        }
+    }
+
+    // --fuzzy 时匹配到的文本与（最接近的）模式的编辑距离，其他匹配方式返回 None
+    fn distance(&self, text: &[u8]) -> Option<usize> {
+        match self {
+            Patterns::Fuzzy(fuzzy, _) => fuzzy.iter().map(|f| f.distance(text)).min(),
+            _ => None,
+        }
+    }
+}
+
+
+// 一次搜索中所有线程共享的只读状态
+struct FileSearch {
+    cfg: Config,
+    options: SearchOptions,
+    // 不上色时为 Colors::none()，输出时就不需要再区分是否上色
+    colors: Colors,
+    patterns: Patterns,
+    // --replace 的模板
+    template: Option<Template>,
+    // --capture 的组号
+    capture: Option<usize>,
+    // --csv-field / --json-path 要匹配的字段
+    field: Option<Field>,
+    with_filename: bool,
+}
+
+impl FileSearch {
+    // 编译模式和替换模板，准备好搜索每个文件都要用到的设置。with_filename 取决于要搜索哪些文件，由调用者决定
+    fn new(cfg: Config, with_filename: bool) -> Result<FileSearch, Box<dyn Error>> {
+        let patterns = Patterns::new(&cfg)?;
+        let template = match &cfg.replace {
+            Some(template) => Some(Template::new(template, |name| patterns.capture_index(name))?),
+            None => None,
+        };
+        let capture = match &cfg.capture {
+            Some(name) => Some(patterns.capture_index(name).ok_or_else(|| format!("invalid --capture: no capture group '{}'", name))?),
+            None => None,
+        };
+        // 和 grep 一样，-o 时不输出上下文行
+        let (before_context, after_context) = match cfg.only_matching && !cfg.json {
+            true => (0, 0),
+            false => (cfg.before_context, cfg.after_context),
+        };
+        let field = match (&cfg.csv_field, &cfg.json_path) {
+            (Some(spec), _) => Field::csv(spec),
+            (None, Some(path)) => Field::json(path),
+            (None, None) => None,
+        };
+        let options = SearchOptions {
+            invert: cfg.invert_match,
+            before_context,
+            after_context,
+            max_count: cfg.max_count,
+            detect_binary: false,
+            multiline: cfg.multiline,
+            encoding: cfg.encoding,
+            csv: cfg.csv_field.is_some(),
+            csv_header: matches!(field, Some(Field::CsvName(_))),
+        };
+        let colors = if cfg.color.enabled() && !cfg.json { Colors::from_env() } else { Colors::none() };
+        Ok(FileSearch { cfg, options, colors, patterns, template, capture, field, with_filename })
+    }
+
+    // 输出行的前缀依次为：文件名、行号、列号、字节偏移，按配置决定是否输出，每一项后面跟着分隔符 sep
+    fn write_prefix(&self, out: &mut dyn Write, name: &str, line_number: usize, column: Option<usize>, byte_offset: usize, sep: &[u8]) -> io::Result<()> {
+        let colors = &self.colors;
+        if self.with_filename {
+            color::paint(out, &colors.file_name, name.as_bytes())?;
+            color::paint(out, &colors.separator, sep)?;
+        }
+        if self.cfg.line_number || self.cfg.column {
+            color::paint(out, &colors.line_number, line_number.to_string().as_bytes())?;
+            color::paint(out, &colors.separator, sep)?;
+        }
+        if let (true, Some(column)) = (self.cfg.column, column) {
+            color::paint(out, &colors.line_number, column.to_string().as_bytes())?;
+            color::paint(out, &colors.separator, sep)?;
+        }
+        if self.cfg.byte_offset {
+            color::paint(out, &colors.byte_offset, byte_offset.to_string().as_bytes())?;
+            color::paint(out, &colors.separator, sep)?;
+        }
+        Ok(())
+    }
+
+    // 输出一行的内容并换行：spans 中的匹配用 match_color 高亮，其余部分用 line_color
+    fn write_line(&self, out: &mut dyn Write, line: &[u8], spans: &[Span], match_color: &str, line_color: &str) -> io::Result<()> {
+        let mut at = 0;
+        for span in spans {
+            color::paint(out, line_color, &line[at..span.start])?;
+            color::paint(out, match_color, &line[span.start..span.end])?;
+            at = span.end;
+        }
+        color::paint(out, line_color, &line[at..])?;
+        out.write_all(b"\n")
+    }
+
+    // 搜索一个文件（"-" 为标准输入），边读边把格式化好的结果写到 out，
+    // 返回是否有选中的行（决定退出码）以及这个文件的统计数据
+    fn file(&self, path: &Path, out: &mut dyn Write) -> io::Result<(bool, Stats)> {
+        if let (true, Some(template)) = (self.cfg.in_place || self.cfg.dry_run, &self.template) {
+            return self.rewrite_file(path, template, out);
+        }
+        let (name, reader): (String, Box<dyn BufRead>) = if path.as_os_str() == "-" {
+            ("(standard input)".to_string(), Box::new(BufReader::new(io::stdin())))
+        } else {
+            (path.display().to_string(), Box::new(BufReader::new(File::open(path)?)))
+        };
+        // 压缩文件按魔数识别，而不是看扩展名，这样从标准输入读入的压缩数据也能搜索
+        let reader = match self.cfg.search_zip {
+            true => gzip::maybe_decompress(reader)?,
+            false => reader,
+        };
+        let Some(field) = &self.field else {
+            return self.search(&name, reader, &self.patterns, out);
+        };
+        // 每个文件有自己的表头，所以每个文件用一个新的 FieldMatcher
+        let matcher = FieldMatcher::new(&self.patterns, field);
+        let result = self.search(&name, reader, &matcher, out)?;
+        match matcher.missing_column() {
+            Some(column) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("no column named '{}' in the CSV header", column))),
+            None => Ok(result),
+        }
+    }
+
+    // 用 matcher 搜索 reader 中的内容，按配置选择输出方式
+    fn search(&self, name: &str, reader: Box<dyn BufRead>, matcher: &dyn search::Matcher, out: &mut dyn Write) -> io::Result<(bool, Stats)> {
+        // 二进制文件只报告是否匹配，除非用 -a 要求当作文本处理
+        let options = SearchOptions { detect_binary: !self.cfg.text, ..self.options };
+
+        if self.cfg.json {
+            let mut printer = JsonPrinter { search: self, name, out, start: Instant::now(), matches: 0 };
+            let stats = self.search_with(name, reader, matcher, options, &mut printer)?;
+            return Ok((stats.matched_lines > 0, Stats { matches: printer.matches, ..stats }));
+        }
+
+        if self.cfg.files_with_matches || self.cfg.files_without_match {
+            // 只关心有没有匹配：找到第一行就可以停止了，除非 --stats、--histogram 要数出所有选中的行
+            let max_count = match self.cfg.stats || self.cfg.histogram {
+                true => self.options.max_count,
+                false => Some(1),
+            };
+            let options = SearchOptions { max_count, ..self.options };
+            let stats = self.search_with(name, reader, matcher, options, &mut Discard)?;
+            // -L 以列出了文件作为“成功”
+            let listed = (stats.matched_lines > 0) == self.cfg.files_with_matches;
+            if listed {
+                color::paint(out, &self.colors.file_name, name.as_bytes())?;
+                out.write_all(b"\n")?;
+            }
+            return Ok((listed, stats));
+        }
+
+        if self.cfg.count {
+            let stats = self.search_with(name, reader, matcher, self.options, &mut Discard)?;
+            let selected = stats.matched_lines;
+            if self.with_filename {
+                color::paint(out, &self.colors.file_name, name.as_bytes())?;
+                color::paint(out, &self.colors.separator, b":")?;
+            }
+            writeln!(out, "{}", selected)?;
+            return Ok((selected > 0, stats));
+        }
+
+        let mut printer = Printer { search: self, matcher, name, out };
+        let stats = self.search_with(name, reader, matcher, options, &mut printer)?;
+        Ok((stats.matched_lines > 0, stats))
+    }
+
+    // 把搜索结果交给 sink，返回这个文件的统计数据；--histogram 时顺便数出这个文件以及每个小时选中的行数
+    fn search_with(&self, name: &str, reader: Box<dyn BufRead>, matcher: &dyn search::Matcher, options: SearchOptions, sink: &mut dyn Sink) -> io::Result<Stats> {
+        let searcher = Searcher::new(matcher, options);
+        if !self.cfg.histogram {
+            return Ok(Stats::of(&searcher.search_reader(name, reader, sink)?));
+        }
+        let mut tally = Tally { sink, hours: BTreeMap::new() };
+        let mut stats = Stats::of(&searcher.search_reader(name, reader, &mut tally)?);
+        stats.histogram.hours = tally.hours;
+        if stats.matched_lines > 0 {
+            stats.histogram.files.push((name.to_string(), stats.matched_lines));
+        }
+        Ok(stats)
+    }
+
+    // --in-place / --dry-run：替换文件中所有选中行里的匹配，原子地写回文件，或者只输出 diff 预览。
+    // 二进制文件和普通输出一样只报告是否匹配，不会被改写
+    fn rewrite_file(&self, path: &Path, template: &Template, out: &mut dyn Write) -> io::Result<(bool, Stats)> {
+        if path.as_os_str() == "-" {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "standard input can't be rewritten in place"));
+        }
+        let name = path.display().to_string();
+        let contents = fs::read(path)?;
+        if self.cfg.search_zip && contents.starts_with(&gzip::MAGIC) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "compressed files can't be rewritten in place"));
+        }
+        // 替换结果是 UTF-8，写回去还要重新编码，目前只支持改写 UTF-8 的文件（带 BOM 的也可以，BOM 会原样保留）
+        if let (encoding @ (Encoding::Utf16Le | Encoding::Utf16Be | Encoding::Latin1), _) = Encoding::detect(&contents, self.cfg.encoding) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} files can't be rewritten in place", encoding)));
+        }
+        let captures = |line: &[u8], span| self.patterns.captures_of(line, span);
+        let options = SearchOptions { before_context: 0, after_context: 0, detect_binary: !self.cfg.text, ..self.options };
+        let mut changes = Vec::new();
+        let summary = search::search_reader(&contents[..], &self.patterns, &options, &mut |event| {
+            if let Event::Match(m) = event {
+                let (new, _) = replace::replace_all(m.line, &m.spans, &captures, template);
+                if new != m.line {
+                    changes.push(Change { line_number: m.line_number, byte_offset: m.byte_offset, old: m.line.to_vec(), new });
+                }
+            }
+            Ok(())
+        })?;
+        if summary.binary_match {
+            writeln!(out, "Binary file {} matches", name)?;
+        } else if !changes.is_empty() {
+            match self.cfg.dry_run {
+                true => replace::write_diff(out, &name, &changes)?,
+                false => replace::write_atomic(path, &replace::apply(&contents, &changes))?,
+            }
+        }
+        Ok((summary.selected > 0, Stats::of(&summary)))
+    }
+
+    // --follow：轮流检查每个文件，输出新的匹配行，直到按下 Ctrl-C。
+    // 单个文件出错（例如暂时没有读权限）时报告一次并继续跟踪其他文件；-m 达到上限的文件不再输出
+    fn follow(&self, files: Vec<io::Result<PathBuf>>) -> Result<bool, Box<dyn Error>> {
+        follow::stop_on_interrupt();
+        let mut tails = Vec::new();
+        for file in files {
+            match file {
+                Ok(path) => tails.push((path.display().to_string(), follow::Tail::new(path), 0, None)),
+                Err(e) => eprintln!("minigrep: {}", e),
+            }
+        }
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let mut matched = false;
+        while !follow::stopped() {
+            for (name, tail, selected, last_error) in &mut tails {
+                let mut printer = Printer { search: self, matcher: &self.patterns, name, out: &mut out };
+                let result = tail.poll(&mut |line_number, byte_offset, line| {
+                    let spans = search::find_all(&self.patterns, line);
+                    if spans.is_empty() != self.cfg.invert_match || self.cfg.max_count.is_some_and(|max| *selected >= max) {
+                        return Ok(());
+                    }
+                    *selected += 1;
+                    matched = true;
+                    printer.matched(&Match { line_number, byte_offset, line, spans, encoding: Encoding::Utf8 })
+                });
+                match result {
+                    Ok(notice) => {
+                        if let Some(notice) = notice {
+                            eprintln!("minigrep: {}: {}", name, notice);
+                        }
+                        *last_error = None;
+                    }
+                    // 管道另一端关闭（例如 minigrep --follow ... | head）时安静地结束
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(matched),
+                    Err(e) => {
+                        let message = e.to_string();
+                        if last_error.as_ref() != Some(&message) {
+                            eprintln!("minigrep: {}: {}", name, message);
+                        }
+                        *last_error = Some(message);
+                    }
+                }
+            }
+            match out.flush() {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(matched),
+                result => result?,
+            }
+            follow::sleep(follow::INTERVAL);
+        }
+        Ok(matched)
+    }
+
+    // --fuzzy 时各个匹配的编辑距离，其他情况下为空
+    fn distances(&self, m: &Match) -> Vec<usize> {
+        m.spans.iter().filter_map(|s| self.patterns.distance(&m.line[s.start..s.end])).collect()
+    }
+}
+
+// -c / -l / -L 只需要搜索结束后的计数，不处理任何一行
+struct Discard;
+
+impl Sink for Discard {
+    fn matched(&mut self, _m: &Match) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// 命令行的文本输出。和 grep 一样，匹配行的各个前缀之后用 ':' 分隔，上下文行用 '-' 分隔，不相邻的两组之间输出 "--"
+struct Printer<'s> {
+    search: &'s FileSearch,
+    // 查找匹配用的 Matcher，-v 时用它标出上下文行中的匹配
+    matcher: &'s dyn search::Matcher,
+    name: &'s str,
+    out: &'s mut dyn Write,
+}
+
+impl Printer<'_> {
+    // -o：每个匹配单独输出一行，--capture 时输出指定的组，--replace 时输出替换的结果。
+    // 前缀中的行号、列号和字节偏移都是输出的文本开始处的；没有参与匹配的组和空的文本不输出
+    fn only_matching(&mut self, m: &Match) -> io::Result<()> {
+        let (search, out) = (self.search, &mut *self.out);
+        let colors = &search.colors;
+        let distances = search.distances(m);
+        for (i, span) in m.spans.iter().enumerate() {
+            let caps = || search.patterns.captures_of(m.line, *span);
+            let (start, text) = match (&search.template, search.capture) {
+                (Some(template), _) => {
+                    let mut replaced = Vec::new();
+                    if let Some(caps) = caps() {
+                        template.expand(m.line, &caps, &mut replaced);
+                    }
+                    (span.start, replaced)
+                }
+                (None, Some(group)) => match caps().and_then(|caps| caps.get(group)) {
+                    Some((start, end)) => (start, m.line[start..end].to_vec()),
+                    None => continue,
+                },
+                (None, None) => (span.start, m.line[span.start..span.end].to_vec()),
+            };
+            if text.is_empty() {
+                continue;
+            }
+            // -U 时匹配可能从记录中间的某一行开始
+            let line_start = m.line[..start].iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+            let line_number = m.line_number + m.line[..line_start].iter().filter(|&&b| b == b'\n').count();
+            let column = m.encoding.original_len(&m.line[line_start..start]) + 1;
+            search.write_prefix(out, self.name, line_number, Some(column), m.offset_of(start), b":")?;
+            if let Some(distance) = distances.get(i) {
+                color::paint(out, &colors.line_number, format!("~{}", distance).as_bytes())?;
+                color::paint(out, &colors.separator, b":")?;
+            }
+            color::paint(out, &colors.selected_match, &text)?;
+            out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for Printer<'_> {
+    fn matched(&mut self, m: &Match) -> io::Result<()> {
+        if self.search.cfg.only_matching {
+            return self.only_matching(m);
+        }
+        let (search, out) = (self.search, &mut *self.out);
+        let colors = &search.colors;
+        search.write_prefix(out, self.name, m.line_number, m.column(), m.byte_offset, b":")?;
+        // --fuzzy：在行的内容前面输出这一行中最接近的匹配的编辑距离，例如 "~1:"
+        if let Some(distance) = search.distances(m).into_iter().min() {
+            color::paint(out, &colors.line_number, format!("~{}", distance).as_bytes())?;
+            color::paint(out, &colors.separator, b":")?;
+        }
+        // 输出替换后的行，高亮的是替换进来的文本。-U 时替换后的内容和原来的行不再一一对应，只在开头输出一次前缀
+        if let Some(template) = &search.template {
+            let (line, spans) = replace::replace_all(m.line, &m.spans, &|line, span| search.patterns.captures_of(line, span), template);
+            return search.write_line(out, &line, &spans, &colors.selected_match, &colors.selected_line);
+        }
+        // -U 的一条记录可能有多行，和 grep 一样每行单独输出，各自带上行号和字节偏移
+        let mut offset = 0;
+        for (i, raw) in m.line.split(|&b| b == b'\n').enumerate() {
+            let end = offset + raw.len();
+            let row = match end < m.line.len() {
+                true => raw.strip_suffix(b"\r").unwrap_or(raw),
+                false => raw,
+            };
+            if i > 0 {
+                search.write_prefix(out, self.name, m.line_number + i, None, m.offset_of(offset), b":")?;
+            }
+            let spans: Vec<Span> = m
+                .spans
+                .iter()
+                .filter_map(|s| {
+                    let clamp = |pos: usize| pos.clamp(offset, offset + row.len()) - offset;
+                    let span = Span { start: clamp(s.start), end: clamp(s.end) };
+                    // 只经过换行符的部分不用高亮；空匹配留在它所在的那一行
+                    (span.start < span.end || (s.start == s.end && (offset..=end).contains(&s.start))).then_some(span)
+                })
+                .collect();
+            search.write_line(out, row, &spans, &colors.selected_match, &colors.selected_line)?;
+            offset = end + 1;
+        }
+        Ok(())
+    }
+
+    fn context(&mut self, c: &Context) -> io::Result<()> {
+        let (search, out) = (self.search, &mut *self.out);
+        let colors = &search.colors;
+        search.write_prefix(out, self.name, c.line_number, None, c.byte_offset, b"-")?;
+        // -v 时上下文行反而是包含匹配的行，上色时也把其中的匹配标出来
+        let spans = match search.cfg.invert_match && !colors.context_match.is_empty() {
+            true => search::find_all(self.matcher, c.line),
+            false => Vec::new(),
+        };
+        search.write_line(out, c.line, &spans, &colors.context_match, &colors.context_line)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        color::paint(self.out, &self.search.colors.separator, b"--")?;
+        self.out.write_all(b"\n")
+    }
+
+    fn end(&mut self, path: &str, summary: &Summary) -> io::Result<()> {
+        if summary.binary_match {
+            writeln!(self.out, "Binary file {} matches", path)?;
+        }
+        Ok(())
+    }
+}
+
+// --json：依次输出 begin、每个匹配行和上下文行、end 事件。二进制文件同样只报告是否匹配，不输出匹配行
+struct JsonPrinter<'s> {
+    search: &'s FileSearch,
+    name: &'s str,
+    out: &'s mut dyn Write,
+    start: Instant,
+    // 匹配的总数（不是行数），Summary 中没有这一项
+    matches: usize,
+}
+
+impl Sink for JsonPrinter<'_> {
+    fn begin(&mut self, path: &str) -> io::Result<()> {
+        json::begin(self.out, path)
+    }
+
+    fn matched(&mut self, m: &Match) -> io::Result<()> {
+        self.matches += m.spans.len();
+        let distances = self.search.distances(m);
+        json::line(self.out, "match", self.name, m.line, m.line_number, m.byte_offset, &m.spans, m.encoding, &distances)
+    }
+
+    fn context(&mut self, c: &Context) -> io::Result<()> {
+        json::line(self.out, "context", self.name, c.line, c.line_number, c.byte_offset, &[], Encoding::Utf8, &[])
+    }
+
+    fn end(&mut self, path: &str, summary: &Summary) -> io::Result<()> {
+        let stats = Stats { matches: self.matches, ..Stats::of(summary) };
+        json::end(self.out, path, summary.binary_match, &stats, self.start.elapsed())
+    }
+}
+
+// 工作线程发回主线程的消息：一段输出，或者一个文件搜索完毕
+enum Report {
+    Output(Vec<u8>),
+    Done(io::Result<(bool, Stats)>),
+}
+
+// 工作线程的输出先攒在缓冲区里，每满一块就发给主线程。
+// 这样大文件的结果可以边搜边打印，而不必等整个文件搜索完、也不必把所有结果都放在内存里。
+struct ChunkWriter<'s> {
+    index: usize,
+    sender: &'s mpsc::Sender<(usize, Report)>,
+    buf: Vec<u8>,
+}
+
+impl ChunkWriter<'_> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+}
+
+impl Write for ChunkWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= Self::CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
 
-pub fn run(cfg: Config) -> Result<(), Box<dyn Error>> {
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            let chunk = std::mem::take(&mut self.buf);
+            // 主线程因为输出出错提前返回时接收端已被丢弃，此时继续搜索也没有意义了
+            self.sender
+                .send((self.index, Report::Output(chunk)))
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "output closed"))?;
+        }
+        Ok(())
+    }
+}
+
+
+/// 执行搜索并把结果打印到标准输出。
+///
+/// 返回值表示是否有选中的行（`-L` 时表示是否列出了文件），`main` 据此决定退出码：
+/// 和 grep 一样，0 表示找到了匹配，1 表示没有匹配，2 表示出错。
+///
+/// # Errors
+///
+/// 正则表达式无法编译、写标准输出失败，或者有文件无法读取时返回错误；
+/// 无法读取的文件会先在标准错误中逐个报告，不影响其他文件的搜索。
+pub fn run(cfg: Config) -> Result<bool, Box<dyn Error>> {
     // Box<dyn Error> 意味着函数会返回实现了 Error trait 的类型，不过无需指定具体将会返回的值的类型。
     // 这提供了在不同的错误场景可能有不同类型的错误返回值的灵活性。这也就是 dyn，它是 “动态的”（“dynamic”）的缩写。
     // 使用 ? => 允许返回的 “任何类型的错误(实现了Error trait的类型)” => Box<dyn Error>
     // 可以后头看一下17result.rs中的传播（propagating）概念
-    let contents = fs::read_to_string(cfg.filename)?;
-    // println!("With text:{}", contents);
-    // println!("Hello, world!");
-
-    let results = if cfg.case_sensitive {
-        search(&cfg.query, &contents)
-    } else {
-        search_case_insensitive(&cfg.query, &contents)
+    let start = Instant::now();
+    let options = WalkOptions {
+        include: cfg.include.iter().map(|p| Glob::new(p)).collect(),
+        exclude: cfg.exclude.iter().map(|p| Glob::new(p)).collect(),
+    };
+    let files = match cfg.follow {
+        // 跟踪时要等待还不存在的文件出现，它们不能当作错误；目录则只跟踪启动时其中已有的文件
+        true => cfg
+            .paths
+            .iter()
+            .flat_map(|p| match p.is_dir() {
+                true => walk::walk(std::slice::from_ref(p), &options),
+                false => vec![Ok(p.clone())],
+            })
+            .collect(),
+        false => walk::walk(&cfg.paths, &options),
+    };
+    if cfg.build_index {
+        return build_index(&cfg, &files);
+    }
+    // 和 grep 一样：只搜索单个文件时直接打印匹配行，否则在每行前加上文件路径。
+    // 在用索引排除文件之前决定，这样用不用索引输出的格式都一样
+    let with_filename = files.len() > 1 || cfg.paths.iter().any(|p| p.is_dir());
+    let files = match &cfg.index_dir {
+        Some(dir) => narrow_with_index(&cfg, dir, files)?,
+        None => files,
     };
 
-    for line in results {
-        println!("{}", line);
+    // 每个文件作为一个任务交给线程池，结果连同文件的序号一起通过通道发回主线程
+    let search = Arc::new(FileSearch::new(cfg, with_filename)?);
+    if search.cfg.follow {
+        return search.follow(files);
     }
+    let pool = ThreadPool::new(search.cfg.threads.min(files.len()).max(1));
+    let cfg = &search.cfg;
+    // 输出上下文时，不同文件的结果之间也要用 "--" 分隔
+    let separate_files = (search.options.before_context > 0 || search.options.after_context > 0)
+        && !cfg.json
+        && !cfg.count
+        && !cfg.files_with_matches
+        && !cfg.files_without_match;
+    let file_separator = match separate_files {
+        true => {
+            let mut separator = Vec::new();
+            color::paint(&mut separator, &search.colors.separator, b"--")?;
+            separator.push(b'\n');
+            Some(separator)
+        }
+        false => None,
+    };
+    let (sender, receiver) = mpsc::channel();
+    for (index, file) in files.into_iter().enumerate() {
+        let search = Arc::clone(&search);
+        let sender = sender.clone();
+        pool.execute(move || {
+            let name = file.as_ref().map(|path| path.display().to_string()).unwrap_or_default();
+            let result = unwind_as_error(&name, || {
+                let mut out = ChunkWriter { index, sender: &sender, buf: Vec::new() };
+                file.and_then(|path| {
+                    search.file(&path, &mut out).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+                })
+                .and_then(|matched| out.flush().map(|_| matched))
+            });
+            // 接收端已被丢弃时结果也不再需要了，忽略发送失败
+            let _ = sender.send((index, Report::Done(result)));
+        });
+    }
+    // 丢弃主线程持有的发送端，所有任务完成后 receiver 的迭代才会结束
+    drop(sender);
 
-    Ok(())
-}
\ No newline at end of file
+    // 所有结果之后的汇总：--json 的 summary 事件，以及 --stats、--histogram 的统计
+    let totals = |out: &mut dyn Write, stats: &Stats| {
+        if cfg.json {
+            json::summary(out, stats, start.elapsed())?;
+        }
+        if cfg.stats {
+            report::write_stats(out, stats, start.elapsed())?;
+        }
+        if cfg.histogram {
+            report::write_histogram(out, &stats.histogram)?;
+        }
+        Ok(())
+    };
+    let (matched, failures) = match print_in_order(receiver, file_separator.as_deref(), &totals) {
+        Ok(result) => result,
+        // 管道另一端提前关闭（例如 minigrep ... | head）时安静地结束，效果和 grep 被 SIGPIPE 终止一样
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(true),
+        Err(e) => return Err(e.into()),
+    };
+
+    if failures > 0 {
+        return Err(format!("{} file(s) could not be searched", failures).into());
+    }
+    Ok(matched)
+}
+
+// 搜索一个文件时 panic（也就是程序的 bug）不能让这个文件的 Report::Done 丢失：否则 print_in_order 会一直等它，
+// 后面所有文件的输出都被悄悄丢掉。把 panic 当作这个文件搜索失败，和无法读取的文件一样报告，退出码为 2
+fn unwind_as_error<T>(name: &str, search: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(search)).unwrap_or_else(|payload| {
+        let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => "unknown error".to_string(),
+        };
+        Err(io::Error::other(format!("{}: internal error: {}", name, message)))
+    })
+}
+
+// minigrep index：为遍历到的文件建立索引，报告索引了多少文件
+fn build_index(cfg: &Config, files: &[io::Result<PathBuf>]) -> Result<bool, Box<dyn Error>> {
+    let dir = cfg.index_dir.clone().unwrap_or_else(|| PathBuf::from(index::DEFAULT_DIR));
+    let (built, errors) = index::build(&dir, files)?;
+    for e in &errors {
+        eprintln!("minigrep: {}", e);
+    }
+    println!(
+        "indexed {} file(s) into {} ({} unchanged, {} trigrams)",
+        built.files,
+        dir.display(),
+        built.unchanged,
+        built.trigrams
+    );
+    if !errors.is_empty() {
+        return Err(format!("{} file(s) could not be indexed", errors.len()).into());
+    }
+    Ok(built.files > 0)
+}
+
+// --index-dir：从查询中提取每个匹配都必须包含的三元组，排除索引中不包含它们的文件。
+// 正则表达式只有在不含元字符（相当于固定字符串）时才能提取；-v 和 --fuzzy 的匹配不一定包含查询中的文本，不排除任何文件。
+// 不在索引中或者建立索引后被修改过的文件照常搜索，并提醒用户更新索引
+fn narrow_with_index(cfg: &Config, dir: &Path, files: Vec<io::Result<PathBuf>>) -> io::Result<Vec<io::Result<PathBuf>>> {
+    let index = index::Index::load(dir)?;
+    // 索引按 BOM 识别编码；强制指定了其他编码时，索引中记下的三元组和实际搜索的内容对不上。
+    // 字段匹配的是去掉转义后的内容，文件中的原文（例如 JSON 的 \u00e9）也不一定包含查询中的三元组
+    let forced = cfg.encoding.is_some_and(|encoding| encoding != Encoding::Utf8);
+    let fields = cfg.csv_field.is_some() || cfg.json_path.is_some();
+    let literals: Option<Vec<&str>> = match cfg.invert_match || cfg.fuzzy.is_some() || forced || fields {
+        true => None,
+        false => cfg.patterns.iter().map(|p| (!cfg.regex || regex::escape(p) == *p).then_some(p.as_str())).collect(),
+    };
+    let Some(query) = literals.and_then(|literals| index::Query::new(&literals, !cfg.case_sensitive)) else {
+        return Ok(files);
+    };
+    let mut stale = 0;
+    let files = files
+        .into_iter()
+        .filter(|file| match file {
+            Ok(path) if path.as_os_str() != "-" => match index.lookup(path, &query, cfg.search_zip) {
+                index::Lookup::Skip => false,
+                index::Lookup::Search => true,
+                index::Lookup::Stale => {
+                    stale += 1;
+                    true
+                }
+            },
+            _ => true,
+        })
+        .collect();
+    if stale > 0 {
+        eprintln!("minigrep: {} file(s) changed or were added since the index was built; run 'minigrep index' to update it", stale);
+    }
+    Ok(files)
+}
+
+// 按文件的遍历顺序输出：正在输出的文件的结果直接写出，其他文件先完成的结果暂存在 pending 中，轮到它时再打印。
+// 单个文件读取失败不应该中断整棵目录树的搜索：先报告到标准错误，最后由调用者统一返回错误。
+// 输出上下文时文件之间用 file_separator 分隔；所有文件输出完之后，把合计的统计数据交给 totals 输出汇总。
+// 返回是否有选中的行，以及无法搜索的文件个数。
+fn print_in_order(
+    receiver: mpsc::Receiver<(usize, Report)>,
+    file_separator: Option<&[u8]>,
+    totals: &dyn Fn(&mut dyn Write, &Stats) -> io::Result<()>,
+) -> io::Result<(bool, usize)> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut pending: BTreeMap<usize, Vec<Report>> = BTreeMap::new();
+    let mut next = 0;
+    let mut failures = 0;
+    // 是否已经输出过内容，以及当前文件是否已经开始输出，用来决定是否在文件之间插入 "--"
+    let mut printed = false;
+    let mut started = false;
+    let mut matched = false;
+    let mut stats = Stats::default();
+    for (index, report) in receiver {
+        pending.entry(index).or_default().push(report);
+        while let Some(reports) = pending.remove(&next) {
+            let mut done = false;
+            for report in reports {
+                match report {
+                    Report::Output(chunk) => {
+                        if let (Some(separator), true, false) = (file_separator, printed, started) {
+                            stdout.write_all(separator)?;
+                        }
+                        stdout.write_all(&chunk)?;
+                        printed = true;
+                        started = true;
+                    }
+                    Report::Done(Ok((found, file_stats))) => {
+                        matched |= found;
+                        stats.add(&file_stats);
+                        done = true;
+                    }
+                    Report::Done(Err(e)) => {
+                        eprintln!("minigrep: {}", e);
+                        failures += 1;
+                        done = true;
+                    }
+                }
+            }
+            // 这个文件还没有搜索完，等它后续的输出
+            if !done {
+                break;
+            }
+            next += 1;
+            started = false;
+        }
+    }
+    totals(&mut stdout, &stats)?;
+    stdout.flush()?;
+    Ok((matched, failures))
+}
@@ -1,5 +1,24 @@
 // cargo run S Cargo.toml
 // CASE_INSENSITIVE=1 cargo run S Cargo.toml
+// cargo run -- -E "^(name|version) *=" Cargo.toml
+// cargo run -- --include "*.rs" --exclude "main.rs" Config .
+// cargo run -- -j 4 fn src
+// cargo run -- -inw --count config src
+// cargo run -- -b --column fn src/main.rs
+// cargo run -- -n -C 1 Config src
+// cargo run -- -L -m 1 unsafe src; echo $?
+// GREP_COLORS="ms=01;32" cargo run -- --color=always -n fn src | less -R
+// cargo run -- -e TODO -e FIXME -f patterns.txt src
+// echo "line-number = true" > .minigreprc && cargo run -- fn src; cargo run -- --no-config fn src
+// cargo run -- -z ERROR /var/log/syslog.2.gz
+// cargo run -- -n --fuzzy 2 conection /var/log/syslog
+// cargo run -- index /var/log && cargo run -- --index-dir .minigrep-index -i "disk full" /var/log
+// cargo run -- --follow -n ERROR /var/log/syslog
+// cargo run -- -U -n -E 'fn \w+\(\n' src
+// cargo run -- -b ERROR windows-utf16.log; cargo run -- --encoding latin-1 -i café legacy.txt
+// cargo run -- -n --csv-field status 500 access.csv; cargo run -- --json-path error.code -x E42 events.jsonl
+// cargo run -- -o -E '[0-9]+ms' app.log; cargo run -- -E --capture user 'login user=(?P<user>\w+)' auth.log | sort | uniq -c
+// cargo run -- --stats --histogram -c ERROR /var/log
 
 
 /*
@@ -33,9 +52,10 @@ main 函数负责多个任务的组织问题在许多二进制项目中很常见
 */
 
 use std::env;
+use std::io::{self, Write};
 use std::process;
 
-use chry_minigrep::Config;
+use chry_minigrep::{ArgsError, Config};
 
 fn main() {
 
@@ -52,23 +72,42 @@ fn main() {
 
     这里出于简单考虑使用了 std::env::args，因为 OsString 值每个平台都不一样而且比 String 值处理起来更为复杂。
     */
-    println!("运行参数： {:?}", env::args());
+    // 搜索结果也输出到标准输出，调试用的参数打印会混进结果里，所以注释掉
+    // println!("运行参数： {:?}", env::args());
     // 直接使用 env::args 返回的迭代器: 一旦 Config::new 获取了迭代器的所有权并不再使用借用的索引操作，
     // 就可以将迭代器中的 String 值移动到 Config 中，而不是调用 clone 分配新的空间。
     let cfg = Config::new(env::args()).unwrap_or_else(|err| {
+        // --help 和 --version 不是真正的错误：把内容打印到标准输出后正常退出
+        // 用 writeln! 而不是 println!：管道另一端提前关闭（例如 minigrep --help | head）时 println! 会 panic，
+        // 和 run 一样安静地忽略这种错误
+        if let ArgsError::Help | ArgsError::Version = err {
+            match writeln!(io::stdout().lock(), "{}", err) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                    eprintln!("minigrep: {}", e);
+                    process::exit(2);
+                }
+                _ => process::exit(0),
+            }
+        }
         // println! 函数只能够打印到标准输出，标准库提供了 eprintln! 宏来打印到标准错误流
         // cargo run > out.txt 如此依旧能看到错误打印
         eprintln!("Problem parsing arguments: {}", err);
+        eprintln!("Try 'minigrep --help' for more information.");
         // 非零的退出状态是一个惯例信号，用来告诉调用程序的进程：该程序以错误状态退出了
-        process::exit(1);   // std::process::exit 会立即停止程序并将传递给它的数字作为退出状态码。
+        // 和 grep 一样，1 留给“没有找到匹配”，出错用 2
+        process::exit(2);   // std::process::exit 会立即停止程序并将传递给它的数字作为退出状态码。
     });
 
     // println!("Searching for {}", cfg.query);
-    // println!("In file {}", cfg.filename);
+    // println!("In files {:?}", cfg.paths);
 
-    if let Err(e) = chry_minigrep::run(cfg) {
-        eprintln!("Application error: {}", e);
-        // 非零的退出状态是一个惯例信号，用来告诉调用程序的进程：该程序以错误状态退出了
-        process::exit(1);   // std::process::exit 会立即停止程序并将传递给它的数字作为退出状态码。
+    // 退出码：0 找到了匹配，1 没有匹配，2 出错（即使部分文件有匹配，出错也优先）
+    match chry_minigrep::run(cfg) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(2);
+        }
     }
 }
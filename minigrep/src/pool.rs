@@ -0,0 +1,133 @@
+// 搜索用的线程池，设计与 webserver 项目中的 ThreadPool 相同：
+//     ThreadPool 持有通道的发送端，所有 Worker 通过 Arc<Mutex<Receiver>> 共享接收端；
+//     Drop 时先给每个 Worker 发送 Terminate，再逐个 join，保证所有已提交的任务都执行完毕。
+// 和 webserver 不同的是这里的 Worker 不打印任何日志，以免混进搜索结果里；
+// 任务 panic 时 Worker 也不会退出，而是继续执行后面的任务。任务的结果由提交任务的一方负责报告。
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+enum Message {
+    NewJob(Job),
+    Terminate,
+}
+
+/// 固定数量线程的线程池
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: mpsc::Sender<Message>,
+}
+
+impl ThreadPool {
+    /// 创建包含 `size` 个线程的线程池。
+    ///
+    /// # Panics
+    ///
+    /// `new` 函数在 size 为 0 时会 panic。
+    pub fn new(size: usize) -> ThreadPool {
+        assert!(size > 0);
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers = Vec::with_capacity(size);
+        for _ in 0..size {
+            workers.push(Worker::new(Arc::clone(&receiver)));
+        }
+
+        ThreadPool { workers, sender }
+    }
+
+    /// 把任务交给池中空闲的线程执行
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // 只要线程池还存在，Worker 就不会退出，接收端也就不会被关闭，所以 send 不会失败
+        self.sender.send(Message::NewJob(Box::new(f))).unwrap();
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // 必须分成两个循环：先让所有 Worker 都收到终止消息，再去 join，否则可能死锁
+        for _ in &self.workers {
+            self.sender.send(Message::Terminate).unwrap();
+        }
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+struct Worker {
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
+        let thread = thread::spawn(move || loop {
+            // 在 loop 内部获取锁，MutexGuard 在这条 let 语句结束时就被释放，执行 job 期间不会持有锁
+            let message = receiver.lock().unwrap().recv().unwrap();
+            match message {
+                // 一个任务 panic 不应该带走这个线程：否则剩下的任务没人执行，Drop 中的 join 也会失败
+                Message::NewJob(job) => {
+                    let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                }
+                Message::Terminate => break,
+            }
+        });
+
+        Worker { thread: Some(thread) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_every_job_before_drop_returns() {
+        let (tx, rx) = mpsc::channel();
+        {
+            let pool = ThreadPool::new(3);
+            for i in 0..10 {
+                let tx = tx.clone();
+                pool.execute(move || tx.send(i).unwrap());
+            }
+        }
+        drop(tx);
+        let mut results: Vec<i32> = rx.iter().collect();
+        results.sort();
+        assert_eq!((0..10).collect::<Vec<_>>(), results);
+    }
+
+    #[test]
+    fn survives_panicking_jobs() {
+        let (tx, rx) = mpsc::channel();
+        {
+            let pool = ThreadPool::new(2);
+            for i in 0..6 {
+                let tx = tx.clone();
+                pool.execute(move || {
+                    if i % 2 == 0 {
+                        panic!("job {} failed", i);
+                    }
+                    tx.send(i).unwrap();
+                });
+            }
+        }
+        drop(tx);
+        let mut results: Vec<i32> = rx.iter().collect();
+        results.sort();
+        assert_eq!(vec![1, 3, 5], results);
+    }
+}
@@ -0,0 +1,229 @@
+// 配置文件 .minigreprc：持久保存常用选项，例如
+//     # 项目的默认设置
+//     line-number = true
+//     context = 2
+//     color = "always"
+//     exclude = ["target/*", "*.lock"]
+// 格式是 TOML 的一个子集，也可以写成不带引号的简单 key=value。键是 SETTINGS 中的长选项的名字（'_' 等同于 '-'），
+// 每一行都被转换成等价的命令行参数：true 变成 --key，false 被忽略，其他值变成 --key=value，数组中的每个元素各一个。
+// 配置文件中的参数排在命令行参数之前解析，因此命令行中的同一选项总是会覆盖它们。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::args::ArgsError;
+
+/// 配置文件的文件名
+pub(crate) const FILE_NAME: &str = ".minigreprc";
+
+// 可以写在配置文件中的设置：只有改变搜索和输出细节、并且能在命令行中改回来的选项。其中的开关都有对应的 --no-*
+// （例如 --no-word-regexp、--no-max-count），其他选项再给一次就会覆盖。模式（regexp、file）、改变匹配或输出方式的选项
+// （regex、multiline、index-dir、stats 等）、改写文件的选项以及 help、version 写进配置文件会让每一次运行都变成另一个命令，
+// 而命令行中又无法关掉它们：例如 regexp 会让命令行中的 QUERY 被当作路径，stats 会让命令行中的 --json 报告冲突
+const SETTINGS: [&str; 19] = [
+    "ignore-case",
+    "case-sensitive",
+    "line-number",
+    "byte-offset",
+    "column",
+    "after-context",
+    "before-context",
+    "context",
+    "max-count",
+    "word-regexp",
+    "line-regexp",
+    "text",
+    "search-zip",
+    "encoding",
+    "color",
+    "colour",
+    "include",
+    "exclude",
+    "threads",
+];
+
+/// 配置文件中的一行转换成的命令行参数，以及这一行的位置（“文件名:行号”），用于错误信息
+pub(crate) type Source = (Option<String>, Vec<String>);
+
+/// 用户主目录：Unix 上为 $HOME，Windows 上为 %USERPROFILE%
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+/// 按优先级从低到高返回要读取的配置文件：先是主目录中的个人设置，
+/// 再是从当前目录向上查找到的第一个配置文件（项目设置）。同一个文件只读一次。
+pub(crate) fn discover(cwd: Option<&Path>, home: Option<&Path>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let personal = home.map(|home| home.join(FILE_NAME)).filter(|file| file.is_file());
+    let project = cwd.and_then(|cwd| cwd.ancestors().map(|dir| dir.join(FILE_NAME)).find(|file| file.is_file()));
+    files.extend(personal.clone());
+    files.extend(project.filter(|project| Some(project) != personal.as_ref()));
+    files
+}
+
+/// 读取配置文件并转换成命令行参数
+pub(crate) fn load(path: &Path) -> Result<Vec<Source>, ArgsError> {
+    let name = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|e| ArgsError::ConfigFile(name.clone(), e.to_string()))?;
+    parse(&text).map_err(|(line, message)| ArgsError::ConfigFile(format!("{}:{}", name, line), message)).map(|lines| {
+        lines.into_iter().map(|(line, args)| (Some(format!("{}:{}", name, line)), args)).collect()
+    })
+}
+
+// 一个设置所在的行号和对应的参数
+type Setting = (usize, Vec<String>);
+
+// 解析配置文件的内容；出错时返回 (行号, 错误信息)
+fn parse(text: &str) -> Result<Vec<Setting>, (usize, String)> {
+    let mut settings = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err((number, "tables are not supported".to_string()));
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| (number, format!("expected 'key = value', found '{}'", line)))?;
+        let key = key.trim().replace('_', "-");
+        if key.is_empty() || key.starts_with('-') {
+            return Err((number, format!("invalid key '{}'", key)));
+        }
+        if !SETTINGS.contains(&key.as_str()) {
+            return Err((number, format!("'{}' can't be set in a config file", key)));
+        }
+        let option = format!("--{}", key);
+        let args = match parse_value(value.trim()).map_err(|message| (number, message))? {
+            Value::Bool(true) => vec![option],
+            Value::Bool(false) => continue,
+            Value::List(values) => values.into_iter().map(|value| format!("{}={}", option, value)).collect(),
+        };
+        settings.push((number, args));
+    }
+    Ok(settings)
+}
+
+enum Value {
+    Bool(bool),
+    // 单个值也当作只有一个元素的列表
+    List(Vec<String>),
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let (value, rest) = match text {
+        "" => return Err("missing value".to_string()),
+        _ if text.starts_with('[') => {
+            let mut values = Vec::new();
+            let mut rest = text[1..].trim_start();
+            loop {
+                if let Some(after) = rest.strip_prefix(']') {
+                    break (Value::List(values), after);
+                }
+                let (value, after) = scalar(rest, ",]")?;
+                values.push(value);
+                rest = after.trim_start();
+                if let Some(after) = rest.strip_prefix(',') {
+                    rest = after.trim_start();
+                } else if !rest.starts_with(']') {
+                    return Err("expected ',' or ']' in array".to_string());
+                }
+            }
+        }
+        _ => {
+            let (value, rest) = scalar(text, "")?;
+            match value.as_str() {
+                // 只有不带引号的 true / false 是布尔值
+                "true" | "false" if !text.starts_with(['"', '\'']) => (Value::Bool(value == "true"), rest),
+                _ => (Value::List(vec![value]), rest),
+            }
+        }
+    };
+    match rest.trim_start() {
+        rest if rest.is_empty() || rest.starts_with('#') => Ok(value),
+        rest => Err(format!("unexpected '{}' after value", rest)),
+    }
+}
+
+// 解析一个值：带转义的 "..."、原样的 '...'，或者直到注释（或 stop 中的字符）为止的不带引号的文本。返回值和剩余的部分
+fn scalar<'t>(text: &'t str, stop: &str) -> Result<(String, &'t str), String> {
+    if let Some(literal) = text.strip_prefix('\'') {
+        let end = literal.find('\'').ok_or("unterminated string")?;
+        return Ok((literal[..end].to_string(), &literal[end + 1..]));
+    }
+    if let Some(quoted) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((value, &quoted[i + 1..])),
+                '\\' => value.push(match chars.next().map(|(_, c)| c) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some(c @ ('"' | '\\')) => c,
+                    _ => return Err("invalid escape in string".to_string()),
+                }),
+                c => value.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+    let end = text.find(|c| c == '#' || stop.contains(c)).unwrap_or(text.len());
+    match text[..end].trim() {
+        "" => Err("missing value".to_string()),
+        value => Ok((value.to_string(), &text[end..])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempDir;
+
+    #[test]
+    fn converts_settings_to_arguments() {
+        let text = "# 注释\n\
+                    line_number = true\n\
+                    column = false\n\
+                    context = 2  # 前后各两行\n\
+                    color = \"always\"\n\
+                    exclude = ['target/*', \"a,b\" , *.lock]\n\
+                    include=*.rs\n";
+        let args: Vec<Vec<String>> = parse(text).unwrap().into_iter().map(|(_, args)| args).collect();
+        assert_eq!(
+            vec![
+                vec!["--line-number"],
+                vec!["--context=2"],
+                vec!["--color=always"],
+                vec!["--exclude=target/*", "--exclude=a,b", "--exclude=*.lock"],
+                vec!["--include=*.rs"],
+            ],
+            args
+        );
+
+        assert_eq!(2, parse("\n[search]\n").unwrap_err().0);
+        // 模式、帮助、改写文件以及改变匹配或输出方式的选项不能写在配置文件中
+        for key in ["regexp", "file", "help", "version", "no-config", "in-place", "replace", "regex", "multiline", "index-dir", "stats"] {
+            assert_eq!(2, parse(&format!("context = 1\n{} = \"x\"", key)).unwrap_err().0, "{}", key);
+        }
+        assert_eq!(1, parse("context 2").unwrap_err().0);
+        assert_eq!(3, parse("context = 1\nmax_count = 2\ncolor = \"x").unwrap_err().0);
+        assert!(parse("color = \"never\" extra").is_err());
+    }
+
+    #[test]
+    fn discovers_personal_then_nearest_project_file() {
+        let dir = TempDir::new("rc");
+        let root = dir.path();
+        let (home, nested) = (root.join("home"), root.join("project/src/deep"));
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(home.join(FILE_NAME), "").unwrap();
+        fs::write(root.join("project").join(FILE_NAME), "").unwrap();
+
+        let files = discover(Some(&nested), Some(&home));
+        assert_eq!(vec![home.join(FILE_NAME), root.join("project").join(FILE_NAME)], files);
+        // 在主目录中运行时，主目录中的配置文件只读一次
+        assert_eq!(vec![home.join(FILE_NAME)], discover(Some(&home), Some(&home)));
+    }
+}
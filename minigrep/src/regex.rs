@@ -0,0 +1,1066 @@
+// grep 是 “Globally search a Regular Expression and Print.” 的首字母缩写，
+// 而最初的 minigrep 只会做子串匹配。这里实现一个不依赖第三方 crate 的小型正则表达式引擎：
+//     1. 解析(parse)：把模式字符串解析成语法树 Node
+//     2. 编译(compile)：把语法树编译成一组虚拟机指令 Inst
+//     3. 执行：用 Pike VM（Thompson NFA 的一种模拟方式）同时推进所有可能的匹配线程，
+//        时间复杂度与 “输入长度 × 程序长度” 成正比，不会像回溯引擎那样出现指数级爆炸。
+//
+// 支持的语法：
+//     字符类  [abc] [^a-z] [[:alpha:]] \d \D \w \W \s \S .
+//     锚点    ^ $ \b \B
+//     分组    (...) (?:...) (?P<name>...) (?<name>...)
+//     选择    a|b
+//     重复    * + ? {n} {n,} {n,m}，后面加 ? 表示非贪婪
+//     转义    \t \n \r \f \v \0 \xHH \x{HHHH} 以及任何标点符号
+
+use std::cell::RefCell;
+use std::error;
+use std::fmt;
+
+use crate::casefold;
+use crate::utf8;
+
+// 防止 a{1000}{1000} 之类的模式编译出巨大的程序
+const MAX_REPEAT: u32 = 1000;
+const MAX_PROGRAM: usize = 100_000;
+
+/// 正则表达式语法错误的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnclosedGroup,
+    UnmatchedParen,
+    UnclosedClass,
+    InvalidRange,
+    NothingToRepeat,
+    InvalidRepetition,
+    TooLarge,
+    TrailingBackslash,
+    UnknownEscape,
+    InvalidGroupName,
+    DuplicateGroupName,
+    UnknownPosixClass,
+}
+
+impl ErrorKind {
+    /// 错误的文字描述
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::UnclosedGroup => "invalid regex: unclosed group '('",
+            ErrorKind::UnmatchedParen => "invalid regex: unmatched ')'",
+            ErrorKind::UnclosedClass => "invalid regex: unclosed character class '['",
+            ErrorKind::InvalidRange => "invalid regex: invalid character class range",
+            ErrorKind::NothingToRepeat => "invalid regex: repetition operator has nothing to repeat",
+            ErrorKind::InvalidRepetition => "invalid regex: invalid repetition count",
+            ErrorKind::TooLarge => "invalid regex: pattern is too large",
+            ErrorKind::TrailingBackslash => "invalid regex: trailing backslash",
+            ErrorKind::UnknownEscape => "invalid regex: unknown escape sequence",
+            ErrorKind::InvalidGroupName => "invalid regex: invalid capture group name",
+            ErrorKind::DuplicateGroupName => "invalid regex: duplicate capture group name",
+            ErrorKind::UnknownPosixClass => "invalid regex: unknown POSIX character class",
+        }
+    }
+}
+
+/// 解析正则表达式时产生的错误，记录了错误种类和出错的字符位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    pos: usize,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// 出错位置（以字符计，从 0 开始）
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.kind.as_str(), self.pos)
+    }
+}
+
+impl error::Error for Error {}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// 构建正则表达式时的选项
+#[derive(Debug, Clone, Default)]
+pub struct RegexOptions {
+    /// 大小写不敏感
+    pub case_insensitive: bool,
+    /// 匹配的前后都不能是单词字符（grep 的 -w）
+    pub whole_word: bool,
+    /// 匹配必须是整个输入（grep 的 -x）
+    pub whole_line: bool,
+    /// 输入可能包含多行（grep 的 -U）：`^`、`$` 以及 `whole_line` 改为匹配每一行的行首和行尾
+    pub multi_line: bool,
+}
+
+/// 转义 `text` 中的所有元字符，得到按字面意义匹配 `text` 的模式
+///
+/// ```
+/// assert_eq!(r"a\.b\*", chry_minigrep::regex::escape("a.b*"));
+/// ```
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// ========================================================================
+// 语法树
+// ========================================================================
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Look {
+    Start,           // ^
+    End,             // $
+    WordBoundary,    // \b
+    NotWordBoundary, // \B
+    // 没有对应的语法，由 RegexOptions::whole_word 加在整个模式的前后
+    NoWordBefore,
+    NoWordAfter,
+    // RegexOptions::multi_line 时 ^ 和 $ 编译成这两个
+    LineStart,
+    LineEnd,
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    // \d \w \s 以及 [:alpha:] 这类由函数判定的字符集，bool 表示是否取反
+    Pred(fn(char) -> bool, bool),
+}
+
+impl ClassItem {
+    fn contains(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Range(lo, hi) => lo <= c && c <= hi,
+            ClassItem::Pred(f, negated) => f(c) != negated,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+    fold: bool, // 大小写不敏感时，同时尝试字符的大小写变体
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let contains = |c: char| self.items.iter().any(|item| item.contains(c));
+        let hit = contains(c) || (self.fold && case_variants(c).any(contains));
+        hit != self.negated
+    }
+}
+
+// 一个字符的简单大小写变体（只考虑一对一映射的情况）
+fn case_variants(c: char) -> impl Iterator<Item = char> {
+    let lower = c.to_lowercase();
+    let upper = c.to_uppercase();
+    let lower = if lower.len() == 1 { lower.last() } else { None };
+    let upper = if upper.len() == 1 { upper.last() } else { None };
+    lower.into_iter().chain(upper).filter(move |&v| v != c)
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Look(Look),
+    Group(Box<Node>, Option<usize>), // 捕获组的序号，None 为非捕获组
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+// 转义序列的解析结果
+enum Escape {
+    Char(char),
+    Pred(fn(char) -> bool, bool),
+    Look(Look),
+}
+
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit()
+}
+
+fn is_space(c: char) -> bool {
+    c.is_whitespace()
+}
+
+fn posix_class(name: &str) -> Option<fn(char) -> bool> {
+    let f: fn(char) -> bool = match name {
+        "alpha" => char::is_alphabetic,
+        "digit" => is_digit,
+        "alnum" => char::is_alphanumeric,
+        "upper" => char::is_uppercase,
+        "lower" => char::is_lowercase,
+        "space" => is_space,
+        "blank" => |c| c == ' ' || c == '\t',
+        "punct" => |c| c.is_ascii_punctuation(),
+        "xdigit" => |c| c.is_ascii_hexdigit(),
+        "cntrl" => char::is_control,
+        "word" => utf8::is_word_char,
+        _ => return None,
+    };
+    Some(f)
+}
+
+// ========================================================================
+// 解析
+// ========================================================================
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    names: Vec<Option<String>>, // 下标即捕获组序号，0 号组代表整个匹配
+}
+
+impl Parser {
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error { kind, pos: self.pos }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn parse(mut self) -> Result<(Node, Vec<Option<String>>)> {
+        let node = self.parse_alternate()?;
+        if self.pos < self.chars.len() {
+            // parse_alternate 只会在遇到多余的 ')' 时提前返回
+            return Err(self.error(ErrorKind::UnmatchedParen));
+        }
+        Ok((node, self.names))
+    }
+
+    fn parse_alternate(&mut self) -> Result<Node> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alternate(branches) })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            items.push(self.parse_repeat(atom)?);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.pop().unwrap(),
+            _ => Node::Concat(items),
+        })
+    }
+
+    fn parse_atom(&mut self) -> Result<Node> {
+        let start = self.pos;
+        let c = self.bump().unwrap();
+        Ok(match c {
+            '(' => self.parse_group(start)?,
+            '[' => Node::Class(self.parse_class(start)?),
+            '.' => Node::Any,
+            '^' => Node::Look(Look::Start),
+            '$' => Node::Look(Look::End),
+            '*' | '+' | '?' => {
+                self.pos = start;
+                return Err(self.error(ErrorKind::NothingToRepeat));
+            }
+            '\\' => match self.parse_escape()? {
+                Escape::Char(c) => Node::Char(c),
+                Escape::Pred(f, negated) => Node::Class(Class {
+                    items: vec![ClassItem::Pred(f, negated)],
+                    negated: false,
+                    fold: false,
+                }),
+                Escape::Look(look) => Node::Look(look),
+            },
+            c => Node::Char(c),
+        })
+    }
+
+    fn parse_group(&mut self, start: usize) -> Result<Node> {
+        let index = if self.eat('?') {
+            if self.eat(':') {
+                None
+            } else if self.eat('<') || (self.eat('P') && self.eat('<')) {
+                let name = self.parse_group_name()?;
+                self.names.push(Some(name));
+                Some(self.names.len() - 1)
+            } else {
+                return Err(self.error(ErrorKind::InvalidGroupName));
+            }
+        } else {
+            self.names.push(None);
+            Some(self.names.len() - 1)
+        };
+
+        let node = self.parse_alternate()?;
+        if !self.eat(')') {
+            return Err(Error { kind: ErrorKind::UnclosedGroup, pos: start });
+        }
+        Ok(Node::Group(Box::new(node), index))
+    }
+
+    fn parse_group_name(&mut self) -> Result<String> {
+        let start = self.pos;
+        let mut name = String::new();
+        loop {
+            match self.bump() {
+                Some('>') => break,
+                Some(c) if c.is_alphanumeric() || c == '_' => name.push(c),
+                _ => return Err(Error { kind: ErrorKind::InvalidGroupName, pos: start }),
+            }
+        }
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(Error { kind: ErrorKind::InvalidGroupName, pos: start });
+        }
+        if self.names.iter().any(|n| n.as_deref() == Some(name.as_str())) {
+            return Err(Error { kind: ErrorKind::DuplicateGroupName, pos: start });
+        }
+        Ok(name)
+    }
+
+    fn parse_repeat(&mut self, mut atom: Node) -> Result<Node> {
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.parse_counted()? {
+                    Some(range) => range,
+                    None => break, // 不是合法的计数重复，把 '{' 当作普通字符
+                },
+                _ => break,
+            };
+            if self.pos == start {
+                self.pos += 1;
+            }
+            if let Node::Look(_) | Node::Empty = atom {
+                self.pos = start;
+                return Err(self.error(ErrorKind::NothingToRepeat));
+            }
+            let greedy = !self.eat('?');
+            atom = Node::Repeat { node: Box::new(atom), min, max, greedy };
+        }
+        Ok(atom)
+    }
+
+    // 解析 {n} {n,} {n,m}；如果后面不是这种形式则不消耗任何字符并返回 None
+    fn parse_counted(&mut self) -> Result<Option<(u32, Option<u32>)>> {
+        let start = self.pos;
+        self.pos += 1;
+        let min = self.parse_number();
+        // {n,} 的上限为空，表示不限次数
+        let max = if self.eat(',') { self.parse_number() } else { min };
+        let min = match min {
+            Some(min) if self.eat('}') => min,
+            _ => {
+                self.pos = start;
+                return Ok(None);
+            }
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(Error { kind: ErrorKind::InvalidRepetition, pos: start });
+        }
+        if min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT) {
+            return Err(Error { kind: ErrorKind::TooLarge, pos: start });
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        // 数字过长时返回一个超出上限的值，交给调用者报告 TooLarge
+        if digits.is_empty() { None } else { Some(digits.parse().unwrap_or(u32::MAX)) }
+    }
+
+    fn parse_escape(&mut self) -> Result<Escape> {
+        let start = self.pos - 1;
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Err(Error { kind: ErrorKind::TrailingBackslash, pos: start }),
+        };
+        Ok(match c {
+            'd' => Escape::Pred(is_digit, false),
+            'D' => Escape::Pred(is_digit, true),
+            'w' => Escape::Pred(utf8::is_word_char, false),
+            'W' => Escape::Pred(utf8::is_word_char, true),
+            's' => Escape::Pred(is_space, false),
+            'S' => Escape::Pred(is_space, true),
+            'b' => Escape::Look(Look::WordBoundary),
+            'B' => Escape::Look(Look::NotWordBoundary),
+            'n' => Escape::Char('\n'),
+            't' => Escape::Char('\t'),
+            'r' => Escape::Char('\r'),
+            'f' => Escape::Char('\x0C'),
+            'v' => Escape::Char('\x0B'),
+            '0' => Escape::Char('\0'),
+            'x' => Escape::Char(self.parse_hex(start)?),
+            c if !c.is_alphanumeric() => Escape::Char(c),
+            _ => return Err(Error { kind: ErrorKind::UnknownEscape, pos: start }),
+        })
+    }
+
+    // \xHH 或 \x{H...}
+    fn parse_hex(&mut self, start: usize) -> Result<char> {
+        let err = Error { kind: ErrorKind::UnknownEscape, pos: start };
+        let digits: String = if self.eat('{') {
+            let mut s = String::new();
+            loop {
+                match self.bump() {
+                    Some('}') => break,
+                    Some(c) => s.push(c),
+                    None => return Err(err),
+                }
+            }
+            s
+        } else {
+            (0..2).filter_map(|_| self.bump()).collect()
+        };
+        u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32).ok_or(err)
+    }
+
+    fn parse_class(&mut self, start: usize) -> Result<Class> {
+        let unclosed = Error { kind: ErrorKind::UnclosedClass, pos: start };
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.bump().ok_or_else(|| unclosed.clone())?;
+            let lo = match c {
+                ']' if !first => break,
+                '[' if self.peek() == Some(':') => {
+                    items.push(self.parse_posix_class()?);
+                    first = false;
+                    continue;
+                }
+                '\\' => match self.parse_escape()? {
+                    Escape::Char(c) => c,
+                    Escape::Pred(f, negated) => {
+                        items.push(ClassItem::Pred(f, negated));
+                        first = false;
+                        continue;
+                    }
+                    Escape::Look(_) => return Err(Error { kind: ErrorKind::UnknownEscape, pos: self.pos - 2 }),
+                },
+                c => c,
+            };
+            first = false;
+
+            // 形如 a-z 的区间；'-' 出现在结尾时按普通字符处理
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                let range_pos = self.pos;
+                self.pos += 1;
+                let hi = match self.bump().ok_or_else(|| unclosed.clone())? {
+                    '\\' => match self.parse_escape()? {
+                        Escape::Char(c) => c,
+                        _ => return Err(Error { kind: ErrorKind::InvalidRange, pos: range_pos }),
+                    },
+                    c => c,
+                };
+                if hi < lo {
+                    return Err(Error { kind: ErrorKind::InvalidRange, pos: range_pos });
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(ClassItem::Range(lo, lo));
+            }
+        }
+        Ok(Class { items, negated, fold: false })
+    }
+
+    // 已经消耗了 '['，当前位于 ':'
+    fn parse_posix_class(&mut self) -> Result<ClassItem> {
+        let start = self.pos - 1;
+        self.pos += 1;
+        let negated = self.eat('^');
+        let mut name = String::new();
+        while let Some(c) = self.bump() {
+            if c == ':' {
+                break;
+            }
+            name.push(c);
+        }
+        if !self.eat(']') {
+            return Err(Error { kind: ErrorKind::UnknownPosixClass, pos: start });
+        }
+        match posix_class(&name) {
+            Some(f) => Ok(ClassItem::Pred(f, negated)),
+            None => Err(Error { kind: ErrorKind::UnknownPosixClass, pos: start }),
+        }
+    }
+}
+
+// ========================================================================
+// 编译
+// ========================================================================
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    FoldChar(char), // 大小写不敏感时的字面字符，保存折叠后的结果
+    Class(Class),
+    Any, // 除换行以外的任意字符
+    Look(Look),
+    Split(usize, usize), // 优先尝试第一个分支
+    Jmp(usize),
+    Save(usize), // 记录当前位置到捕获槽
+    Match,
+}
+
+struct Compiler {
+    prog: Vec<Inst>,
+    fold: bool,
+    multi_line: bool,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize> {
+        if self.prog.len() >= MAX_PROGRAM {
+            return Err(Error { kind: ErrorKind::TooLarge, pos: 0 });
+        }
+        self.prog.push(inst);
+        Ok(self.prog.len() - 1)
+    }
+
+    fn look(&mut self, look: Look) -> Result<usize> {
+        let look = match (look, self.multi_line) {
+            (Look::Start, true) => Look::LineStart,
+            (Look::End, true) => Look::LineEnd,
+            (look, _) => look,
+        };
+        self.push(Inst::Look(look))
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<()> {
+        match node {
+            Node::Empty => {}
+            Node::Char(c) => {
+                // 比较折叠后的字符，这样 'ẞ' 和 'ß'、'ſ' 和 's' 这类没有一对一大小写关系的字符也能匹配
+                if self.fold {
+                    self.push(Inst::FoldChar(casefold::fold(*c)))?;
+                } else {
+                    self.push(Inst::Char(*c))?;
+                }
+            }
+            Node::Any => {
+                self.push(Inst::Any)?;
+            }
+            Node::Class(class) => {
+                let mut class = class.clone();
+                class.fold = self.fold;
+                self.push(Inst::Class(class))?;
+            }
+            Node::Look(look) => {
+                self.look(*look)?;
+            }
+            Node::Group(node, index) => match index {
+                Some(i) => {
+                    self.push(Inst::Save(2 * i))?;
+                    self.compile(node)?;
+                    self.push(Inst::Save(2 * i + 1))?;
+                }
+                None => self.compile(node)?,
+            },
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.compile(node)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 == branches.len() {
+                        self.compile(branch)?;
+                    } else {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.compile(branch)?;
+                        jumps.push(self.push(Inst::Jmp(0))?);
+                        self.prog[split] = Inst::Split(split + 1, self.prog.len());
+                    }
+                }
+                let end = self.prog.len();
+                for jump in jumps {
+                    self.prog[jump] = Inst::Jmp(end);
+                }
+            }
+            Node::Repeat { node, min, max, greedy } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    // x* => L: Split(body, out); body; Jmp(L)
+                    None => {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.compile(node)?;
+                        self.push(Inst::Jmp(split))?;
+                        let out = self.prog.len();
+                        self.prog[split] = self.split(split + 1, out, *greedy);
+                    }
+                    // x{n,m} => 必须的 n 个之后，再跟 m-n 个可选的 x
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.push(Inst::Split(0, 0))?);
+                            self.compile(node)?;
+                        }
+                        let out = self.prog.len();
+                        for split in splits {
+                            self.prog[split] = self.split(split + 1, out, *greedy);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn split(&self, body: usize, out: usize, greedy: bool) -> Inst {
+        if greedy { Inst::Split(body, out) } else { Inst::Split(out, body) }
+    }
+}
+
+// ========================================================================
+// 执行：Pike VM
+// ========================================================================
+
+// 稀疏集合：O(1) 地判断某条指令是否已经在线程列表中，且清空也是 O(1)。
+// 所有线程的捕获位置放在同一块内存中：第 i 个线程的是 slots[i * n..(i + 1) * n]，n 为每个线程记录的位置数
+#[derive(Default)]
+struct Threads {
+    sparse: Vec<usize>,
+    dense: Vec<usize>,
+    slots: Vec<Option<usize>>,
+}
+
+impl Threads {
+    // 清空列表，并准备好容纳 len 条指令、每个线程 n 个捕获位置；已经分配的内存会被复用
+    fn reset(&mut self, len: usize, n: usize) {
+        self.sparse.resize(len, 0);
+        self.dense.clear();
+        self.slots.resize(len * n, None);
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        let i = self.sparse[pc];
+        i < self.dense.len() && self.dense[i] == pc
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+    }
+}
+
+// 一次搜索用到的全部工作内存。每个线程一份，反复搜索时不必每次都重新分配
+#[derive(Default)]
+struct Scratch {
+    clist: Threads,
+    nlist: Threads,
+    stack: Vec<Frame>,
+    caps: Vec<Option<usize>>,
+    // 找到的匹配的捕获位置
+    found: Vec<Option<usize>>,
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
+
+enum Frame {
+    Explore(usize),
+    Restore(usize, Option<usize>),
+}
+
+/// 一次匹配中各个捕获组的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures {
+    slots: Vec<Option<usize>>,
+}
+
+impl Captures {
+    // 只有 0 号组的匹配，供不使用正则表达式的查找方式和捕获组共用同一套替换逻辑
+    pub(crate) fn from_match(start: usize, end: usize) -> Captures {
+        Captures { slots: vec![Some(start), Some(end)] }
+    }
+
+    /// 第 `i` 个捕获组匹配到的字节区间 `(start, end)`，0 号组为整个匹配
+    pub fn get(&self, i: usize) -> Option<(usize, usize)> {
+        match (self.slots.get(2 * i)?, self.slots.get(2 * i + 1)?) {
+            (Some(start), Some(end)) => Some((*start, *end)),
+            _ => None,
+        }
+    }
+
+    /// 捕获组的数量（包括 0 号组）
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+/// 编译好的正则表达式
+///
+/// # Examples
+///
+/// ```
+/// use chry_minigrep::regex::Regex;
+///
+/// let re = Regex::new(r"^\d{4}-(\d{2})").unwrap();
+/// assert!(re.is_match(b"2021-03-01 started"));
+/// assert_eq!(Some((0, 7)), re.find_at(b"2021-03-01", 0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Regex {
+    prog: Vec<Inst>,
+    names: Vec<Option<String>>,
+    anchored: bool, // 模式以 ^ 开头，只可能在开头匹配
+}
+
+impl Regex {
+    /// 编译正则表达式
+    pub fn new(pattern: &str) -> Result<Regex> {
+        Regex::with_options(pattern, &RegexOptions::default())
+    }
+
+    /// 使用指定的选项编译正则表达式
+    pub fn with_options(pattern: &str, options: &RegexOptions) -> Result<Regex> {
+        let parser = Parser { chars: pattern.chars().collect(), pos: 0, names: vec![None] };
+        let (node, names) = parser.parse()?;
+
+        let mut compiler = Compiler { prog: Vec::new(), fold: options.case_insensitive, multi_line: options.multi_line };
+        compiler.push(Inst::Save(0))?;
+        if options.whole_line {
+            compiler.look(Look::Start)?;
+        }
+        if options.whole_word {
+            compiler.push(Inst::Look(Look::NoWordBefore))?;
+        }
+        compiler.compile(&node)?;
+        if options.whole_word {
+            compiler.push(Inst::Look(Look::NoWordAfter))?;
+        }
+        if options.whole_line {
+            compiler.look(Look::End)?;
+        }
+        compiler.push(Inst::Save(1))?;
+        compiler.push(Inst::Match)?;
+
+        let anchored = matches!(compiler.prog.get(1), Some(Inst::Look(Look::Start)));
+        Ok(Regex { prog: compiler.prog, names, anchored })
+    }
+
+    /// 捕获组的数量（包括代表整个匹配的 0 号组）
+    pub fn captures_len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// 按名字查找捕获组的序号
+    pub fn capture_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n.as_deref() == Some(name))
+    }
+
+    /// 判断 `haystack` 中是否存在匹配
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    /// 从 `start` 开始查找最左边的匹配，返回其字节区间 `(start, end)`
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        self.find(haystack, start, false)
+    }
+
+    /// 从 `start` 开始查找最左边的匹配，并返回所有捕获组的位置
+    pub fn captures_at(&self, haystack: &[u8], start: usize) -> Option<Captures> {
+        self.captures(haystack, start, false)
+    }
+
+    // 和 captures_at 相同，但不接受从 start 开始的空匹配。`|a` 在 "a" 前面先得到空匹配，
+    // 逐个查找所有匹配时用它找出同一位置上非空的 "a"
+    pub(crate) fn captures_nonempty_at(&self, haystack: &[u8], start: usize) -> Option<Captures> {
+        self.captures(haystack, start, true)
+    }
+
+    // 只需要整个匹配的区间时只记录 0 号组的两个位置，其他捕获组的 Save 指令直接跳过
+    fn find(&self, haystack: &[u8], start: usize, not_empty: bool) -> Option<(usize, usize)> {
+        SCRATCH.with(|scratch| {
+            let scratch = &mut *scratch.borrow_mut();
+            match self.run(scratch, haystack, start, not_empty, 2) {
+                true => Some((scratch.found[0]?, scratch.found[1]?)),
+                false => None,
+            }
+        })
+    }
+
+    fn captures(&self, haystack: &[u8], start: usize, not_empty: bool) -> Option<Captures> {
+        SCRATCH.with(|scratch| {
+            let scratch = &mut *scratch.borrow_mut();
+            match self.run(scratch, haystack, start, not_empty, 2 * self.names.len()) {
+                true => Some(Captures { slots: scratch.found.clone() }),
+                false => None,
+            }
+        })
+    }
+
+    // 执行 Pike VM，每个线程记录前 n 个捕获位置。找到匹配时返回 true，捕获位置在 scratch.found 中
+    fn run(&self, scratch: &mut Scratch, haystack: &[u8], start: usize, not_empty: bool, n: usize) -> bool {
+        let Scratch { clist, nlist, stack, caps, found } = scratch;
+        clist.reset(self.prog.len(), n);
+        nlist.reset(self.prog.len(), n);
+        caps.clear();
+        caps.resize(n, None);
+        let mut matched = false;
+        let mut pos = start;
+
+        loop {
+            // 还没有找到匹配时，在当前位置开启一个新线程（优先级最低）
+            if !matched && (!self.anchored || pos == 0) {
+                caps.iter_mut().for_each(|slot| *slot = None);
+                self.add_thread(clist, stack, caps, 0, haystack, pos);
+            }
+            if clist.dense.is_empty() {
+                break;
+            }
+
+            let next = utf8::decode(haystack, pos);
+            for i in 0..clist.dense.len() {
+                let pc = clist.dense[i];
+                let hit = match (&self.prog[pc], next) {
+                    // 被拒绝的空匹配不结束搜索，优先级更低的线程继续
+                    (Inst::Match, _) if not_empty && pos == start => false,
+                    (Inst::Match, _) => {
+                        found.clear();
+                        found.extend_from_slice(&clist.slots[i * n..(i + 1) * n]);
+                        matched = true;
+                        // 更低优先级的线程不必再继续：leftmost-first 语义
+                        break;
+                    }
+                    (Inst::Char(expected), Some((c, _))) => *expected == c,
+                    (Inst::FoldChar(expected), Some((c, _))) => *expected == casefold::fold(c),
+                    (Inst::Class(class), Some((c, _))) => class.matches(c),
+                    (Inst::Any, Some((c, _))) => c != '\n',
+                    _ => false,
+                };
+                if hit {
+                    let len = next.unwrap().1;
+                    caps.copy_from_slice(&clist.slots[i * n..(i + 1) * n]);
+                    self.add_thread(nlist, stack, caps, pc + 1, haystack, pos + len);
+                }
+            }
+
+            match next {
+                Some((_, len)) => pos += len,
+                None => break,
+            }
+            std::mem::swap(clist, nlist);
+            nlist.clear();
+        }
+
+        matched
+    }
+
+    // 沿着 Jmp / Split / Save / Look 这些不消耗字符的指令展开，把能停在“消耗字符的指令”上的线程加入列表。
+    // 使用显式栈代替递归，避免模式较大时栈溢出。
+    fn add_thread(
+        &self,
+        list: &mut Threads,
+        stack: &mut Vec<Frame>,
+        caps: &mut [Option<usize>],
+        pc: usize,
+        haystack: &[u8],
+        pos: usize,
+    ) {
+        stack.push(Frame::Explore(pc));
+        while let Some(frame) = stack.pop() {
+            let pc = match frame {
+                Frame::Explore(pc) => pc,
+                Frame::Restore(slot, old) => {
+                    caps[slot] = old;
+                    continue;
+                }
+            };
+            if list.contains(pc) {
+                continue;
+            }
+            list.sparse[pc] = list.dense.len();
+            list.dense.push(pc);
+
+            match self.prog[pc] {
+                Inst::Jmp(target) => stack.push(Frame::Explore(target)),
+                Inst::Split(first, second) => {
+                    stack.push(Frame::Explore(second));
+                    stack.push(Frame::Explore(first));
+                }
+                // 没有记录这个位置（只要整个匹配的区间时）
+                Inst::Save(slot) if slot >= caps.len() => stack.push(Frame::Explore(pc + 1)),
+                Inst::Save(slot) => {
+                    stack.push(Frame::Restore(slot, caps[slot]));
+                    caps[slot] = Some(pos);
+                    stack.push(Frame::Explore(pc + 1));
+                }
+                Inst::Look(look) => {
+                    if look_matches(look, haystack, pos) {
+                        stack.push(Frame::Explore(pc + 1));
+                    }
+                }
+                _ => {
+                    let i = list.dense.len() - 1;
+                    list.slots[i * caps.len()..(i + 1) * caps.len()].copy_from_slice(caps);
+                }
+            }
+        }
+    }
+}
+
+fn look_matches(look: Look, haystack: &[u8], pos: usize) -> bool {
+    match look {
+        Look::Start => pos == 0,
+        Look::End => pos == haystack.len(),
+        Look::LineStart => utf8::is_line_start(haystack, pos),
+        Look::LineEnd => utf8::is_line_end(haystack, pos),
+        Look::WordBoundary | Look::NotWordBoundary => {
+            let before = utf8::decode_last(haystack, pos).is_some_and(|(c, _)| utf8::is_word_char(c));
+            let after = utf8::decode(haystack, pos).is_some_and(|(c, _)| utf8::is_word_char(c));
+            (before != after) == (look == Look::WordBoundary)
+        }
+        Look::NoWordBefore => !utf8::decode_last(haystack, pos).is_some_and(|(c, _)| utf8::is_word_char(c)),
+        Look::NoWordAfter => !utf8::decode(haystack, pos).is_some_and(|(c, _)| utf8::is_word_char(c)),
+    }
+}
+
+impl crate::search::Matcher for Regex {
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        self.find_at(line, at)
+    }
+
+    fn find_nonempty_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        self.find(line, at, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, haystack: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern).unwrap().find_at(haystack.as_bytes(), 0)
+    }
+
+    #[test]
+    fn syntax() {
+        assert_eq!(Some((3, 6)), find("[0-9]+", "abc123def"));
+        assert_eq!(Some((0, 3)), find(r"^\w+", "foo bar"));
+        assert_eq!(None, find(r"^bar", "foo bar"));
+        assert_eq!(Some((4, 7)), find(r"bar$", "foo bar"));
+        assert_eq!(Some((4, 7)), find("cat|dog", "hot dog"));
+        assert_eq!(Some((0, 4)), find("(ab){2,3}", "ababx"));
+        assert_eq!(Some((0, 2)), find("a.?", "ab"));
+        assert_eq!(Some((5, 8)), find(r"\bcat\b", "cat_ cat"));
+        assert_eq!(Some((0, 7)), find("[[:alpha:]]+", "中文a1"));
+        assert_eq!(Some((0, 1)), find("a+?", "aaa"));
+    }
+
+    #[test]
+    fn captures_and_case() {
+        let re = Regex::new(r"(?P<key>\w+)=(\d+)").unwrap();
+        let caps = re.captures_at(b"x: port=8080", 0).unwrap();
+        assert_eq!(Some((3, 7)), caps.get(1));
+        assert_eq!(Some((8, 12)), caps.get(2));
+        assert_eq!(Some(1), re.capture_index("key"));
+        // find_at 只记录 0 号组，结果和完整的捕获相同；两者交替使用同一份工作内存
+        let short = Regex::new("(a)|b").unwrap();
+        assert_eq!(Some((3, 12)), re.find_at(b"x: port=8080", 0));
+        assert_eq!(Some((1, 2)), short.find_at(b"xb", 0));
+        assert_eq!(Some((8, 12)), re.captures_at(b"x: port=8080", 0).unwrap().get(2));
+        assert_eq!(None, short.captures_at(b"xba", 0).unwrap().get(1));
+        assert_eq!(Some((2, 3)), short.captures_at(b"xba", 2).unwrap().get(1));
+
+        let options = RegexOptions { case_insensitive: true, ..Default::default() };
+        let re = Regex::with_options("hello [a-c]", &options).unwrap();
+        assert!(re.is_match(b"HeLLo B"));
+        let re = Regex::with_options("straße|kσ", &options).unwrap();
+        assert_eq!(Some((0, 8)), re.find_at("STRAẞE".as_bytes(), 0));
+        assert_eq!(Some((0, 5)), re.find_at("\u{212A}ς".as_bytes(), 0));
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let word = RegexOptions { whole_word: true, ..Default::default() };
+        let re = Regex::with_options("foo|foo.b", &word).unwrap();
+        // 最左边的候选 "foo.b" 后面紧跟着字母，退而选择同一起点的 "foo"
+        assert_eq!(Some((0, 3)), re.find_at(b"foo.bar", 0));
+        assert_eq!(None, re.find_at("foo_ éfoo".as_bytes(), 0));
+        // 和 \b 不同，以非单词字符开头的模式前面是空格也可以匹配
+        assert_eq!(Some((2, 4)), Regex::with_options("-v", &word).unwrap().find_at(b"a -v", 0));
+
+        let line = RegexOptions { whole_line: true, ..Default::default() };
+        let re = Regex::with_options("a|ab", &line).unwrap();
+        assert_eq!(Some((0, 2)), re.find_at(b"ab", 0));
+        assert_eq!(None, re.find_at(b"abc", 0));
+
+        // 多行输入中 ^ 和 $ 匹配每一行的行首和行尾（包括 \r\n 之前）
+        let multi = RegexOptions { multi_line: true, ..Default::default() };
+        let re = Regex::with_options(r"^b\w*$\n^c", &multi).unwrap();
+        assert_eq!(Some((4, 9)), re.find_at(b"abc\nbcd\ncd", 0));
+        assert_eq!(Some((4, 7)), Regex::with_options(r"^b\w*$", &multi).unwrap().find_at(b"abc\nbcd\r\ncd", 0));
+        let re = Regex::with_options("a|ab", &RegexOptions { whole_line: true, ..multi }).unwrap();
+        assert_eq!(Some((4, 6)), re.find_at(b"abc\nab\n", 0));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let kind = |p| Regex::new(p).unwrap_err().kind();
+        assert_eq!(ErrorKind::UnclosedGroup, kind("(ab"));
+        assert_eq!(ErrorKind::UnmatchedParen, kind("ab)"));
+        assert_eq!(ErrorKind::UnclosedClass, kind("[ab"));
+        assert_eq!(ErrorKind::NothingToRepeat, kind("*a"));
+        assert_eq!(ErrorKind::InvalidRange, kind("[z-a]"));
+        assert_eq!(ErrorKind::InvalidRepetition, kind("a{3,1}"));
+    }
+}
@@ -0,0 +1,248 @@
+// 搜索并替换：--replace 的模板、--in-place 的原子改写以及 --dry-run 的 diff 预览。
+//
+// 模板中可以引用捕获组：$1、${1}、$name、${name}，$0 为整个匹配，$$ 表示一个 '$'。
+// 非正则模式下只有 $0。不存在的组在解析模板时就会报错，而不是悄悄地替换成空字符串。
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+use crate::regex::Captures;
+use crate::search::Span;
+
+/// 一行中的一个匹配的各个捕获组的位置
+pub(crate) type FindCaptures<'f> = dyn Fn(&[u8], Span) -> Option<Captures> + 'f;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(Vec<u8>),
+    Group(usize),
+}
+
+/// 解析好的替换模板
+#[derive(Debug, Clone)]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// 解析模板，`group` 把组的名字（或者数字）解析为组的序号，组不存在时返回 None
+    pub(crate) fn new(template: &str, group: impl Fn(&str) -> Option<usize>) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = Vec::new();
+        let mut rest = template;
+        while let Some(i) = rest.find('$') {
+            literal.extend_from_slice(&rest.as_bytes()[..i]);
+            let after = &rest[i + 1..];
+            // ${name} 可以紧跟其他字符，$name 取尽可能长的由字母、数字和 '_' 组成的名字
+            let (name, next) = if let Some(next) = after.strip_prefix('$') {
+                literal.push(b'$');
+                rest = next;
+                continue;
+            } else if let Some(braced) = after.strip_prefix('{').filter(|b| b.contains('}')) {
+                let end = braced.find('}').unwrap_or_default();
+                (&braced[..end], &braced[end + 1..])
+            } else {
+                let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            };
+            if name.is_empty() {
+                // 后面不是组名的 '$' 按原样保留
+                literal.push(b'$');
+                rest = after;
+                continue;
+            }
+            let index = group(name).ok_or_else(|| format!("invalid replacement: no capture group '{}'", name))?;
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(Part::Group(index));
+            rest = next;
+        }
+        literal.extend_from_slice(rest.as_bytes());
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// 按一次匹配展开模板，追加到 out；没有参与匹配的组展开为空
+    pub(crate) fn expand(&self, line: &[u8], caps: &Captures, out: &mut Vec<u8>) {
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.extend_from_slice(text),
+                Part::Group(i) => {
+                    if let Some((start, end)) = caps.get(*i) {
+                        out.extend_from_slice(&line[start..end]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 把一行中的匹配 `spans`（由 `search::find_all` 找出，空匹配也要替换）替换为模板展开的结果，
+/// 返回替换后的行以及替换文本在其中的区间（用于高亮）
+pub(crate) fn replace_all(line: &[u8], spans: &[Span], captures: &FindCaptures, template: &Template) -> (Vec<u8>, Vec<Span>) {
+    let mut replaced = Vec::with_capacity(line.len());
+    let mut replaced_spans = Vec::new();
+    let mut copied = 0;
+    for &span in spans {
+        let Some(caps) = captures(line, span) else { continue };
+        replaced.extend_from_slice(&line[copied..span.start]);
+        let start = replaced.len();
+        template.expand(line, &caps, &mut replaced);
+        replaced_spans.push(Span { start, end: replaced.len() });
+        copied = span.end;
+    }
+    replaced.extend_from_slice(&line[copied..]);
+    (replaced, replaced_spans)
+}
+
+/// 替换后内容发生了变化的一行。`old` 和 `new` 都不包括行尾的换行符
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Change {
+    pub line_number: usize,
+    pub byte_offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// 把各行的改动应用到整个文件的内容上，`changes` 按行号从小到大排列。换行符（包括 "\r\n"）保持不变
+pub(crate) fn apply(contents: &[u8], changes: &[Change]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(contents.len());
+    let mut copied = 0;
+    for change in changes {
+        result.extend_from_slice(&contents[copied..change.byte_offset]);
+        result.extend_from_slice(&change.new);
+        copied = change.byte_offset + change.old.len();
+    }
+    result.extend_from_slice(&contents[copied..]);
+    result
+}
+
+/// 以统一 diff 格式（unified diff，不带上下文）输出改动，可以直接交给 patch -p0 使用。
+/// 相邻的改动行合并成一个块；模板中含有换行符时新的一侧会比旧的一侧多出几行，-U 时旧的一侧也可能是多行。
+pub(crate) fn write_diff(out: &mut dyn Write, name: &str, changes: &[Change]) -> io::Result<()> {
+    let lines = |text: &[u8]| text.iter().filter(|&&b| b == b'\n').count() + 1;
+    writeln!(out, "--- {}\n+++ {}", name, name)?;
+    // 前面的块让新文件多出（或少了）的行数
+    let mut shift: isize = 0;
+    let mut rest = changes;
+    while let Some(first) = rest.first() {
+        // 一个改动紧接着上一个改动的最后一行时属于同一个块
+        let mut next_line = first.line_number;
+        let len = rest
+            .iter()
+            .take_while(|change| {
+                let adjacent = change.line_number == next_line;
+                next_line = change.line_number + lines(&change.old);
+                adjacent
+            })
+            .count();
+        let (hunk, next) = rest.split_at(len);
+        let old_lines: usize = hunk.iter().map(|c| lines(&c.old)).sum();
+        let new_lines: usize = hunk.iter().map(|c| lines(&c.new)).sum();
+        let new_start = first.line_number as isize + shift;
+        writeln!(out, "@@ -{},{} +{},{} @@", first.line_number, old_lines, new_start, new_lines)?;
+        for (sign, text) in hunk.iter().map(|c| (b"-", &c.old)).chain(hunk.iter().map(|c| (b"+", &c.new))) {
+            for line in text.split(|&b| b == b'\n') {
+                out.write_all(sign)?;
+                out.write_all(line)?;
+                out.write_all(b"\n")?;
+            }
+        }
+        shift += new_lines as isize - old_lines as isize;
+        rest = next;
+    }
+    Ok(())
+}
+
+/// 原子地改写文件：先把新内容完整写入同一目录下的临时文件，再重命名覆盖原文件。
+/// 这样其他进程要么看到旧内容，要么看到新内容；中途出错时原文件保持不变。原文件的权限会被保留。
+/// 符号链接改写的是它指向的文件，链接本身保持不变
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    // 直接重命名到链接上会把链接替换成普通文件，而它指向的文件没有变化
+    let path = &fs::canonicalize(path)?;
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let temp = dir.join(format!(".{}.minigrep-{}.tmp", name.to_string_lossy(), process::id()));
+    let permissions = fs::metadata(path)?.permissions();
+    let result = OpenOptions::new().write(true).create_new(true).open(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.set_permissions(permissions)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regex::Regex;
+    use crate::search::find_all;
+    use crate::tests::TempDir;
+
+    #[test]
+    fn expands_capture_groups() {
+        let re = Regex::new(r"(?P<key>\w+)=(\d+)").unwrap();
+        let group = |name: &str| match name.parse::<usize>() {
+            Ok(i) => Some(i).filter(|&i| i < re.captures_len()),
+            Err(_) => re.capture_index(name),
+        };
+        let replace = |re: &Regex, line: &[u8], template: &Template| {
+            let spans = find_all(re, line);
+            replace_all(line, &spans, &|line, span: Span| re.captures_at(line, span.start), template)
+        };
+
+        let template = Template::new("${2}_$key $$1 $", group).unwrap();
+        let (line, spans) = replace(&re, b"a=1, bb=22;", &template);
+        assert_eq!("1_a $1 $, 22_bb $1 $;", String::from_utf8(line).unwrap());
+        assert_eq!(vec![Span { start: 0, end: 8 }, Span { start: 10, end: 20 }], spans);
+
+        // 可以匹配空串的模式：和 sed 's/a*/<&>/g' 一样，每个空匹配都替换，紧跟在 "a" 后面的空匹配除外
+        let (line, _) = replace(&Regex::new("a*").unwrap(), b"x_cbB xax_", &Template::new("<$0>", |_| Some(0)).unwrap());
+        assert_eq!("<>x<>_<>c<>b<>B<> <>x<a>x<>_<>", String::from_utf8(line).unwrap());
+
+        assert!(Template::new("$3", group).is_err());
+        assert!(Template::new("$1x", group).is_err());
+    }
+
+    #[test]
+    fn applies_changes_and_writes_diff() {
+        let contents = b"one\r\ntwo\nthree\nfour\n";
+        let change = |line_number, byte_offset, old: &str, new: &str| Change {
+            line_number,
+            byte_offset,
+            old: old.into(),
+            new: new.into(),
+        };
+        let changes = [change(1, 0, "one", "1"), change(2, 5, "two", "2\n2"), change(4, 15, "four", "4")];
+        assert_eq!(&b"1\r\n2\n2\nthree\n4\n"[..], &apply(contents, &changes)[..]);
+
+        let mut diff = Vec::new();
+        write_diff(&mut diff, "a.txt", &changes).unwrap();
+        assert_eq!(
+            "--- a.txt\n+++ a.txt\n@@ -1,2 +1,3 @@\n-one\n-two\n+1\n+2\n+2\n@@ -4,1 +5,1 @@\n-four\n+4\n",
+            String::from_utf8(diff).unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_symlinks() {
+        let dir = TempDir::new("symlink");
+        let (real, link) = (dir.path().join("real.txt"), dir.path().join("link.txt"));
+        fs::write(&real, "foo\n").unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        write_atomic(&link, b"bar\n").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!("bar\n", fs::read_to_string(&real).unwrap());
+    }
+}
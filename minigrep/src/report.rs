@@ -0,0 +1,244 @@
+// --stats 与 --histogram：所有文件搜索完毕后输出的统计。
+//
+// --stats 输出搜索了多少文件和字节、选中了多少行、用了多少时间以及吞吐量。
+// --histogram 输出每个文件选中的行数，以及按行首的时间戳归到每个小时的行数。
+// 时间戳支持 ISO 8601（2026-10-17T14:03:00 或 2026-10-17 14:03:00）和 syslog（Oct 17 14:03:00）两种写法，
+// 可以用 '[' 括起来；行首没有时间戳的行不计入按小时的统计。
+// syslog 的时间戳没有年份，无法和带年份的时间排在一起，所以单独列出，其中只按月、日、小时排序。
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::json::Stats;
+use crate::search::{Context, Match, Sink, Summary};
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// 时间戳所在的小时。syslog 的时间戳没有年份，它们都排在带年份的之前
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Hour {
+    year: Option<u16>,
+    month: u8,
+    day: u8,
+    hour: u8,
+}
+
+impl fmt::Display for Hour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.year {
+            Some(year) => write!(f, "{:04}-{:02}-{:02} {:02}:00", year, self.month, self.day, self.hour),
+            None => write!(f, "{} {:2} {:02}:00", MONTHS[self.month as usize - 1], self.day, self.hour),
+        }
+    }
+}
+
+impl Hour {
+    /// 解析行首的时间戳
+    pub(crate) fn parse(line: &[u8]) -> Option<Hour> {
+        let line = line.strip_prefix(b"[").unwrap_or(line);
+        // 把 line 中 start..end 范围内的数字（可以用空格补齐）解析出来
+        let number = |start: usize, end: usize| -> Option<u16> {
+            let digits = line.get(start..end)?;
+            let digits = &digits[digits.iter().take_while(|&&b| b == b' ').count()..];
+            match !digits.is_empty() && digits.iter().all(u8::is_ascii_digit) {
+                true => std::str::from_utf8(digits).ok()?.parse().ok(),
+                false => None,
+            }
+        };
+        let (year, month, day, hour) = if line.get(4) == Some(&b'-') {
+            // 2026-10-17T14
+            if line.get(7) != Some(&b'-') || !matches!(line.get(10), Some(b'T' | b' ')) || line.get(13) != Some(&b':') {
+                return None;
+            }
+            (Some(number(0, 4)?), number(5, 7)?, number(8, 10)?, number(11, 13)?)
+        } else {
+            // Oct 17 14:
+            let month = MONTHS.iter().position(|m| line.starts_with(m.as_bytes()))? as u16 + 1;
+            if line.get(3) != Some(&b' ') || line.get(6) != Some(&b' ') || line.get(9) != Some(&b':') {
+                return None;
+            }
+            (None, month, number(4, 6)?, number(7, 9)?)
+        };
+        match (1..=12).contains(&month) && (1..=31).contains(&day) && hour < 24 {
+            true => Some(Hour { year, month: month as u8, day: day as u8, hour: hour as u8 }),
+            false => None,
+        }
+    }
+}
+
+/// --histogram 的统计数据
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Histogram {
+    /// 有选中行的文件和它们选中的行数，按输出的顺序排列
+    pub files: Vec<(String, usize)>,
+    pub hours: BTreeMap<Hour, usize>,
+}
+
+impl Histogram {
+    pub(crate) fn add(&mut self, other: &Histogram) {
+        self.files.extend(other.files.iter().cloned());
+        for (hour, count) in &other.hours {
+            *self.hours.entry(*hour).or_default() += count;
+        }
+    }
+}
+
+/// 转发给另一个 Sink，同时按小时数出选中的行
+pub(crate) struct Tally<'s> {
+    pub sink: &'s mut dyn Sink,
+    pub hours: BTreeMap<Hour, usize>,
+}
+
+impl Sink for Tally<'_> {
+    fn begin(&mut self, path: &str) -> io::Result<()> {
+        self.sink.begin(path)
+    }
+
+    fn matched(&mut self, m: &Match) -> io::Result<()> {
+        if let Some(hour) = Hour::parse(m.line) {
+            *self.hours.entry(hour).or_default() += 1;
+        }
+        self.sink.matched(m)
+    }
+
+    fn context(&mut self, c: &Context) -> io::Result<()> {
+        self.sink.context(c)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        self.sink.context_break()
+    }
+
+    fn end(&mut self, path: &str, summary: &Summary) -> io::Result<()> {
+        self.sink.end(path, summary)
+    }
+}
+
+/// --stats：和搜索结果之间空一行
+pub(crate) fn write_stats(out: &mut dyn Write, stats: &Stats, elapsed: Duration) -> io::Result<()> {
+    let seconds = elapsed.as_secs_f64();
+    writeln!(out)?;
+    writeln!(out, "{} files searched", stats.searches)?;
+    writeln!(out, "{} files contained matches", stats.searches_with_match)?;
+    writeln!(out, "{} matched lines", stats.matched_lines)?;
+    writeln!(out, "{} bytes searched", stats.bytes_searched)?;
+    writeln!(out, "{:.6} seconds", seconds)?;
+    // 用时太短（例如只搜索了空文件）时吞吐量没有意义
+    if seconds > 0.0 {
+        writeln!(out, "{:.2} MiB/s", stats.bytes_searched as f64 / seconds / (1024.0 * 1024.0))?;
+    }
+    Ok(())
+}
+
+// 一组带横条的计数，横条按其中最大的计数缩放
+fn write_bars(out: &mut dyn Write, title: &str, rows: &[(String, usize)]) -> io::Result<()> {
+    const WIDTH: usize = 40;
+    let max = rows.iter().map(|(_, count)| *count).max().unwrap_or(1);
+    let label = rows.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+    let digits = max.to_string().len();
+    writeln!(out, "{}:", title)?;
+    for (name, count) in rows {
+        let padding = label - name.chars().count();
+        let bar = (count * WIDTH).div_ceil(max);
+        writeln!(out, "  {}{}  {:>digits$} {}", name, " ".repeat(padding), count, "#".repeat(bar), digits = digits)?;
+    }
+    Ok(())
+}
+
+/// --histogram：和搜索结果之间空一行。按小时的统计分成带年份的和 syslog（没有年份）的两部分，
+/// 各自按时间排序；没有选中行带某一种时间戳时不输出那一部分
+pub(crate) fn write_histogram(out: &mut dyn Write, histogram: &Histogram) -> io::Result<()> {
+    writeln!(out)?;
+    write_bars(out, "matched lines per file", &histogram.files)?;
+    let (dated, syslog): (Vec<_>, Vec<_>) = histogram.hours.iter().partition(|(hour, _)| hour.year.is_some());
+    for (title, hours) in [("matched lines per hour", dated), ("matched lines per hour (syslog, year unknown)", syslog)] {
+        if !hours.is_empty() {
+            let rows: Vec<(String, usize)> = hours.into_iter().map(|(hour, count)| (hour.to_string(), *count)).collect();
+            write_bars(out, title, &rows)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{args, temp_files};
+    use crate::{Config, FileSearch};
+
+    #[test]
+    fn parses_leading_timestamps() {
+        let hour = |line: &str| Hour::parse(line.as_bytes()).map(|hour| hour.to_string());
+        assert_eq!(Some("2026-10-17 14:00".to_string()), hour("2026-10-17T14:03:59Z GET /"));
+        assert_eq!(Some("2026-10-17 09:00".to_string()), hour("[2026-10-17 09:00:00] ERROR"));
+        assert_eq!(Some("Oct  7 23:00".to_string()), hour("Oct  7 23:15:01 host sshd[1]: ok"));
+        assert_eq!(None, hour("2026-13-17 14:03 bad month"));
+        assert_eq!(None, hour("2026-10-17"));
+        assert_eq!(None, hour("Octopus 17 14:00"));
+        assert_eq!(None, hour("GET / 200"));
+    }
+
+    #[test]
+    fn writes_scaled_bars() {
+        let mut histogram = Histogram { files: vec![("a.log".to_string(), 2)], ..Default::default() };
+        let other = Histogram {
+            files: vec![("b/c.log".to_string(), 40)],
+            hours: vec![(Hour::parse(b"Oct 17 14:00:00").unwrap(), 3)].into_iter().collect(),
+        };
+        histogram.add(&other);
+        histogram.add(&other);
+        let mut out = Vec::new();
+        write_histogram(&mut out, &histogram).unwrap();
+        let expected = format!(
+            "\nmatched lines per file:\n  a.log     2 ##\n  b/c.log  40 {0}\n  b/c.log  40 {0}\nmatched lines per hour (syslog, year unknown):\n  Oct 17 14:00  6 {0}\n",
+            "#".repeat(40)
+        );
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn lists_syslog_hours_apart_from_dated_ones() {
+        let lines = ["Dec 31 23:59:00", "2026-10-17 14:03:00", "Jan  2 08:00:00", "2025-12-31T23:00:00", "Dec 31 23:01:00"];
+        let mut hours = BTreeMap::new();
+        for line in lines {
+            *hours.entry(Hour::parse(line.as_bytes()).unwrap()).or_default() += 1;
+        }
+        let mut out = Vec::new();
+        write_histogram(&mut out, &Histogram { files: Vec::new(), hours }).unwrap();
+        let bar = "#".repeat(40);
+        let expected = format!(
+            "\nmatched lines per file:\nmatched lines per hour:\n  2025-12-31 23:00  1 {0}\n  2026-10-17 14:00  1 {0}\n\
+             matched lines per hour (syslog, year unknown):\n  Jan  2 08:00  1 {1}\n  Dec 31 23:00  2 {0}\n",
+            bar,
+            "#".repeat(20)
+        );
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn stats_count_every_selected_line() {
+        let (_dir, paths) = temp_files("stats", &[("a.txt", b"fn a\nfn b\nlet c\nfn d\n"), ("b.txt", b"let e\n")]);
+        // 和 print_in_order 一样把各个文件的统计加起来，只比较输出中和用时无关的部分
+        let totals = |list: &[&str]| {
+            let search = FileSearch::new(Config::new(args(&[&["minigrep", "--no-config"], list].concat())).unwrap(), true).unwrap();
+            let mut stats = Stats::default();
+            for path in &paths {
+                stats.add(&search.file(path, &mut io::sink()).unwrap().1);
+            }
+            let mut out = Vec::new();
+            write_stats(&mut out, &stats, std::time::Duration::ZERO).unwrap();
+            (String::from_utf8(out).unwrap(), stats.histogram.files)
+        };
+        let expected = "\n2 files searched\n1 files contained matches\n3 matched lines\n27 bytes searched\n";
+        let files = vec![(paths[0].display().to_string(), 3)];
+        // -l 和 -L 找到第一行就能决定是否列出文件，但 --stats 和 --histogram 仍然要数出所有选中的行
+        for mode in ["-n", "-c", "-l", "-L"] {
+            let (text, histogram) = totals(&["--stats", "--histogram", mode, "fn"]);
+            assert!(text.starts_with(expected), "{}: {}", mode, text);
+            assert_eq!(files, histogram, "{}", mode);
+        }
+        assert!(totals(&["--stats", "-m", "2", "-l", "fn"]).0.contains("\n2 matched lines\n"));
+    }
+}
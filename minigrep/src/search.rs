@@ -0,0 +1,729 @@
+// 搜索引擎：逐行查找匹配，并把结果整理成带位置信息的匹配记录。
+// 具体“怎么在一行里找到匹配”由调用者以 Matcher 的形式传入（普通字符串、大小写不敏感、正则表达式、闭包……），
+// 这里只负责切分行、记录行号和字节偏移，收集一行中的所有匹配区间，以及挑选匹配行前后的上下文行。
+// 内容通过 BufRead 流式读取并按字节处理，不要求是合法的 UTF-8，也不需要把整个文件读进内存。
+// 只有 -U 例外：模式要在整个内容上匹配才能跨越换行符，这时会先把内容全部读进来。
+// UTF-16 和 Latin-1 的内容先转换成 UTF-8 再搜索，报告的字节偏移仍然是在原始内容中的位置。
+//
+// 结果交给 Sink 处理：命令行的文本输出和 --json 输出都只是 Sink 的一种实现，其他 crate 可以用
+// Searcher + 自己的 Sink 嵌入搜索，例如收集结果、统计或者转换成别的格式。
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::encoding::{Decoder, Encoding};
+use crate::utf8;
+
+/// 匹配在行内的字节区间 `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 一条匹配记录
+///
+/// # Examples
+///
+/// ```
+/// let contents = "first line\nsecond line";
+/// let results = chry_minigrep::search("line", contents);
+///
+/// assert_eq!(2, results[1].line_number);
+/// assert_eq!(11, results[1].byte_offset);
+/// assert_eq!(Some(8), results[1].column());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// 行号，从 1 开始
+    pub line_number: usize,
+    /// 行首在整个内容中的字节偏移，从 0 开始。内容经过转码时是在原始内容（包括 BOM）中的偏移
+    pub byte_offset: usize,
+    /// 行的内容（原始字节），不包含行尾的换行符。
+    /// 多行模式（`-U`）下是匹配经过的所有行，行与行之间的换行符保留在其中
+    pub line: &'a [u8],
+    /// 行内所有不重叠的匹配，按出现顺序排列；反向匹配（-v）选出的行没有匹配区间
+    pub spans: Vec<Span>,
+    /// 内容原来的编码。`line` 是转码后的 UTF-8，行内的位置要用它换算回原始内容
+    pub encoding: Encoding,
+}
+
+impl<'a> Match<'a> {
+    /// 第一个匹配的起始列（从 1 开始，按字节计）。内容经过转码时和 `byte_offset` 一样按原始内容的字节计
+    pub fn column(&self) -> Option<usize> {
+        self.spans.first().map(|span| self.encoding.original_len(&self.line[..span.start]) + 1)
+    }
+
+    /// `line` 中的位置 `at` 在原始内容中的字节偏移
+    pub fn offset_of(&self, at: usize) -> usize {
+        self.byte_offset + self.encoding.original_len(&self.line[..at])
+    }
+}
+
+/// 匹配行前后的上下文行（对应 `-A` / `-B` / `-C`）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Context<'a> {
+    pub line_number: usize,
+    pub byte_offset: usize,
+    pub line: &'a [u8],
+}
+
+/// 带上下文的搜索结果，按行的顺序排列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    Match(Match<'a>),
+    Context(Context<'a>),
+    /// 两组不相邻的行之间的分隔，grep 用 `--` 表示
+    Break,
+}
+
+/// 在一行中查找匹配的方式
+///
+/// 闭包 `Fn(&[u8], usize) -> Option<(usize, usize)>` 也实现了这个 trait；直接传入闭包时需要写明参数的类型，
+/// 例如 `|line: &[u8], at: usize| ...`，否则编译器推断不出它对任意生命周期的行都适用。
+///
+/// # Examples
+///
+/// ```
+/// use chry_minigrep::search::Matcher;
+///
+/// // 查找连续的数字
+/// struct Digits;
+///
+/// impl Matcher for Digits {
+///     fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+///         let start = at + line[at..].iter().position(u8::is_ascii_digit)?;
+///         let len = line[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+///         Some((start, start + len))
+///     }
+/// }
+///
+/// assert_eq!(Some((6, 9)), Digits.find_at(b"port: 443", 0));
+/// ```
+pub trait Matcher {
+    /// 在 `line` 中从 `at` 开始查找下一个匹配，返回其字节区间 `(start, end)`
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)>;
+
+    /// 和 `find_at` 相同，但不接受从 `at` 开始的空匹配。`find_at` 在某个位置给出空匹配时，
+    /// 如果这里能给出从同一位置开始的非空匹配（例如正则表达式 `|a` 遇到 "a"），就用非空的那个，和 grep 一致。
+    /// 默认实现返回 None，即总是使用 `find_at` 的结果
+    fn find_nonempty_at(&self, _line: &[u8], _at: usize) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// 读到 CSV 表头（`SearchOptions::csv_header`）时调用，表头本身不会交给 `find_at`。默认什么也不做
+    fn header(&self, _record: &[u8]) {}
+}
+
+impl<F: Fn(&[u8], usize) -> Option<(usize, usize)>> Matcher for F {
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        self(line, at)
+    }
+}
+
+// 去掉行尾的 \n 以及 \r\n 中的 \r
+fn trim_line(raw: &[u8]) -> &[u8] {
+    let line = raw.strip_suffix(b"\n").unwrap_or(raw);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+// CSV 记录的一段（quoted 为开始时是否在引号中）结束时是否在引号中。和 fields.rs 切分字段时一样，
+// 只有字段开头的引号才括起字段，其他位置的引号是普通字符；引号中的 "" 表示一个引号
+fn csv_quoted(bytes: &[u8], mut quoted: bool) -> bool {
+    let mut field_start = !quoted;
+    let mut bytes = bytes.iter().peekable();
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'"' if quoted => match bytes.peek() {
+                Some(b'"') => {
+                    bytes.next();
+                }
+                _ => quoted = false,
+            },
+            b'"' if field_start => quoted = true,
+            b',' if !quoted => {
+                field_start = true;
+                continue;
+            }
+            _ => {}
+        }
+        field_start = false;
+    }
+    quoted
+}
+
+// 一行（-U 时为整个内容）中依次出现的不重叠的匹配。和 grep 一样，空匹配之后从下一个字符继续查找，
+// 紧跟在上一个匹配后面的空匹配不算：a* 在 "xax" 中的匹配是开头和末尾的空串以及 "a"
+pub(crate) struct Matches<'m, 'l> {
+    matcher: &'m dyn Matcher,
+    line: &'l [u8],
+    at: usize,
+    last_end: Option<usize>,
+}
+
+impl<'m, 'l> Matches<'m, 'l> {
+    pub(crate) fn new(matcher: &'m dyn Matcher, line: &'l [u8]) -> Matches<'m, 'l> {
+        Matches { matcher, line, at: 0, last_end: None }
+    }
+}
+
+impl Iterator for Matches<'_, '_> {
+    type Item = Span;
+
+    fn next(&mut self) -> Option<Span> {
+        while self.at <= self.line.len() {
+            let (mut start, mut end) = self.matcher.find_at(self.line, self.at)?;
+            if start == end {
+                if let Some(longer) = self.matcher.find_nonempty_at(self.line, start).filter(|m| m.0 == start) {
+                    (start, end) = longer;
+                }
+            }
+            self.at = match start == end {
+                true => start + utf8::decode(self.line, start).map_or(1, |(_, len)| len),
+                false => end,
+            };
+            if start == end && self.last_end == Some(start) {
+                continue;
+            }
+            self.last_end = Some(end);
+            return Some(Span { start, end });
+        }
+        None
+    }
+}
+
+/// 找出一行中所有不重叠的匹配
+pub(crate) fn find_all(matcher: &dyn Matcher, line: &[u8]) -> Vec<Span> {
+    Matches::new(matcher, line).collect()
+}
+
+/// 控制逐行搜索行为的选项
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    /// 选出没有匹配的行（`-v`）
+    pub invert: bool,
+    /// 匹配行之前、之后输出的上下文行数（`-B` / `-A`）
+    pub before_context: usize,
+    pub after_context: usize,
+    /// 选出这么多行之后就停止搜索（`-m`），之后只会再输出最后一个匹配的后文
+    pub max_count: Option<usize>,
+    /// 检测二进制内容：一旦发现是二进制文件，第一次选中行时就停止搜索，不再输出任何行
+    pub detect_binary: bool,
+    /// 多行模式（`-U`）：在整个内容上查找匹配，一个匹配可以跨越多行。
+    /// 匹配经过的行合成一条记录，共享行的几个匹配也合在一起；`-m` 和选中的数量都按记录计算
+    pub multiline: bool,
+    /// 内容的编码。None 时按开头的 BOM 识别，没有 BOM 就按原样搜索；
+    /// UTF-16 和 Latin-1 的内容会先转换成 UTF-8，匹配区间是在转换后的行中的位置
+    pub encoding: Option<Encoding>,
+    /// 按 RFC 4180 切分 CSV 记录：双引号中的换行符不结束一条记录，一条记录的所有行作为一个整体交给
+    /// Matcher 和 Sink，行号是记录第一行的行号
+    pub csv: bool,
+    /// 第一条记录是 CSV 表头：它只交给 `Matcher::header`，既不会被选中（包括 `-v`），也不作为上下文输出
+    pub csv_header: bool,
+}
+
+/// 一次搜索的统计结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// 选中的行数
+    pub selected: usize,
+    /// 在二进制文件中找到了匹配（此时搜索在第一次选中时就已停止）
+    pub binary_match: bool,
+    /// 已经读取的（原始内容的）字节数，提前停止时不包括没有读到的部分
+    pub bytes_searched: usize,
+}
+
+// 判断是否为二进制内容的启发式规则和 grep 相同：包含 NUL 字节
+fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0)
+}
+
+/// 从 `reader` 中逐行读取并搜索，每产生一个匹配行、上下文行或分隔符就调用一次 `emit`。
+///
+/// 内容不要求是合法的 UTF-8，任意大小的输入都只需要保存当前行和最多 `before_context` 行前文。
+/// 相互重叠或紧挨着的上下文窗口会被合并，每行最多出现一次；不相邻的两组之间插入 [`Event::Break`]。
+///
+/// `emit` 返回错误（例如输出端已经关闭）时搜索立即停止并返回该错误。
+pub(crate) fn search_reader<R: BufRead>(
+    mut reader: R,
+    matcher: &dyn Matcher,
+    options: &SearchOptions,
+    emit: &mut dyn FnMut(Event) -> io::Result<()>,
+) -> io::Result<Summary> {
+    // BOM 本身不参与搜索，但仍然计入字节偏移
+    let (encoding, bom) = Encoding::detect(reader.fill_buf()?, options.encoding);
+    reader.consume(bom);
+    match encoding {
+        Encoding::Utf8 => search_decoded(reader, matcher, options, encoding, bom, emit),
+        _ => search_decoded(Decoder::new(reader, encoding), matcher, options, encoding, bom, emit),
+    }
+}
+
+// 搜索已经是 UTF-8（或者按原样处理）的内容。encoding 是原始内容的编码，用来把每行的长度换算回原始的字节数；
+// start 是原始内容中已经跳过的字节数
+fn search_decoded<R: BufRead>(
+    mut reader: R,
+    matcher: &dyn Matcher,
+    options: &SearchOptions,
+    encoding: Encoding,
+    start: usize,
+    emit: &mut dyn FnMut(Event) -> io::Result<()>,
+) -> io::Result<Summary> {
+    if options.multiline {
+        return search_multiline(reader, matcher, options, encoding, start, emit);
+    }
+    let (before, after) = (options.before_context, options.after_context);
+    let mut summary = Summary::default();
+    // 先看一眼缓冲区中的第一块数据，这样即使第一个匹配出现在 NUL 字节之前也能识别出二进制文件
+    let mut binary = options.detect_binary && looks_binary(reader.fill_buf()?);
+    // 还没有输出、可能会作为“前文”的行，最多保留 before 行；它们的内容必须复制出来，因为读缓冲会被覆盖
+    let mut pending: VecDeque<(usize, usize, Vec<u8>)> = VecDeque::with_capacity(before);
+    // 最近输出的一行的行号（CSV 记录为它最后一行的行号），用来判断下一行是否与之相邻
+    let mut last_line = None;
+    // 上一条 CSV 记录在第一行之后还占了几行
+    let mut extra_lines = 0;
+    // 还需要输出几行“后文”
+    let mut after_left = 0;
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+    let mut offset = start;
+
+    loop {
+        // 达到 -m 的上限后不再选择新的行，输出完剩余的后文就可以结束了
+        let limited = options.max_count.is_some_and(|max| summary.selected >= max);
+        if limited && after_left == 0 {
+            break;
+        }
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        // 行尾仍在引号中说明换行符属于一个被引号括起来的字段，记录还没有结束
+        let mut quoted = options.csv && csv_quoted(&buf, false);
+        while quoted {
+            let from = buf.len();
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                let message = format!("unterminated quoted field in the CSV record starting on line {}", line_number + 1 + extra_lines);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+            quoted = csv_quoted(&buf[from..], true);
+        }
+        line_number += 1 + extra_lines;
+        let byte_offset = offset;
+        offset += encoding.original_len(&buf);
+        let line = trim_line(&buf);
+        extra_lines = if options.csv { line.iter().filter(|&&b| b == b'\n').count() } else { 0 };
+        if options.detect_binary && !binary && looks_binary(line) {
+            binary = true;
+        }
+        if options.csv_header && line_number == 1 {
+            matcher.header(line);
+            continue;
+        }
+
+        let spans = if limited { Vec::new() } else { find_all(matcher, line) };
+        if !limited && spans.is_empty() == options.invert {
+            summary.selected += 1;
+            if binary {
+                summary.binary_match = true;
+                break;
+            }
+            let first = pending.front().map_or(line_number, |(number, _, _)| *number);
+            if (before > 0 || after > 0) && last_line.is_some_and(|last| first > last + 1) {
+                emit(Event::Break)?;
+            }
+            for (line_number, byte_offset, line) in pending.drain(..) {
+                emit(Event::Context(Context { line_number, byte_offset, line: &line }))?;
+            }
+            emit(Event::Match(Match { line_number, byte_offset, line, spans, encoding }))?;
+            last_line = Some(line_number + extra_lines);
+            after_left = after;
+        } else if after_left > 0 {
+            emit(Event::Context(Context { line_number, byte_offset, line }))?;
+            last_line = Some(line_number + extra_lines);
+            after_left -= 1;
+        } else if before > 0 {
+            // 复用被挤出窗口的那一行的缓冲区
+            let mut stored = match pending.len() == before {
+                true => pending.pop_front().map(|(_, _, stored)| stored).unwrap_or_default(),
+                false => Vec::new(),
+            };
+            stored.clear();
+            stored.extend_from_slice(line);
+            pending.push_back((line_number, byte_offset, stored));
+        }
+    }
+    summary.bytes_searched = offset;
+    Ok(summary)
+}
+
+// 多行模式：读入全部内容，在上面查找所有匹配，再把每个匹配换算成它经过的行
+fn search_multiline<R: BufRead>(
+    mut reader: R,
+    matcher: &dyn Matcher,
+    options: &SearchOptions,
+    encoding: Encoding,
+    start: usize,
+    emit: &mut dyn FnMut(Event) -> io::Result<()>,
+) -> io::Result<Summary> {
+    let mut contents = Vec::new();
+    reader.read_to_end(&mut contents)?;
+    let binary = options.detect_binary && looks_binary(&contents);
+    // 每一行行首的偏移；和逐行读取一样，结尾的换行符之后不算新的一行
+    let mut starts = vec![0];
+    starts.extend(contents.iter().enumerate().filter(|&(_, &b)| b == b'\n').map(|(i, _)| i + 1));
+    if starts.last() == Some(&contents.len()) {
+        starts.pop();
+    }
+    let line_of = |pos: usize| starts.partition_point(|&start| start <= pos) - 1;
+    let line_end = |i: usize| starts.get(i + 1).map_or(contents.len(), |&next| next);
+    let line = |i: usize| trim_line(&contents[starts[i]..line_end(i)]);
+    // 每一行行首在原始内容中的偏移，最后一项是原始内容的总长度
+    let mut origins = vec![start];
+    for i in 0..starts.len() {
+        origins.push(origins[i] + encoding.original_len(&contents[starts[i]..line_end(i)]));
+    }
+
+    // 选中的记录：(第一行, 最后一行, 匹配在内容中的区间)。二进制文件找到一条就够了
+    let limit = if binary { Some(1) } else { options.max_count };
+    let mut records: Vec<(usize, usize, Vec<Span>)> = Vec::new();
+    for Span { start, end } in Matches::new(matcher, &contents) {
+        // 文件末尾的空匹配不属于任何一行
+        if start == contents.len() && start == end {
+            break;
+        }
+        let first = line_of(start);
+        let last = line_of(end.max(start + 1) - 1).max(first);
+        let count = records.len();
+        match records.last_mut().filter(|record| first <= record.1) {
+            Some(record) => {
+                record.1 = record.1.max(last);
+                record.2.push(Span { start, end });
+            }
+            // 前一条记录已经完整，达到上限时它就是最后一条
+            None if !options.invert && limit == Some(count) => break,
+            None => records.push((first, last, vec![Span { start, end }])),
+        }
+    }
+    if options.invert {
+        // -v：选中没有被任何匹配经过的行，每行一条记录
+        let mut covered = vec![false; starts.len()];
+        for (first, last, _) in &records {
+            covered[*first..=*last].iter_mut().for_each(|c| *c = true);
+        }
+        records = (0..starts.len()).filter(|&i| !covered[i]).map(|i| (i, i, Vec::new())).collect();
+        if let Some(max) = limit {
+            records.truncate(max);
+        }
+    }
+
+    let (before, after) = (options.before_context, options.after_context);
+    let mut summary = Summary { bytes_searched: origins[starts.len()], ..Summary::default() };
+    let context = |i: usize| Event::Context(Context { line_number: i + 1, byte_offset: origins[i], line: line(i) });
+    // 下一个还没有输出的行
+    let mut next = 0;
+    for (first, last, spans) in records {
+        summary.selected += 1;
+        if binary {
+            summary.binary_match = true;
+            return Ok(summary);
+        }
+        // 上一条记录的后文，然后是这一条的前文
+        let after_end = match summary.selected {
+            1 => 0,
+            _ => first.min(next + after),
+        };
+        for i in next..after_end {
+            emit(context(i))?;
+        }
+        next = next.max(after_end);
+        let context_start = first.saturating_sub(before).max(next);
+        if (before > 0 || after > 0) && summary.selected > 1 && context_start > next {
+            emit(Event::Break)?;
+        }
+        for i in context_start..first {
+            emit(context(i))?;
+        }
+        let offset = starts[first];
+        let block = trim_line(&contents[offset..line_end(last)]);
+        let spans = spans
+            .into_iter()
+            // 匹配末尾的换行符不属于输出的内容
+            .map(|span| Span { start: (span.start - offset).min(block.len()), end: (span.end - offset).min(block.len()) })
+            .collect();
+        emit(Event::Match(Match { line_number: first + 1, byte_offset: origins[first], line: block, spans, encoding }))?;
+        next = last + 1;
+    }
+    if summary.selected > 0 {
+        for i in next..(next + after).min(starts.len()) {
+            emit(context(i))?;
+        }
+    }
+    Ok(summary)
+}
+
+/// 在内存中的 `contents` 里逐行搜索，只返回选中的行，忽略上下文选项。
+/// 返回的行直接借用 `contents`，所以内容总是按原样搜索，不会转码。
+pub(crate) fn search_lines<'a>(contents: &'a [u8], matcher: &dyn Matcher, options: &SearchOptions) -> Vec<Match<'a>> {
+    let options = SearchOptions { before_context: 0, after_context: 0, encoding: Some(Encoding::Utf8), ..*options };
+    let mut results = Vec::new();
+    // 事件中的行借用的是读缓冲，按字节偏移换回 contents 中的切片，返回值才能和 contents 的生命周期关联
+    let mut collect = |event: Event| {
+        if let Event::Match(m) = event {
+            let line = &contents[m.byte_offset..m.byte_offset + m.line.len()];
+            results.push(Match { line, ..m });
+        }
+        Ok(())
+    };
+    // 从切片读取不会出错，collect 也不会返回错误
+    search_reader(contents, matcher, &options, &mut collect).expect("searching a slice cannot fail");
+    results
+}
+
+/// 接收搜索结果。除了 `matched` 以外都有什么也不做的默认实现，按需要覆盖即可。
+///
+/// 任何一个方法返回错误（例如输出端已经关闭）时搜索立即停止，[`Searcher`] 把这个错误返回给调用者。
+pub trait Sink {
+    /// 开始搜索一个文件，`path` 是传给 [`Searcher`] 的名字
+    fn begin(&mut self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// 一个选中的行（`-v` 时为不匹配的行，此时没有匹配区间）
+    fn matched(&mut self, m: &Match) -> io::Result<()>;
+
+    /// 选中行前后的上下文行
+    fn context(&mut self, _c: &Context) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// 两组不相邻的行之间，grep 在这里输出 `--`
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// 一个文件搜索完毕
+    fn end(&mut self, _path: &str, _summary: &Summary) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 用一个 [`Matcher`] 逐行搜索，把结果交给 [`Sink`]。同一个 `Searcher` 可以搜索任意多个输入。
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use chry_minigrep::search::{Match, SearchOptions, Searcher, Sink};
+///
+/// // 只记录匹配行的行号
+/// struct LineNumbers(Vec<usize>);
+///
+/// impl Sink for LineNumbers {
+///     fn matched(&mut self, m: &Match) -> io::Result<()> {
+///         self.0.push(m.line_number);
+///         Ok(())
+///     }
+/// }
+///
+/// let find = |line: &[u8], at: usize| line[at..].iter().position(|&b| b == b'!').map(|i| (at + i, at + i + 1));
+/// let searcher = Searcher::new(&find, SearchOptions::default());
+/// let mut sink = LineNumbers(Vec::new());
+/// let summary = searcher.search_slice("notes", b"hi!\nhello\nbye!\n", &mut sink).unwrap();
+/// assert_eq!((vec![1, 3], 2), (sink.0, summary.selected));
+/// ```
+#[derive(Clone, Copy)]
+pub struct Searcher<'m> {
+    matcher: &'m dyn Matcher,
+    options: SearchOptions,
+}
+
+impl<'m> Searcher<'m> {
+    pub fn new(matcher: &'m dyn Matcher, options: SearchOptions) -> Searcher<'m> {
+        Searcher { matcher, options }
+    }
+
+    /// 搜索 `reader` 中的内容，`path` 只用于传给 [`Sink::begin`] 和 [`Sink::end`]
+    pub fn search_reader(&self, path: &str, reader: impl BufRead, sink: &mut dyn Sink) -> io::Result<Summary> {
+        sink.begin(path)?;
+        let summary = search_reader(reader, self.matcher, &self.options, &mut |event| match event {
+            Event::Match(m) => sink.matched(&m),
+            Event::Context(c) => sink.context(&c),
+            Event::Break => sink.context_break(),
+        })?;
+        sink.end(path, &summary)?;
+        Ok(summary)
+    }
+
+    /// 搜索内存中的内容
+    pub fn search_slice(&self, path: &str, contents: &[u8], sink: &mut dyn Sink) -> io::Result<Summary> {
+        self.search_reader(path, contents, sink)
+    }
+
+    /// 打开并搜索一个文件
+    pub fn search_path(&self, path: &Path, sink: &mut dyn Sink) -> io::Result<Summary> {
+        let file = File::open(path)?;
+        self.search_reader(&path.display().to_string(), BufReader::new(file), sink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 返回一个在行中查找 needle 的 Matcher 闭包
+    fn finder(needle: &'static str) -> impl Fn(&[u8], usize) -> Option<(usize, usize)> {
+        move |line, at| {
+            line[at..]
+                .windows(needle.len())
+                .position(|w| w == needle.as_bytes())
+                .map(|i| (at + i, at + i + needle.len()))
+        }
+    }
+
+    // 把事件简化成 "行号:" / "行号-" / "--"，和 grep 的输出格式一致
+    fn render(contents: &[u8], options: &SearchOptions) -> (Vec<String>, Summary) {
+        let mut events = Vec::new();
+        let summary = search_reader(contents, &finder("hit"), options, &mut |event| {
+            events.push(match event {
+                Event::Match(m) => format!("{}:", m.line_number),
+                Event::Context(c) => format!("{}-", c.line_number),
+                Event::Break => "--".to_string(),
+            });
+            Ok(())
+        })
+        .unwrap();
+        (events, summary)
+    }
+
+    #[test]
+    fn records_positions_of_every_match() {
+        let contents = b"ab ab\r\n\ncab\n";
+        let results = search_lines(contents, &finder("ab"), &SearchOptions::default());
+
+        assert_eq!(2, results.len());
+        assert_eq!((1, 0, &b"ab ab"[..]), (results[0].line_number, results[0].byte_offset, results[0].line));
+        assert_eq!(vec![Span { start: 0, end: 2 }, Span { start: 3, end: 5 }], results[0].spans);
+        assert_eq!((3, 8, Some(2)), (results[1].line_number, results[1].byte_offset, results[1].column()));
+
+        let inverted = search_lines(contents, &finder("ab"), &SearchOptions { invert: true, ..Default::default() });
+        assert_eq!(vec![(2, &b""[..])], inverted.iter().map(|m| (m.line_number, m.line)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn merges_context_windows() {
+        let contents = b"1\n2 hit\n3\n4 hit\n5\n6\n7\n8\n9 hit\n10";
+
+        let context = |before, after| SearchOptions { before_context: before, after_context: after, ..Default::default() };
+        let (events, summary) = render(contents, &context(1, 1));
+        assert_eq!(vec!["1-", "2:", "3-", "4:", "5-", "--", "8-", "9:", "10-"], events);
+        assert_eq!(3, summary.selected);
+
+        // 两个窗口之间恰好没有空隙时也不需要分隔符
+        let (events, _) = render(contents, &context(0, 4));
+        assert_eq!(vec!["2:", "3-", "4:", "5-", "6-", "7-", "8-", "9:", "10-"], events);
+
+        // -m 1：选中一行后停止，但仍然输出它的后文（即使后文中也有匹配）
+        let options = SearchOptions { max_count: Some(1), ..context(0, 2) };
+        assert_eq!(vec!["2:", "3-", "4-"], render(contents, &options).0);
+    }
+
+    #[test]
+    fn multiline_matches_span_lines() {
+        let contents = b"1\n2 hi\r\nt 3\n4\n5 hit\n6 hit\n7\n";
+        let multiline = SearchOptions { multiline: true, ..Default::default() };
+        // 跨越第 2、3 行的匹配和同在第 3 行的另一个匹配合成一条记录
+        let results = search_lines(contents, &finder("hi\r\nt"), &multiline);
+        assert_eq!(1, results.len());
+        assert_eq!((2, 2, &b"2 hi\r\nt 3"[..]), (results[0].line_number, results[0].byte_offset, results[0].line));
+        assert_eq!(vec![Span { start: 2, end: 7 }], results[0].spans);
+
+        // 匹配以换行符结尾时不包括下一行，区间也不超出记录的内容
+        let results = search_lines(contents, &finder("3\n"), &multiline);
+        assert_eq!((3, vec![Span { start: 2, end: 3 }]), (results[0].line_number, results[0].spans.clone()));
+
+        let (events, summary) = render(contents, &SearchOptions { before_context: 1, after_context: 1, ..multiline });
+        assert_eq!(vec!["4-", "5:", "6:", "7-"], events);
+        assert_eq!(2, summary.selected);
+        let (events, _) = render(contents, &SearchOptions { invert: true, max_count: Some(2), ..multiline });
+        assert_eq!(vec!["1:", "2:"], events);
+        let results = search_lines(contents, &finder("hit\n6"), &SearchOptions { max_count: Some(1), ..multiline });
+        assert_eq!((5, &b"5 hit\n6 hit"[..]), (results[0].line_number, results[0].line));
+    }
+
+    #[test]
+    fn csv_records_span_quoted_line_breaks() {
+        let contents = b"id,note\n1,\"a \"\"hit\"\"\nstill 1\"\n2,x\n3,\"hit\r\n\"\n";
+        let csv = SearchOptions { csv: true, ..Default::default() };
+        let results = search_lines(contents, &finder("hit"), &csv);
+        assert_eq!(
+            vec![(2, 8, &b"1,\"a \"\"hit\"\"\nstill 1\""[..]), (5, 34, &b"3,\"hit\r\n\""[..])],
+            results.iter().map(|m| (m.line_number, m.byte_offset, m.line)).collect::<Vec<_>>()
+        );
+        // 相邻按记录的最后一行计算：第 4 行紧接着第 2、3 行的记录
+        let (events, _) = render(contents, &SearchOptions { after_context: 1, ..csv });
+        assert_eq!(vec!["2:", "4-", "5:"], events);
+        // 表头既不会被 -v 选中，也不作为上下文
+        let header = SearchOptions { csv_header: true, ..csv };
+        let results = search_lines(contents, &finder("x"), &SearchOptions { invert: true, ..header });
+        assert_eq!(vec![2, 5], results.iter().map(|m| m.line_number).collect::<Vec<_>>());
+        let (events, _) = render(contents, &SearchOptions { before_context: 1, ..header });
+        assert_eq!(vec!["2:", "4-", "5:"], events);
+
+        // 不在字段开头的引号是普通字符，不会把后面的记录都并进来
+        let contents = b"id,size,status\n1,5\" pipe,200\n2,3,500\n3,\"4\"\"\",500\n";
+        let results = search_lines(contents, &finder("500"), &csv);
+        assert_eq!(vec![3, 4], results.iter().map(|m| m.line_number).collect::<Vec<_>>());
+        // 直到文件末尾都没有闭合的引号是错误
+        let error = search_reader(&b"id,note\n1,ok\n2,\"open\n3,x\n"[..], &finder("x"), &csv, &mut |_| Ok(())).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert!(error.to_string().ends_with("starting on line 3"), "{}", error);
+    }
+
+    #[test]
+    fn transcodes_and_reports_original_offsets() {
+        // (字节偏移, 行, 匹配区间)
+        let collect = |contents: &[u8], options: &SearchOptions| {
+            let mut results = Vec::new();
+            search_reader(contents, &finder("hit"), options, &mut |event| {
+                if let Event::Match(m) = event {
+                    results.push((m.byte_offset, String::from_utf8(m.line.to_vec()).unwrap(), m.spans));
+                }
+                Ok(())
+            })
+            .map(|summary| (results, summary.bytes_searched))
+            .unwrap()
+        };
+        let mut contents = vec![0xFF, 0xFE];
+        contents.extend("1 ok\r\n2 hit é\n3 hit\n".encode_utf16().flat_map(u16::to_le_bytes));
+        // 行首偏移包括 BOM，按 UTF-16 的字节计算；行的内容和匹配区间是 UTF-8 的
+        let (results, searched) = collect(&contents, &SearchOptions::default());
+        assert_eq!(vec![(14, "2 hit é".to_string(), vec![Span { start: 2, end: 5 }]), (30, "3 hit".to_string(), vec![Span { start: 2, end: 5 }])], results);
+        assert_eq!(contents.len(), searched);
+        let multiline = SearchOptions { multiline: true, ..Default::default() };
+        assert_eq!(vec![14, 30], collect(&contents, &multiline).0.iter().map(|r| r.0).collect::<Vec<_>>());
+
+        // 没有 BOM 时需要指定编码，否则 UTF-16 的内容因为含有 NUL 会被当作二进制
+        let utf16le = SearchOptions { encoding: Some(Encoding::Utf16Le), ..Default::default() };
+        assert_eq!(vec![12, 28], collect(&contents[2..], &utf16le).0.iter().map(|r| r.0).collect::<Vec<_>>());
+        let latin1 = SearchOptions { encoding: Some(Encoding::Latin1), ..Default::default() };
+        assert_eq!(vec![(5, "naïve hit".to_string(), vec![Span { start: 7, end: 10 }])], collect(b"caf\xe9\nna\xefve hit\n", &latin1).0);
+    }
+
+    #[test]
+    fn stops_at_first_match_in_binary_content() {
+        let options = SearchOptions { detect_binary: true, ..Default::default() };
+        let (events, summary) = render(b"1 hit\n2 \0\n3 hit\n", &options);
+        assert!(events.is_empty());
+        assert_eq!(Summary { selected: 1, binary_match: true, bytes_searched: 6 }, summary);
+
+        // 非法的 UTF-8 不算二进制；没有开启检测时 NUL 也当作普通字符
+        let (events, summary) = render(b"1 \xffhit\n", &options);
+        assert_eq!((vec!["1:".to_string()], false), (events, summary.binary_match));
+        let (events, _) = render(b"1 hit\0\n", &SearchOptions::default());
+        assert_eq!(vec!["1:"], events);
+    }
+}
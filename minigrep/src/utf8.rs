@@ -0,0 +1,80 @@
+// 按字节处理文本时使用的 UTF-8 小工具。
+// 搜索的内容不一定是合法的 UTF-8（日志里常混有乱码），所以匹配引擎直接工作在 &[u8] 上，
+// 需要字符语义时用这里的函数就地解码：非法字节按单字节的 U+FFFD 处理，保证位置始终对应原始字节。
+
+/// 解码 `bytes[at..]` 开头的一个字符，返回字符及其占用的字节数。
+///
+/// 遇到非法或截断的 UTF-8 序列时返回 `(char::REPLACEMENT_CHARACTER, 1)`。
+pub(crate) fn decode(bytes: &[u8], at: usize) -> Option<(char, usize)> {
+    let b0 = *bytes.get(at)?;
+    if b0 < 0x80 {
+        return Some((b0 as char, 1));
+    }
+    // 根据首字节判断序列长度
+    let len = match b0 {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return Some((char::REPLACEMENT_CHARACTER, 1)),
+    };
+    match bytes.get(at..at + len).map(std::str::from_utf8) {
+        Some(Ok(s)) => s.chars().next().map(|c| (c, len)),
+        _ => Some((char::REPLACEMENT_CHARACTER, 1)),
+    }
+}
+
+/// 解码 `at` 之前的最后一个字符（用于单词边界等需要“回看”的判断）。
+pub(crate) fn decode_last(bytes: &[u8], at: usize) -> Option<(char, usize)> {
+    if at == 0 {
+        return None;
+    }
+    // 最多往回找 4 个字节，跳过续字节(0b10xxxxxx)找到首字节，再确认它恰好解码到 at 处
+    let lo = at.saturating_sub(4);
+    for start in (lo..at).rev() {
+        if bytes[start] & 0xC0 != 0x80 {
+            return match decode(bytes, start) {
+                Some((c, len)) if start + len == at => Some((c, len)),
+                _ => Some((char::REPLACEMENT_CHARACTER, 1)),
+            };
+        }
+    }
+    Some((char::REPLACEMENT_CHARACTER, 1))
+}
+
+/// 组成“单词”的字符：Unicode 字母、数字以及下划线。
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// `bytes[start..end]` 的前一个字符和后一个字符都不是单词字符（或者不存在），即它是一个完整的单词。
+///
+/// 这是 grep -w 的规则，和 `\b` 不同：匹配本身以非单词字符开头或结尾时也照样适用。
+pub(crate) fn is_whole_word(bytes: &[u8], start: usize, end: usize) -> bool {
+    let before = decode_last(bytes, start).is_some_and(|(c, _)| is_word_char(c));
+    let after = decode(bytes, end).is_some_and(|(c, _)| is_word_char(c));
+    !before && !after
+}
+
+/// `pos` 是否位于某一行的行首：内容的开头，或者紧跟在 `\n` 之后
+pub(crate) fn is_line_start(bytes: &[u8], pos: usize) -> bool {
+    pos == 0 || bytes[pos - 1] == b'\n'
+}
+
+/// `pos` 是否位于某一行的行尾：内容的末尾，或者紧挨在 `\n`、`\r\n` 之前
+pub(crate) fn is_line_end(bytes: &[u8], pos: usize) -> bool {
+    matches!(&bytes[pos..], [] | [b'\n', ..] | [b'\r', b'\n', ..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_mixed_bytes() {
+        let bytes = "a中".as_bytes();
+        assert_eq!(Some(('a', 1)), decode(bytes, 0));
+        assert_eq!(Some(('中', 3)), decode(bytes, 1));
+        assert_eq!(Some(('中', 3)), decode_last(bytes, 4));
+        assert_eq!(Some((char::REPLACEMENT_CHARACTER, 1)), decode(b"\xffa", 0));
+    }
+}
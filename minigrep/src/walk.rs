@@ -0,0 +1,392 @@
+// 递归遍历目录，收集需要搜索的文件。
+//     --include / --exclude 的 glob 模式决定哪些文件参与搜索
+//     遍历过程中遇到的 .gitignore 文件会被读取，忽略其中列出的文件和目录（以及 .git 目录本身）；
+//     从子目录开始遍历时，它上面直到仓库根目录（包含 .git 的目录）的各层 .gitignore 同样有效
+// 每个目录下的条目按文件名排序，保证输出顺序稳定。
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    Any,                           // ?  匹配除 '/' 以外的单个字符
+    Star,                          // *  匹配除 '/' 以外的任意个字符
+    DoubleStar,                    // ** 匹配任意个字符（可以跨目录）
+    DoubleStarSlash,               // **/ 匹配零个或多个目录
+    Class(Vec<(char, char)>, bool), // [a-z] [!abc]
+}
+
+/// gitignore 风格的 glob 模式
+///
+/// 不含 `/` 的模式只和文件名比较，例如 `*.rs`；含有 `/` 的模式和相对路径比较，例如 `src/**/*.rs`。
+/// 以 `/` 结尾的模式只匹配目录。
+///
+/// # Examples
+///
+/// ```
+/// use chry_minigrep::walk::Glob;
+///
+/// assert!(Glob::new("*.rs").is_match("src/lib.rs", false));
+/// assert!(Glob::new("src/**/*.rs").is_match("src/a/b/lib.rs", false));
+/// assert!(!Glob::new("target/").is_match("target", false));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl Glob {
+    /// 解析 glob 模式。不完整的 `[` 会被当作普通字符，因此解析总是成功的。
+    pub fn new(pattern: &str) -> Glob {
+        let mut pattern = pattern;
+        let dir_only = pattern.len() > 1 && pattern.ends_with('/');
+        if dir_only {
+            pattern = &pattern[..pattern.len() - 1];
+        }
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let token = match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    i += 1;
+                    if chars.get(i + 1) == Some(&'/') {
+                        i += 1;
+                        Token::DoubleStarSlash
+                    } else {
+                        Token::DoubleStar
+                    }
+                }
+                '*' => Token::Star,
+                '?' => Token::Any,
+                '[' => match parse_class(&chars[i + 1..]) {
+                    Some((token, len)) => {
+                        i += len;
+                        token
+                    }
+                    None => Token::Literal('['),
+                },
+                '\\' if i + 1 < chars.len() => {
+                    i += 1;
+                    Token::Literal(chars[i])
+                }
+                c => Token::Literal(c),
+            };
+            tokens.push(token);
+            i += 1;
+        }
+        Glob { tokens, anchored, dir_only }
+    }
+
+    /// 判断相对路径 `path`（以 `/` 分隔）是否匹配该模式，`is_dir` 表示该路径是否为目录
+    pub fn is_match(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let target = if self.anchored {
+            path
+        } else {
+            path.rsplit('/').next().unwrap_or(path)
+        };
+        let chars: Vec<char> = target.chars().collect();
+        match_tokens(&self.tokens, &chars)
+    }
+}
+
+// 解析 [...] 中的内容（不包含开头的 '['），返回 Class 以及消耗的字符数
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ']' && !first {
+            return Some((Token::Class(ranges, negated), i + 1));
+        }
+        first = false;
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&hi| hi != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    None
+}
+
+fn match_tokens(tokens: &[Token], s: &[char]) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return s.is_empty(),
+    };
+    match token {
+        Token::Literal(c) => s.first() == Some(c) && match_tokens(rest, &s[1..]),
+        Token::Any => s.first().is_some_and(|&c| c != '/') && match_tokens(rest, &s[1..]),
+        Token::Class(ranges, negated) => match s.first() {
+            Some(&c) if c != '/' => {
+                let hit = ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+                hit != *negated && match_tokens(rest, &s[1..])
+            }
+            _ => false,
+        },
+        Token::Star => {
+            // * 不能跨越 '/'
+            let limit = s.iter().position(|&c| c == '/').unwrap_or(s.len());
+            (0..=limit).any(|i| match_tokens(rest, &s[i..]))
+        }
+        Token::DoubleStar => (0..=s.len()).any(|i| match_tokens(rest, &s[i..])),
+        Token::DoubleStarSlash => (0..=s.len())
+            .filter(|&i| i == 0 || s[i - 1] == '/')
+            .any(|i| match_tokens(rest, &s[i..])),
+    }
+}
+
+/// 遍历时使用的过滤选项
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// 非空时，只有匹配其中任意一个模式的文件才会被搜索
+    pub include: Vec<Glob>,
+    /// 匹配其中任意一个模式的文件会被跳过
+    pub exclude: Vec<Glob>,
+}
+
+impl WalkOptions {
+    fn wants(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|g| g.is_match(path, false)))
+            && !self.exclude.iter().any(|g| g.is_match(path, false))
+    }
+}
+
+// 一个 .gitignore 文件中的规则，base 为该文件所在的目录。
+// 遍历起点之上的 .gitignore 的 base 是遍历的起点，prefix 是起点相对于该文件所在目录的路径
+struct Ignore {
+    base: PathBuf,
+    prefix: String,
+    rules: Vec<(Glob, bool)>, // bool 表示以 '!' 开头的反向规则
+}
+
+impl Ignore {
+    fn load(dir: &Path) -> Option<Ignore> {
+        let text = fs::read_to_string(dir.join(".gitignore")).ok()?;
+        let rules = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| match line.strip_prefix('!') {
+                Some(line) => (Glob::new(line), true),
+                None => (Glob::new(line.strip_prefix('\\').unwrap_or(line)), false),
+            })
+            .collect();
+        Some(Ignore { base: dir.to_path_buf(), prefix: String::new(), rules })
+    }
+
+    // 由外到内读取 root 之上直到仓库根目录的各层 .gitignore；root 不在 git 仓库中时一个也不读
+    fn ancestors(root: &Path) -> Vec<Ignore> {
+        let Ok(abs) = fs::canonicalize(root) else { return Vec::new() };
+        let mut ignores = Vec::new();
+        for dir in abs.ancestors() {
+            if dir != abs {
+                if let Some(ignore) = Ignore::load(dir) {
+                    let prefix = slash_path(abs.strip_prefix(dir).unwrap_or(&abs));
+                    ignores.push(Ignore { base: root.to_path_buf(), prefix, ..ignore });
+                }
+            }
+            if dir.join(".git").exists() {
+                ignores.reverse();
+                return ignores;
+            }
+        }
+        Vec::new()
+    }
+}
+
+// 由外到内依次检查各层 .gitignore，最后一条匹配的规则决定是否忽略
+fn is_ignored(ignores: &[Ignore], path: &Path, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for ignore in ignores {
+        let rel = match path.strip_prefix(&ignore.base) {
+            Ok(rel) if ignore.prefix.is_empty() => slash_path(rel),
+            Ok(rel) => format!("{}/{}", ignore.prefix, slash_path(rel)),
+            Err(_) => continue,
+        };
+        for (glob, negated) in &ignore.rules {
+            if glob.is_match(&rel, is_dir) {
+                ignored = !negated;
+            }
+        }
+    }
+    ignored
+}
+
+// 统一使用 '/' 作为路径分隔符，方便和 glob 比较
+fn slash_path(path: &Path) -> String {
+    let s = path.to_string_lossy();
+    if std::path::MAIN_SEPARATOR == '/' { s.into_owned() } else { s.replace(std::path::MAIN_SEPARATOR, "/") }
+}
+
+fn with_path(path: &Path, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+/// 展开命令行给出的路径：文件（以及表示标准输入的 `-`）直接返回，目录则递归遍历。
+///
+/// 结果按遍历顺序排列；无法读取的路径以 `Err` 的形式出现在相应位置，不会中断整个遍历。
+pub fn walk(paths: &[PathBuf], options: &WalkOptions) -> Vec<io::Result<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        // "-" 表示标准输入，原样交给调用者
+        if path.as_os_str() == "-" {
+            files.push(Ok(path.clone()));
+            continue;
+        }
+        match fs::metadata(path) {
+            Ok(meta) if meta.is_dir() => {
+                let mut ignores = Ignore::ancestors(path);
+                walk_dir(path, path, options, &mut ignores, &mut files);
+            }
+            Ok(_) => {
+                if options.wants(&slash_path(path)) {
+                    files.push(Ok(path.clone()));
+                }
+            }
+            Err(e) => files.push(Err(with_path(path, e))),
+        }
+    }
+    files
+}
+
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    options: &WalkOptions,
+    ignores: &mut Vec<Ignore>,
+    files: &mut Vec<io::Result<PathBuf>>,
+) {
+    let entries = match fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()) {
+        Ok(mut entries) => {
+            entries.sort_by_key(|entry| entry.file_name());
+            entries
+        }
+        Err(e) => {
+            files.push(Err(with_path(dir, e)));
+            return;
+        }
+    };
+
+    let pushed = match Ignore::load(dir) {
+        Some(ignore) => {
+            ignores.push(ignore);
+            true
+        }
+        None => false,
+    };
+
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let is_dir = match entry.file_type() {
+            Ok(t) if t.is_symlink() => {
+                // 不跟随指向目录的符号链接（避免循环），失效的链接也一并跳过
+                if fs::metadata(&path).map(|m| m.is_dir()).unwrap_or(true) {
+                    continue;
+                }
+                false
+            }
+            Ok(t) => t.is_dir(),
+            Err(e) => {
+                files.push(Err(with_path(&path, e)));
+                continue;
+            }
+        };
+        if is_ignored(ignores, &path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            walk_dir(root, &path, options, ignores, files);
+        } else if options.wants(&slash_path(path.strip_prefix(root).unwrap_or(&path))) {
+            files.push(Ok(path));
+        }
+    }
+
+    if pushed {
+        ignores.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempDir;
+
+    #[test]
+    fn glob_matching() {
+        assert!(Glob::new("*.log").is_match("logs/app.log", false));
+        assert!(!Glob::new("*.log").is_match("app.log.gz", false));
+        assert!(Glob::new("/build").is_match("build", true));
+        assert!(!Glob::new("/build").is_match("src/build", true));
+        assert!(Glob::new("**/test_*.rs").is_match("test_a.rs", false));
+        assert!(Glob::new("**/test_*.rs").is_match("a/b/test_a.rs", false));
+        assert!(Glob::new("file[0-9].txt").is_match("file7.txt", false));
+        assert!(!Glob::new("file[!0-9].txt").is_match("file7.txt", false));
+        assert!(Glob::new("docs/**").is_match("docs/a/b.md", false));
+    }
+
+    #[test]
+    fn walk_honours_gitignore_and_globs() {
+        let dir = TempDir::new("walk");
+        let root = dir.path().to_path_buf();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.tmp\n!keep.tmp\n").unwrap();
+        for file in &["src/lib.rs", "src/nested/a.rs", "src/notes.md", "target/out.rs", "x.tmp", "keep.tmp"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let names = |options: &WalkOptions| -> Vec<String> {
+            walk(std::slice::from_ref(&root), options)
+                .into_iter()
+                .map(|f| slash_path(f.unwrap().strip_prefix(&root).unwrap()))
+                .collect()
+        };
+
+        let all = names(&WalkOptions::default());
+        assert_eq!(vec![".gitignore", "keep.tmp", "src/lib.rs", "src/nested/a.rs", "src/notes.md"], all);
+
+        let options = WalkOptions { include: vec![Glob::new("*.rs")], exclude: vec![Glob::new("src/nested/*")] };
+        assert_eq!(vec!["src/lib.rs"], names(&options));
+    }
+    #[test]
+    fn walk_from_subdirectory_honours_parent_gitignore() {
+        let dir = TempDir::new("walk-nested");
+        let root = dir.path();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("src/sub")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n/src/sub/gen.rs\n").unwrap();
+        fs::write(root.join("src/.gitignore"), "!keep.log\n").unwrap();
+        for file in &["src/sub/e.log", "src/sub/keep.log", "src/sub/gen.rs", "src/sub/main.rs"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        // 上层的规则相对于它们各自所在的目录，下层的规则仍然可以覆盖它们
+        let start = root.join("src/sub");
+        let names: Vec<String> = walk(std::slice::from_ref(&start), &WalkOptions::default())
+            .into_iter()
+            .map(|f| slash_path(f.unwrap().strip_prefix(&start).unwrap()))
+            .collect();
+        assert_eq!(vec!["keep.log", "main.rs"], names);
+    }
+}
@@ -0,0 +1,143 @@
+// 作为外部 crate 使用搜索库：自定义 Matcher 和 Sink
+//
+// cargo test --test searcher
+
+use std::fs;
+use std::io;
+
+use chry_minigrep::aho_corasick::AhoCorasick;
+use chry_minigrep::regex::Regex;
+use chry_minigrep::{Context, Match, Matcher, SearchOptions, Searcher, Sink, Summary};
+
+// 把收到的回调记录成 grep 风格的文本，方便断言
+#[derive(Default)]
+struct Transcript(Vec<String>);
+
+impl Sink for Transcript {
+    fn begin(&mut self, path: &str) -> io::Result<()> {
+        self.0.push(format!("begin {}", path));
+        Ok(())
+    }
+
+    fn matched(&mut self, m: &Match) -> io::Result<()> {
+        let spans: Vec<String> = m.spans.iter().map(|s| format!("{}..{}", s.start, s.end)).collect();
+        self.0.push(format!("{}:{} [{}]", m.line_number, String::from_utf8_lossy(m.line), spans.join(" ")));
+        Ok(())
+    }
+
+    fn context(&mut self, c: &Context) -> io::Result<()> {
+        self.0.push(format!("{}-{}", c.line_number, String::from_utf8_lossy(c.line)));
+        Ok(())
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        self.0.push("--".to_string());
+        Ok(())
+    }
+
+    fn end(&mut self, path: &str, summary: &Summary) -> io::Result<()> {
+        self.0.push(format!("end {} selected={} bytes={}", path, summary.selected, summary.bytes_searched));
+        Ok(())
+    }
+}
+
+// 查找连续的 ASCII 数字
+struct Digits;
+
+impl Matcher for Digits {
+    fn find_at(&self, line: &[u8], at: usize) -> Option<(usize, usize)> {
+        let start = at + line[at..].iter().position(u8::is_ascii_digit)?;
+        let len = line[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+        Some((start, start + len))
+    }
+}
+
+#[test]
+fn sink_receives_every_callback_in_order() {
+    let re = Regex::new(r"err(or)?").unwrap();
+    let options = SearchOptions { before_context: 1, after_context: 1, ..Default::default() };
+    let searcher = Searcher::new(&re, options);
+    let mut sink = Transcript::default();
+    let contents = b"a\nerror: x\nb\nc\nd\nerr err\r\ne";
+    let summary = searcher.search_slice("log", contents, &mut sink).unwrap();
+    assert_eq!(
+        vec![
+            "begin log",
+            "1-a",
+            "2:error: x [0..5]",
+            "3-b",
+            "--",
+            "5-d",
+            "6:err err [0..3 4..7]",
+            "7-e",
+            "end log selected=2 bytes=27",
+        ],
+        sink.0
+    );
+    assert_eq!(2, summary.selected);
+
+    // 同一个 Searcher 可以继续搜索其他输入
+    let summary = searcher.search_reader("empty", &b""[..], &mut sink).unwrap();
+    assert_eq!(0, summary.selected);
+    assert_eq!(vec!["begin empty", "end empty selected=0 bytes=0"], sink.0[9..]);
+}
+
+#[test]
+fn custom_matchers_and_closures() {
+    let mut sink = Transcript::default();
+    let options = SearchOptions { invert: true, ..Default::default() };
+    Searcher::new(&Digits, options).search_slice("ports", b"port 80\nnone\n443\n", &mut sink).unwrap();
+    assert_eq!(vec!["begin ports", "2:none []", "end ports selected=1 bytes=17"], sink.0);
+
+    // 闭包和库里的其他匹配方式同样可以直接使用
+    let shout = |line: &[u8], at: usize| line[at..].iter().position(|&b| b == b'!').map(|i| (at + i, at + i + 1));
+    let mut sink = Transcript::default();
+    Searcher::new(&shout, SearchOptions::default()).search_slice("-", b"hi!\nhello", &mut sink).unwrap();
+    assert_eq!("1:hi! [2..3]", sink.0[1]);
+
+    let automaton = AhoCorasick::new(&["cat", "dog"], true);
+    let mut sink = Transcript::default();
+    let options = SearchOptions { max_count: Some(1), ..Default::default() };
+    let summary = Searcher::new(&automaton, options).search_slice("-", b"DOG and cat\ncat\n", &mut sink).unwrap();
+    assert_eq!((1, "1:DOG and cat [0..3 8..11]"), (summary.selected, sink.0[1].as_str()));
+}
+
+// 找到第一个匹配后就放弃：返回错误时搜索立即停止，错误原样返回给调用者
+struct FirstOnly(Option<usize>);
+
+impl Sink for FirstOnly {
+    fn matched(&mut self, m: &Match) -> io::Result<()> {
+        self.0 = Some(m.line_number);
+        Err(io::Error::new(io::ErrorKind::Interrupted, "found"))
+    }
+
+    fn end(&mut self, _path: &str, _summary: &Summary) -> io::Result<()> {
+        panic!("end must not be called after an error");
+    }
+}
+
+#[test]
+fn sink_errors_stop_the_search() {
+    let mut sink = FirstOnly(None);
+    let err = Searcher::new(&Digits, SearchOptions::default()).search_slice("-", b"a\n1\n2\n", &mut sink).unwrap_err();
+    assert_eq!((io::ErrorKind::Interrupted, Some(2)), (err.kind(), sink.0));
+}
+
+#[test]
+fn searches_files_by_path() {
+    let dir = std::env::temp_dir().join(format!("minigrep-searcher-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("ports.txt");
+    fs::write(&file, "http 80\nhttps 443\n").unwrap();
+
+    let searcher = Searcher::new(&Digits, SearchOptions::default());
+    let mut sink = Transcript::default();
+    let summary = searcher.search_path(&file, &mut sink).unwrap();
+    assert_eq!(2, summary.selected);
+    assert_eq!(format!("begin {}", file.display()), sink.0[0]);
+    assert_eq!("2:https 443 [6..9]", sink.0[2]);
+
+    let missing = searcher.search_path(&dir.join("missing.txt"), &mut sink).unwrap_err();
+    assert_eq!(io::ErrorKind::NotFound, missing.kind());
+    fs::remove_dir_all(&dir).unwrap();
+}